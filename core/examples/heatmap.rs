@@ -0,0 +1,47 @@
+//! Runs a ROM without a window and maps how it uses memory, e.g.
+//! `cargo run -p core --example heatmap -- roms/pong.ch8 pong.png 600`.
+//!
+//! Writes the heatmap as a PNG, 8 pixels per byte, and prints the usage of every byte.
+
+use core::chip8::Chip8;
+use core::heatmap::HeatmapBus;
+use core::Ram;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [rom, out, rest @ ..] = args.as_slice() else {
+        eprintln!("usage: heatmap <rom> <out.png> [frames]");
+        return ExitCode::FAILURE;
+    };
+    let frames = match rest.first() {
+        Some(arg) => arg
+            .parse()
+            .unwrap_or_else(|_| panic!("{} isn't a number", arg)),
+        None => 600,
+    };
+
+    let rom = match std::fs::read(rom) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("can't read {}: {}", rom, error);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut chip8 = Chip8::with_bus(HeatmapBus::new(Ram::default()));
+    if let Err(error) = chip8.load(&rom) {
+        eprintln!("{}", error);
+        return ExitCode::FAILURE;
+    }
+    for _ in 0..frames {
+        chip8.run_frame();
+    }
+
+    let heatmap = chip8.bus().heatmap();
+    if let Err(error) = std::fs::write(out, heatmap.to_png(8)) {
+        eprintln!("can't write {}: {}", out, error);
+        return ExitCode::FAILURE;
+    }
+    print!("{}\n{}", heatmap.map(), heatmap.table());
+    ExitCode::SUCCESS
+}
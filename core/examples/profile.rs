@@ -0,0 +1,42 @@
+//! Runs a ROM without a window and prints where its time goes, e.g.
+//! `cargo run -p core --example profile -- roms/pong.ch8 600 20`.
+//!
+//! Arguments: the ROM, then optionally the number of frames and of hotspots to list.
+
+use core::chip8::Chip8;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [rom, rest @ ..] = args.as_slice() else {
+        eprintln!("usage: profile <rom> [frames] [hotspots]");
+        return ExitCode::FAILURE;
+    };
+    let number = |index: usize, default: usize| match rest.get(index) {
+        Some(arg) => arg
+            .parse()
+            .unwrap_or_else(|_| panic!("{} isn't a number", arg)),
+        None => default,
+    };
+
+    let rom = match std::fs::read(rom) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("can't read {}: {}", rom, error);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut chip8 = Chip8::new();
+    if let Err(error) = chip8.load(&rom) {
+        eprintln!("{}", error);
+        return ExitCode::FAILURE;
+    }
+
+    chip8.start_profiling();
+    for _ in 0..number(0, 600) {
+        chip8.run_frame();
+    }
+    let profile = chip8.stop_profiling().expect("profiling was started");
+    print!("{}", profile.report(chip8.bus(), number(1, 20)));
+    ExitCode::SUCCESS
+}
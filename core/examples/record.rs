@@ -0,0 +1,58 @@
+//! Runs a ROM without a window and records it, e.g.
+//! `cargo run -p core --example record -- roms/pong.ch8 pong.gif 600 4 1`.
+//!
+//! Arguments: the ROM, the output (`.gif` or `.y4m`), then optionally the number of
+//! frames, the scale and the frame skip.
+
+use core::chip8::Chip8;
+use core::clip::{Clip, ClipOptions};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [rom, output, rest @ ..] = args.as_slice() else {
+        eprintln!("usage: record <rom> <output.gif|output.y4m> [frames] [scale] [frame skip]");
+        return ExitCode::FAILURE;
+    };
+    let number = |index: usize, default: usize| match rest.get(index) {
+        Some(arg) => arg
+            .parse()
+            .unwrap_or_else(|_| panic!("{} isn't a number", arg)),
+        None => default,
+    };
+    let frames = number(0, 600);
+    let options = ClipOptions {
+        scale: number(1, 4),
+        frame_skip: number(2, 0) as u32,
+        ..ClipOptions::default()
+    };
+
+    let rom = match std::fs::read(rom) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("can't read {}: {}", rom, error);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut chip8 = Chip8::new();
+    if let Err(error) = chip8.load(&rom) {
+        eprintln!("{}", error);
+        return ExitCode::FAILURE;
+    }
+
+    let mut clip = Clip::new(options);
+    for _ in 0..frames {
+        chip8.run_frame();
+        clip.capture(chip8.get_display());
+    }
+    let bytes = if output.ends_with(".y4m") {
+        clip.to_y4m()
+    } else {
+        clip.to_gif()
+    };
+    if let Err(error) = std::fs::write(output, bytes) {
+        eprintln!("can't write {}: {}", output, error);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
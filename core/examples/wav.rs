@@ -0,0 +1,61 @@
+//! Renders the beeper of a ROM or an input movie to a WAV file, without a sound device, e.g.
+//! `cargo run -p core --example wav -- roms/pong.ch8 pong.wav 600 440`.
+//!
+//! Arguments: the ROM or movie, the output, then optionally the number of frames to run a
+//! ROM for and the frequency of the tone. A movie runs for as long as it was recorded.
+
+use core::audio::{Beeper, Tone};
+use core::chip8::Chip8;
+use core::movie::{Movie, MOVIE_MAGIC};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [input, output, rest @ ..] = args.as_slice() else {
+        eprintln!("usage: wav <rom or movie> <output.wav> [frames] [frequency]");
+        return ExitCode::FAILURE;
+    };
+    let number = |index: usize, default: u32| match rest.get(index) {
+        Some(arg) => arg
+            .parse()
+            .unwrap_or_else(|_| panic!("{} isn't a number", arg)),
+        None => default,
+    };
+    let frames = number(0, 600);
+    let mut beeper = Beeper::new(Tone {
+        frequency: number(1, 440),
+        ..Tone::default()
+    });
+
+    let bytes = match std::fs::read(input) {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            eprintln!("can't read {}: {}", input, error);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut chip8 = Chip8::new();
+    if bytes.starts_with(&MOVIE_MAGIC) {
+        let replayed = Movie::from_bytes(&bytes)
+            .and_then(|movie| movie.replay_with(&mut chip8, |chip8| beeper.capture(chip8)));
+        if let Err(error) = replayed {
+            eprintln!("{}", error);
+            return ExitCode::FAILURE;
+        }
+    } else {
+        if let Err(error) = chip8.load(&bytes) {
+            eprintln!("{}", error);
+            return ExitCode::FAILURE;
+        }
+        for _ in 0..frames {
+            chip8.run_frame();
+            beeper.capture(&chip8);
+        }
+    }
+
+    if let Err(error) = std::fs::write(output, beeper.to_wav()) {
+        eprintln!("can't write {}: {}", output, error);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,138 @@
+//! Headless sound: renders the beeper to samples and WAV files, to check audio timing
+//! without a sound device. Beeps are placed to the sample with COSMAC VIP timing, and
+//! last whole 60ths of a second with fixed timing, which has no clock within a frame.
+
+use crate::bus::Bus;
+use crate::chip8::Chip8;
+use crate::timing::VIP_FRAME_BUDGET;
+use alloc::vec::Vec;
+use std::ops::Range;
+
+/// Ticks of the timers per second.
+const FRAME_RATE: u64 = 60;
+
+/// The square wave played while the sound timer runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tone {
+    /// In hertz.
+    pub frequency: u32,
+    /// Peak amplitude of the 16-bit samples.
+    pub volume: i16,
+    pub sample_rate: u32,
+}
+
+impl Default for Tone {
+    /// The beep of the libretro core.
+    fn default() -> Self {
+        Tone {
+            frequency: 440,
+            volume: 6000,
+            sample_rate: 44100,
+        }
+    }
+}
+
+/// Mono samples of the beeper, a 60th of a second per frame.
+#[derive(Debug, Clone)]
+pub struct Beeper {
+    tone: Tone,
+    samples: Vec<i16>,
+    frames: u64,
+    // Position in the wave, in units of 1 / sample_rate of a period
+    phase: u32,
+}
+
+impl Beeper {
+    /// Panics if the sample rate or the frequency is 0.
+    pub fn new(tone: Tone) -> Self {
+        assert!(
+            tone.sample_rate > 0 && tone.frequency > 0,
+            "tone needs a sample rate and a frequency"
+        );
+        Beeper {
+            tone,
+            samples: Vec::new(),
+            frames: 0,
+            phase: 0,
+        }
+    }
+
+    /// Call after every frame, e.g. after `run_frame` or `tick_timers`. With COSMAC VIP
+    /// timing, beeps start and stop on the sample where Fx18 ran, see `Chip8::beep`.
+    pub fn capture<B: Bus>(&mut self, chip8: &Chip8<B>) {
+        self.frame_span(chip8.beep());
+    }
+
+    /// Renders a frame of silence, or of the tone.
+    pub fn frame(&mut self, on: bool) {
+        self.frame_span(on.then_some(0..VIP_FRAME_BUDGET));
+    }
+
+    /// Renders a frame with the tone during `span`, in cycles out of `VIP_FRAME_BUDGET`.
+    /// Frames are cut on whole samples and add up to the exact time, so sample rates not
+    /// divisible by 60 don't drift.
+    pub fn frame_span(&mut self, span: Option<Range<u32>>) {
+        let rate = self.tone.sample_rate as u64;
+        let start = self.frames * rate / FRAME_RATE;
+        let end = (self.frames + 1) * rate / FRAME_RATE;
+        self.frames += 1;
+
+        let len = end - start;
+        let budget = VIP_FRAME_BUDGET as u64;
+        let on = span.map_or(0..0, |span| {
+            span.start as u64 * len / budget..span.end as u64 * len / budget
+        });
+        for sample in 0..len {
+            if !on.contains(&sample) {
+                // Every beep starts at the same point of the wave
+                self.phase = 0;
+                self.samples.push(0);
+                continue;
+            }
+            let sample = if self.phase < self.tone.sample_rate / 2 {
+                self.tone.volume
+            } else {
+                -self.tone.volume
+            };
+            self.samples.push(sample);
+            self.phase = (self.phase + self.tone.frequency) % self.tone.sample_rate;
+        }
+    }
+
+    pub fn tone(&self) -> Tone {
+        self.tone
+    }
+
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    /// Frames rendered so far.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// The samples as a 16-bit mono PCM WAV file.
+    pub fn to_wav(&self) -> Vec<u8> {
+        let data_len = self.samples.len() as u32 * 2;
+        let mut wav = Vec::with_capacity(44 + data_len as usize);
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        // PCM, one channel
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&self.tone.sample_rate.to_le_bytes());
+        wav.extend_from_slice(&(self.tone.sample_rate * 2).to_le_bytes());
+        // Bytes per sample, then bits per sample
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+        for sample in &self.samples {
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+        wav
+    }
+}
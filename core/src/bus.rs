@@ -0,0 +1,190 @@
+use crate::ram::Ram;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use std::ops::Range;
+
+/// Why the interpreter touched memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// An instruction byte was fetched.
+    Fetch,
+    /// Data was read, e.g. by Dxyn or Fx65.
+    Read,
+    /// Data was written, e.g. by Fx33 or Fx55.
+    Write,
+}
+
+/// Memory as seen by `Chip8`. Every fetch, read and write of the interpreter goes through it.
+///
+/// `peek` and `poke` are for the host (loading ROMs, the font set, debuggers) and shouldn't
+/// trigger side effects or be affected by protections.
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+
+    fn write(&mut self, addr: u16, value: u8);
+
+    fn fetch(&mut self, addr: u16) -> u8 {
+        self.read(addr)
+    }
+
+    fn peek(&self, addr: u16) -> u8;
+
+    fn poke(&mut self, addr: u16, value: u8);
+
+    /// Whether an instruction fetched from `addr` may be decoded once and reused until
+    /// the interpreter writes to it. Memory that changes by itself must return false.
+    fn cacheable(&self, _addr: u16) -> bool {
+        true
+    }
+}
+
+impl Bus for Ram {
+    fn read(&mut self, addr: u16) -> u8 {
+        Ram::read(self, addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        Ram::write(self, addr, value)
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        Ram::read(self, addr)
+    }
+
+    fn poke(&mut self, addr: u16, value: u8) {
+        Ram::write(self, addr, value)
+    }
+}
+
+/// Hardware mapped over a region of memory. Addresses are relative to the start of the region.
+pub trait Device {
+    fn read(&mut self, offset: u16) -> u8;
+
+    fn write(&mut self, offset: u16, value: u8);
+
+    /// Reads without side effects. Devices that can't do that return 0.
+    fn peek(&self, _offset: u16) -> u8 {
+        0
+    }
+
+    /// Writes without side effects. Devices that can't do that ignore it.
+    fn poke(&mut self, _offset: u16, _value: u8) {}
+}
+
+type Observer = Box<dyn FnMut(Access, u16, u8)>;
+
+/// A bus made of `Ram` with devices mapped over it, read-only regions and access observers.
+///
+/// ```
+/// use core::bus::{Access, MappedBus};
+/// use core::chip8::Chip8;
+///
+/// let mut bus = MappedBus::new();
+/// // Keep games from overwriting the font set
+/// bus.protect(0x000..0x050);
+/// bus.observe(|access, addr, value| {
+///     if access == Access::Write {
+///         println!("{:03x} <- {:02x}", addr, value);
+///     }
+/// });
+/// let chip8 = Chip8::with_bus(bus);
+/// ```
+#[derive(Default)]
+pub struct MappedBus {
+    ram: Ram,
+    devices: Vec<(Range<u16>, Box<dyn Device>)>,
+    read_only: Vec<Range<u16>>,
+    observers: Vec<Observer>,
+}
+
+impl MappedBus {
+    pub fn new() -> Self {
+        MappedBus::default()
+    }
+
+    /// Routes every access inside `range` to `device`. Later mappings win over earlier ones.
+    pub fn map(&mut self, range: Range<u16>, device: impl Device + 'static) -> &mut Self {
+        self.devices.push((range, Box::new(device)));
+        self
+    }
+
+    /// Ignores interpreter writes inside `range`. The host can still `poke` it.
+    pub fn protect(&mut self, range: Range<u16>) -> &mut Self {
+        self.read_only.push(range);
+        self
+    }
+
+    /// Calls `observer` with every interpreter access, after it happened.
+    /// Writes to protected regions are reported too, with the value that was attempted.
+    pub fn observe(&mut self, observer: impl FnMut(Access, u16, u8) + 'static) -> &mut Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    fn device(&mut self, addr: u16) -> Option<(&mut Box<dyn Device>, u16)> {
+        self.devices
+            .iter_mut()
+            .rev()
+            .find(|(range, _)| range.contains(&addr))
+            .map(|(range, device)| (device, addr - range.start))
+    }
+
+    fn access(&mut self, access: Access, addr: u16) -> u8 {
+        let value = match self.device(addr) {
+            Some((device, offset)) => device.read(offset),
+            None => self.ram.read(addr),
+        };
+        self.notify(access, addr, value);
+        value
+    }
+
+    fn notify(&mut self, access: Access, addr: u16, value: u8) {
+        for observer in self.observers.iter_mut() {
+            observer(access, addr, value);
+        }
+    }
+}
+
+impl Bus for MappedBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.access(Access::Read, addr)
+    }
+
+    fn fetch(&mut self, addr: u16) -> u8 {
+        self.access(Access::Fetch, addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        if !self.read_only.iter().any(|range| range.contains(&addr)) {
+            match self.device(addr) {
+                Some((device, offset)) => device.write(offset, value),
+                None => self.ram.write(addr, value),
+            }
+        }
+        self.notify(Access::Write, addr, value);
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        let device = self
+            .devices
+            .iter()
+            .rev()
+            .find(|(range, _)| range.contains(&addr));
+        match device {
+            Some((range, device)) => device.peek(addr - range.start),
+            None => self.ram.read(addr),
+        }
+    }
+
+    fn poke(&mut self, addr: u16, value: u8) {
+        match self.device(addr) {
+            Some((device, offset)) => device.poke(offset, value),
+            None => self.ram.write(addr, value),
+        }
+    }
+
+    /// Devices can change under the interpreter, and observers expect to see every fetch.
+    fn cacheable(&self, addr: u16) -> bool {
+        self.observers.is_empty() && !self.devices.iter().any(|(range, _)| range.contains(&addr))
+    }
+}
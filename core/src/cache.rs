@@ -0,0 +1,45 @@
+use crate::opcode::Opcode;
+use crate::ram::MEMORY_SIZE;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Instructions already decoded, by address, so running hot code skips fetching
+/// and matching nibbles. Entries must be dropped whenever one of their bytes changes.
+#[derive(Clone)]
+pub(crate) struct DecodeCache {
+    entries: Vec<Option<Opcode>>,
+}
+
+impl Default for DecodeCache {
+    fn default() -> Self {
+        DecodeCache {
+            entries: vec![None; MEMORY_SIZE],
+        }
+    }
+}
+
+impl DecodeCache {
+    pub fn get(&self, addr: u16) -> Option<Opcode> {
+        self.entries.get(addr as usize).copied().flatten()
+    }
+
+    pub fn insert(&mut self, addr: u16, opcode: Opcode) {
+        if let Some(entry) = self.entries.get_mut(addr as usize) {
+            *entry = Some(opcode);
+        }
+    }
+
+    /// Forgets the instructions that use the byte at `addr`, the one starting there
+    /// and the one starting right before it.
+    pub fn invalidate(&mut self, addr: u16) {
+        for start in [addr.wrapping_sub(1), addr] {
+            if let Some(entry) = self.entries.get_mut(start as usize) {
+                *entry = None;
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+}
@@ -0,0 +1,281 @@
+//! RAM search and cheats: find where a game keeps its lives or score, then freeze them.
+//!
+//! Cheat files are text, one cheat per line: `on` or `off`, the target (`0x2F4` for a
+//! memory address, `V3` for a register), the value, then a name.
+//!
+//! ```text
+//! # Pong
+//! on VE 0 Scores stay at 0
+//! off VE 55 Both players at 5
+//! ```
+
+use crate::bus::Bus;
+use crate::chip8::Chip8;
+use crate::ram::MEMORY_SIZE;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use std::fmt;
+
+/// How a byte has to compare for its address to stay a candidate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    Equals(u8),
+    NotEquals(u8),
+    /// Went up since the last snapshot.
+    Increased,
+    Decreased,
+    Unchanged,
+    Changed,
+    /// Went up by exactly this much, wrapping around.
+    IncreasedBy(u8),
+    DecreasedBy(u8),
+}
+
+impl Condition {
+    fn holds(&self, previous: u8, current: u8) -> bool {
+        match *self {
+            Condition::Equals(value) => current == value,
+            Condition::NotEquals(value) => current != value,
+            Condition::Increased => current > previous,
+            Condition::Decreased => current < previous,
+            Condition::Unchanged => current == previous,
+            Condition::Changed => current != previous,
+            Condition::IncreasedBy(delta) => current == previous.wrapping_add(delta),
+            Condition::DecreasedBy(delta) => current == previous.wrapping_sub(delta),
+        }
+    }
+}
+
+/// Narrows down the addresses of a variable by comparing memory over time.
+#[derive(Debug, Clone)]
+pub struct RamSearch {
+    candidates: Vec<u16>,
+    snapshot: Vec<u8>,
+}
+
+impl RamSearch {
+    /// Starts with every address as a candidate, and takes a snapshot.
+    pub fn new(bus: &impl Bus) -> Self {
+        let mut search = RamSearch {
+            candidates: (0..MEMORY_SIZE as u16).collect(),
+            snapshot: Vec::new(),
+        };
+        search.snapshot(bus);
+        search
+    }
+
+    /// Keeps the candidates whose byte meets `condition`, compared with the last snapshot,
+    /// then takes a new snapshot. Returns how many are left.
+    pub fn filter(&mut self, bus: &impl Bus, condition: Condition) -> usize {
+        let snapshot = &self.snapshot;
+        self.candidates
+            .retain(|addr| condition.holds(snapshot[*addr as usize], bus.peek(*addr)));
+        self.snapshot(bus);
+        self.candidates.len()
+    }
+
+    pub fn snapshot(&mut self, bus: &impl Bus) {
+        self.snapshot = (0..MEMORY_SIZE as u16).map(|addr| bus.peek(addr)).collect();
+    }
+
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+
+    /// The byte at `addr` when the last snapshot was taken.
+    pub fn previous(&self, addr: u16) -> u8 {
+        self.snapshot[addr as usize]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Memory(u16),
+    Register(u8),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Memory(addr) => write!(f, "{:#05X}", addr),
+            Target::Register(register) => write!(f, "V{:X}", register),
+        }
+    }
+}
+
+/// Keeps a byte of memory or a register at `value`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub name: String,
+    pub target: Target,
+    pub value: u8,
+    pub enabled: bool,
+}
+
+impl Cheat {
+    /// Parses a code like `0x2F4 3` or `VA 0xFF`, as entered in a frontend. It's
+    /// enabled, with the code as its name.
+    pub fn from_code(code: &str) -> Result<Cheat, CheatError> {
+        let mut parts = code.split_whitespace();
+        let target = parse_target(parts.next().ok_or(CheatError::Missing("target"))?)?;
+        let value = parse_value(parts.next().ok_or(CheatError::Missing("value"))?)?;
+        if parts.next().is_some() {
+            return Err(CheatError::Invalid("code"));
+        }
+        Ok(Cheat {
+            name: code.trim().to_string(),
+            target,
+            value,
+            enabled: true,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheatError {
+    Missing(&'static str),
+    Invalid(&'static str),
+    /// A problem on a line of a cheat file, counting from 1.
+    Line(usize, &'static str),
+}
+
+impl fmt::Display for CheatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheatError::Missing(what) => write!(f, "cheat has no {}", what),
+            CheatError::Invalid(what) => write!(f, "cheat has an invalid {}", what),
+            CheatError::Line(line, what) => write!(f, "line {}: invalid {}", line, what),
+        }
+    }
+}
+
+impl std::error::Error for CheatError {}
+
+fn parse_target(target: &str) -> Result<Target, CheatError> {
+    if let Some(register) = target.strip_prefix(['V', 'v']) {
+        return match u8::from_str_radix(register, 16) {
+            Ok(register) if register < 16 => Ok(Target::Register(register)),
+            _ => Err(CheatError::Invalid("register")),
+        };
+    }
+    let addr = target
+        .strip_prefix("0x")
+        .or_else(|| target.strip_prefix("0X"))
+        .ok_or(CheatError::Invalid("address"))?;
+    match u16::from_str_radix(addr, 16) {
+        Ok(addr) if (addr as usize) < MEMORY_SIZE => Ok(Target::Memory(addr)),
+        _ => Err(CheatError::Invalid("address")),
+    }
+}
+
+fn parse_value(value: &str) -> Result<u8, CheatError> {
+    let parsed = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|_| CheatError::Invalid("value"))
+}
+
+/// The cheats of one ROM.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cheats {
+    pub cheats: Vec<Cheat>,
+}
+
+impl Cheats {
+    /// Writes every enabled cheat. Call it before each frame to keep the values frozen.
+    pub fn apply<B: Bus>(&self, chip8: &mut Chip8<B>) {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled) {
+            match cheat.target {
+                Target::Memory(addr) => {
+                    if chip8.bus().peek(addr) != cheat.value {
+                        chip8.poke(addr, cheat.value);
+                    }
+                }
+                Target::Register(register) => {
+                    chip8.set_register(register, cheat.value);
+                }
+            }
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, CheatError> {
+        let mut cheats = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let at_line = |error: CheatError| match error {
+                CheatError::Missing(what) | CheatError::Invalid(what) => {
+                    CheatError::Line(index + 1, what)
+                }
+                error => error,
+            };
+            let mut rest = line;
+            let mut field = || {
+                let trimmed = rest.trim_start();
+                let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+                let (field, tail) = trimmed.split_at(end);
+                rest = tail;
+                field
+            };
+            let enabled = match field() {
+                "on" => true,
+                "off" => false,
+                _ => return Err(CheatError::Line(index + 1, "on/off")),
+            };
+            let target = parse_target(field()).map_err(at_line)?;
+            let value = parse_value(field()).map_err(at_line)?;
+            cheats.push(Cheat {
+                name: rest.trim().to_string(),
+                target,
+                value,
+                enabled,
+            });
+        }
+        Ok(Cheats { cheats })
+    }
+}
+
+impl fmt::Display for Cheats {
+    /// The cheat file format, which `parse` reads back.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for cheat in &self.cheats {
+            let state = if cheat.enabled { "on" } else { "off" };
+            writeln!(
+                f,
+                "{} {} {:#04X} {}",
+                state, cheat.target, cheat.value, cheat.name
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "database")]
+impl Cheats {
+    /// Where the cheats of `rom` are kept in `dir`: its SHA-1, like in the ROM database.
+    pub fn path(dir: impl AsRef<std::path::Path>, rom: &[u8]) -> std::path::PathBuf {
+        let name = alloc::format!("{}.cht", crate::database::sha1_hex(rom));
+        dir.as_ref().join(name)
+    }
+
+    /// The cheats saved for `rom` in `dir`, or none if there's no file yet.
+    pub fn load_for(dir: impl AsRef<std::path::Path>, rom: &[u8]) -> std::io::Result<Self> {
+        match std::fs::read_to_string(Cheats::path(dir, rom)) {
+            Ok(text) => Cheats::parse(&text)
+                .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Cheats::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn save_for(&self, dir: impl AsRef<std::path::Path>, rom: &[u8]) -> std::io::Result<()> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        std::fs::write(Cheats::path(dir, rom), self.to_string())
+    }
+}
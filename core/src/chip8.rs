@@ -1,8 +1,26 @@
-use crate::cpu::{Cpu, PROGRAM_START_ADDRESS};
+mod threaded;
+
+use crate::bus::Bus;
+use crate::cache::DecodeCache;
+use crate::cpu::{Cpu, PROGRAM_START_ADDRESS, STACK_SIZE};
+#[cfg(feature = "database")]
+use crate::database::{RomDatabase, RomInfo};
 use crate::display::{Display, SCREEN_HEIGHT, SCREEN_WIDTH};
 use crate::keyboard::Keyboard;
-use crate::ram::Ram;
-use std::fmt::Debug;
+use crate::movie::{Movie, Recording};
+use crate::opcode::{Opcode, UnknownOpcodeError};
+use crate::profiler::{Profile, Profiling};
+use crate::quirks::Quirks;
+use crate::ram::{Ram, MEMORY_SIZE};
+use crate::state::{self, StateError, StateReader, StateWriter};
+use crate::timing::{self, Timing, VIP_FRAME_BUDGET};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use std::fmt;
+use std::ops::Range;
+use threaded::BlockCache;
 
 const FONT_SET: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -23,242 +41,758 @@ const FONT_SET: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 const OPCODE_SIZE: u16 = 2;
+/// Instructions executed per frame unless a ROM asks for something else.
+pub const DEFAULT_TICKRATE: u32 = 10;
 
-#[derive(Debug)]
-enum Opcode {
-    /// Clears the screen.
-    _00e0,
-    /// Returns from a subroutine.
-    _00ee,
-    /// Jumps to address NNN.
-    _1nnn(u16),
-    /// Calls subroutine at NNN.
-    _2nnn(u16),
-    /// Skips the next instruction if VX equals NN. (Usually the next instruction is a jump to skip a code block);
-    _3xnn(u8, u8),
-    /// Skips the next instruction if VX does not equal NN. (Usually the next instruction is a jump to skip a code block);
-    _4xnn(u8, u8),
-    /// Skips the next instruction if VX equals VY. (Usually the next instruction is a jump to skip a code block);
-    _5xy0(u8, u8),
-    /// Sets VX to NN.
-    _6xnn(u8, u8),
-    /// Adds NN to VX. (Carry flag is not changed)
-    _7xnn(u8, u8),
-    /// Sets VX to the value of VY.
-    _8xy0(u8, u8),
-    /// Sets VX to VX or VY. (Bitwise OR operation)
-    _8xy1(u8, u8),
-    /// Sets VX to VX and VY. (Bitwise AND operation)
-    _8xy2(u8, u8),
-    /// Sets VX to VX xor VY. (Bitwise XOR operation)
-    _8xy3(u8, u8),
-    /// Adds VY to VX. VF is set to 1 when there's a carry, and to 0 when there isn't.
-    _8xy4(u8, u8),
-    /// VY is subtracted from VX. VF is set to 0 when there's a borrow, and 1 when there isn't.
-    _8xy5(u8, u8),
-    /// Shifts VX right by one. VF is set to the value of the least significant bit of VX before the shift.
-    _8xy6(u8, u8),
-    /// Sets VX to VY minus VX. VF is set to 0 when there's a borrow, and 1 when there isn't.
-    _8xy7(u8, u8),
-    /// Shifts VX left by one. VF is set to the value of the most significant bit of VX before the shift.
-    _8xye(u8, u8),
-    /// Skips the next instruction if VX doesn't equal VY. (Usually the next instruction is a jump to skip a code block);
-    _9xy0(u8, u8),
-    /// Sets I to the address NNN.
-    _Annn(u16),
-    /// Jumps to the address NNN plus V0.
-    _Bnnn(u16),
-    /// Sets VX to the result of a bitwise and operation on a random number (Typically: 0 to 255) and NN.
-    _Cxnn(u8, u8),
-    /// Draws a sprite at coordinate (VX, VY) that has a width of 8 pixels and a height of N pixels. Each row of 8 pixels is read as bit-coded starting from memory location I; I value doesn't change after the execution of this instruction. As described above, VF is set to 1 if any screen pixels are flipped from set to unset when the sprite is drawn, and to 0 if that doesn't happen.
-    _Dxyn(u8, u8, u8),
-    /// Skips the next instruction if the key stored in VX is pressed. (Usually the next instruction is a jump to skip a code block);
-    _Ex9e(u8),
-    /// Skips the next instruction if the key stored in VX isn't pressed. (Usually the next instruction is a jump to skip a code block);
-    _Exa1(u8),
-    /// Sets VX to the value of the delay timer.
-    _Fx07(u8),
-    /// A key press is awaited, and then stored in VX. (Blocking Operation. All instruction halted until next key event);
-    _Fx0a(u8),
-    /// Sets the delay timer to VX.
-    _Fx15(u8),
-    /// Sets the sound timer to VX.
-    _Fx18(u8),
-    /// Adds VX to I. (VF is not affected)
-    _Fx1e(u8),
-    /// Sets I to the location of the sprite for the character in VX. Characters 0-F (in hexadecimal) are represented by a 4x5 font.;
-    _Fx29(u8),
-    /// Stores the binary-coded decimal representation of VX, with the most significant of three digits at the address in I, the middle digit at I plus 1, and the least significant digit at I plus 2. (In other words, take the decimal representation of VX, place the hundreds digit in memory at location in I, the tens digit at location I+1, and the ones digit at location I+2.);
-    _Fx33(u8),
-    /// Stores from V0 to VX (including VX) in memory, starting at address I. The offset from I is increased by 1 for each value written, but I itself is left unmodified.
-    _Fx55(u8),
-    /// Fills from V0 to VX (including VX) with values from memory, starting at address I. The offset from I is increased by 1 for each value written, but I itself is left unmodified.
-    _Fx65(u8),
+/// Where a ROM is placed in memory and how memory is prepared before it.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadOptions {
+    /// Address the first byte of the ROM is written to. The program counter starts here.
+    pub address: u16,
+    /// Zero every byte above the font set before writing the ROM, so nothing is left over from a previous one.
+    pub clear_memory: bool,
 }
 
-struct UnknownOpcodeError((u16, u16, u16, u16));
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            address: PROGRAM_START_ADDRESS,
+            clear_memory: false,
+        }
+    }
+}
 
-impl Debug for UnknownOpcodeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "UnknownOpcodeError {:x?}", self.0)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// The ROM doesn't fit between the load address and the end of memory.
+    RomTooLarge { size: usize, max: usize },
+    /// The load address overlaps the font set or lies outside of memory.
+    InvalidAddress(u16),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::RomTooLarge { size, max } => {
+                write!(f, "ROM is too large: {} bytes, at most {} fit", size, max)
+            }
+            LoadError::InvalidAddress(addr) => {
+                write!(f, "ROM can't be loaded at address {:#05x}", addr)
+            }
+        }
     }
 }
 
-impl TryFrom<u16> for Opcode {
-    type Error = UnknownOpcodeError;
-
-    fn try_from(hex_opcode: u16) -> Result<Self, Self::Error> {
-        use Opcode::*;
-        let hex_nibbles = (
-            ((hex_opcode & 0xF000) >> 12),
-            ((hex_opcode & 0x0F00) >> 8),
-            ((hex_opcode & 0x00F0) >> 4),
-            (hex_opcode & 0x000F),
-        );
-
-        let nnn = hex_opcode & 0x0FFF;
-        let nn = (hex_opcode & 0x00FF) as u8;
-        let x = hex_nibbles.1 as u8;
-        let y = hex_nibbles.2 as u8;
-        let n = hex_nibbles.3 as u8;
-
-        match hex_nibbles {
-            (0, 0, 0xE, 0) => Ok(_00e0),
-            (0, 0, 0xE, 0xE) => Ok(_00ee),
-            (1, _, _, _) => Ok(_1nnn(nnn)),
-            (2, _, _, _) => Ok(_2nnn(nnn)),
-            (3, _, _, _) => Ok(_3xnn(x, nn)),
-            (4, _, _, _) => Ok(_4xnn(x, nn)),
-            (5, _, _, _) => Ok(_5xy0(x, y)),
-            (6, _, _, _) => Ok(_6xnn(x, nn)),
-            (7, _, _, _) => Ok(_7xnn(x, nn)),
-            (8, _, _, 0) => Ok(_8xy0(x, y)),
-            (8, _, _, 1) => Ok(_8xy1(x, y)),
-            (8, _, _, 2) => Ok(_8xy2(x, y)),
-            (8, _, _, 3) => Ok(_8xy3(x, y)),
-            (8, _, _, 4) => Ok(_8xy4(x, y)),
-            (8, _, _, 5) => Ok(_8xy5(x, y)),
-            (8, _, _, 6) => Ok(_8xy6(x, y)),
-            (8, _, _, 7) => Ok(_8xy7(x, y)),
-            (8, _, _, 0xE) => Ok(_8xye(x, y)),
-            (9, _, _, 0) => Ok(_9xy0(x, y)),
-            (0xA, _, _, _) => Ok(_Annn(nnn)),
-            (0xB, _, _, _) => Ok(_Bnnn(nnn)),
-            (0xC, _, _, _) => Ok(_Cxnn(x, nn)),
-            (0xD, _, _, _) => Ok(_Dxyn(x, y, n)),
-            (0xE, _, 9, 0xE) => Ok(_Ex9e(x)),
-            (0xE, _, 0xA, 0x1) => Ok(_Exa1(x)),
-            (0xF, _, 0, 7) => Ok(_Fx07(x)),
-            (0xF, _, 0, 0xA) => Ok(_Fx0a(x)),
-            (0xF, _, 1, 5) => Ok(_Fx15(x)),
-            (0xF, _, 1, 8) => Ok(_Fx18(x)),
-            (0xF, _, 1, 0xE) => Ok(_Fx1e(x)),
-            (0xF, _, 2, 9) => Ok(_Fx29(x)),
-            (0xF, _, 3, 3) => Ok(_Fx33(x)),
-            (0xF, _, 5, 5) => Ok(_Fx55(x)),
-            (0xF, _, 6, 5) => Ok(_Fx65(x)),
-            _ => Err(UnknownOpcodeError(hex_nibbles)),
+impl std::error::Error for LoadError {}
+
+/// Why `try_run_frame` or `try_emulate_cycle` stopped. The program counter is left on
+/// the instruction that couldn't run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunError {
+    UnknownOpcode(UnknownOpcodeError),
+    /// A call with all 16 levels of the stack in use.
+    StackOverflow,
+    /// A return outside of any subroutine.
+    StackUnderflow,
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::UnknownOpcode(err) => write!(f, "{}", err),
+            RunError::StackOverflow => write!(f, "stack overflow"),
+            RunError::StackUnderflow => write!(f, "return without a call"),
         }
     }
 }
 
+impl std::error::Error for RunError {}
+
+impl From<UnknownOpcodeError> for RunError {
+    fn from(err: UnknownOpcodeError) -> Self {
+        RunError::UnknownOpcode(err)
+    }
+}
+
+/// How `run_frame` executes instructions. `emulate_cycle` always uses the interpreter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    /// Fetches, decodes and runs one instruction at a time. This is the reference.
+    #[default]
+    Interpreter,
+    /// Runs straight-line code from cached blocks of pre-decoded handlers. Several times
+    /// faster on compute-heavy code, about the same on tight polling loops.
+    Threaded,
+    /// Runs `Threaded`, and panics if any frame ends differently than with `Interpreter`.
+    /// Meant for tests, it's slower than both.
+    Verify,
+}
+
 #[derive(Clone)]
-pub struct Chip8 {
-    ram: Ram,
+pub struct Chip8<B: Bus = Ram> {
+    bus: B,
     cpu: Cpu,
     display: Display,
     keyboard: Keyboard,
+    quirks: Quirks,
+    tickrate: u32,
+    timing: Timing,
+    // Cycles left in the current frame with `Timing::CosmacVip`, negative when overspent
+    cycle_budget: i64,
+    // Set by Dxyn when the vblank quirk is on, cleared when the frame ends
+    waiting_vblank: bool,
+    // Same generator as `StdRng`, which can't report its position for save states
+    rng: ChaCha12Rng,
+    decode_cache: DecodeCache,
+    engine: Engine,
+    blocks: BlockCache<B>,
+    recording: Option<Recording>,
+    // Cycles of the last frame, and of this one so far, with the beeper on. Measured
+    // against `VIP_FRAME_BUDGET`, see `beep`
+    beep: Option<Range<u32>>,
+    frame_beep: Option<Range<u32>>,
+    profiling: Option<Box<Profiling>>,
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Chip8::new()
+    }
 }
 
 impl Chip8 {
     pub fn new() -> Self {
+        Chip8::with_bus(Ram::default())
+    }
+
+    pub fn with_engine(engine: Engine) -> Self {
+        let mut chip = Chip8::new();
+        chip.set_engine(engine);
+        chip
+    }
+}
+
+impl<B: Bus> Chip8<B> {
+    /// Runs the interpreter on top of a custom memory bus, see `bus::MappedBus`.
+    pub fn with_bus(bus: B) -> Self {
         let mut chip = Chip8 {
-            ram: Ram::default(),
+            bus,
             cpu: Cpu::default(),
             display: Display::default(),
             keyboard: Keyboard::default(),
+            quirks: Quirks::default(),
+            tickrate: DEFAULT_TICKRATE,
+            timing: Timing::default(),
+            cycle_budget: 0,
+            waiting_vblank: false,
+            rng: initial_rng(),
+            decode_cache: DecodeCache::default(),
+            engine: Engine::default(),
+            blocks: BlockCache::default(),
+            recording: None,
+            beep: None,
+            frame_beep: None,
+            profiling: None,
         };
-        chip.ram.load_fontset(FONT_SET);
+        chip.load_fontset();
         chip
     }
 
     pub fn reset(&mut self) -> &mut Self {
-        self.ram = Ram::default();
+        for addr in 0..MEMORY_SIZE {
+            self.bus.poke(addr as u16, 0);
+        }
         self.cpu = Cpu::default();
         self.display = Display::default();
         self.keyboard = Keyboard::default();
-        self.ram.load_fontset(FONT_SET);
+        self.cycle_budget = 0;
+        self.waiting_vblank = false;
+        self.beep = None;
+        self.frame_beep = None;
+        self.load_fontset();
+        self
+    }
+
+    fn load_fontset(&mut self) {
+        for (addr, byte) in FONT_SET.iter().enumerate() {
+            self.bus.poke(addr as u16, *byte);
+        }
+        self.clear_caches();
+    }
+
+    fn clear_caches(&mut self) {
+        self.decode_cache.clear();
+        self.blocks.clear();
+    }
+
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    /// Changes made through the bus are invisible to the interpreter's caches, so they're dropped.
+    pub fn bus_mut(&mut self) -> &mut B {
+        self.clear_caches();
+        &mut self.bus
+    }
+
+    pub fn register(&self, register: u8) -> u8 {
+        self.cpu.read_register(register)
+    }
+
+    pub fn set_register(&mut self, register: u8, value: u8) -> &mut Self {
+        self.cpu.write_register(register, value);
+        self
+    }
+
+    /// Writes a byte from outside the program, like a debugger or a cheat. Only the
+    /// caches covering `addr` are dropped, unlike with `bus_mut`.
+    pub fn poke(&mut self, addr: u16, value: u8) {
+        self.bus.poke(addr, value);
+        self.decode_cache.invalidate(addr);
+        self.blocks.invalidate(addr, 1);
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.cpu.pc
+    }
+
+    pub fn set_pc(&mut self, pc: u16) -> &mut Self {
+        self.cpu.pc = pc;
+        self
+    }
+
+    /// The index register.
+    pub fn i(&self) -> u16 {
+        self.cpu.i
+    }
+
+    pub fn set_i(&mut self, i: u16) -> &mut Self {
+        self.cpu.i = i;
         self
     }
 
-    pub fn load(&mut self, rom: &[u8]) {
+    /// Addresses of the CALL instructions of the subroutines being run, the innermost last.
+    pub fn stack(&self) -> &[u16] {
+        self.cpu.stack()
+    }
+
+    /// Frames left before the delay timer reaches 0.
+    pub fn delay_timer(&self) -> u8 {
+        self.cpu.delay_timer
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) -> &mut Self {
+        self.quirks = quirks;
+        self
+    }
+
+    pub fn tickrate(&self) -> u32 {
+        self.tickrate
+    }
+
+    pub fn set_tickrate(&mut self, tickrate: u32) -> &mut Self {
+        self.tickrate = tickrate;
+        self
+    }
+
+    /// Makes Cxnn produce the same numbers on every run.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.rng = ChaCha12Rng::seed_from_u64(seed);
+        self
+    }
+
+    /// Reseeds Cxnn from `source`, like a hardware RNG on boards without an OS.
+    pub fn seed_from_rng<R: RngCore>(&mut self, source: R) -> Result<&mut Self, rand::Error> {
+        self.rng = ChaCha12Rng::from_rng(source)?;
+        Ok(self)
+    }
+
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+
+    pub fn set_engine(&mut self, engine: Engine) -> &mut Self {
+        self.engine = engine;
+        self.blocks.clear();
+        self
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    pub fn set_timing(&mut self, timing: Timing) -> &mut Self {
+        self.timing = timing;
+        self.cycle_budget = 0;
+        self
+    }
+
+    pub fn load(&mut self, rom: &[u8]) -> Result<(), LoadError> {
+        self.load_with(rom, LoadOptions::default())
+    }
+
+    pub fn load_with(&mut self, rom: &[u8], options: LoadOptions) -> Result<(), LoadError> {
+        Self::check_load(rom, options)?;
+        if options.clear_memory {
+            for addr in FONT_SET.len()..MEMORY_SIZE {
+                self.bus.poke(addr as u16, 0);
+            }
+        }
         for (i, byte) in rom.iter().enumerate() {
-            self.ram
-                .write(PROGRAM_START_ADDRESS as u16 + i as u16, *byte);
+            self.bus.poke(options.address + i as u16, *byte);
+        }
+        self.clear_caches();
+        self.cpu.pc = options.address;
+        Ok(())
+    }
+
+    fn check_load(rom: &[u8], options: LoadOptions) -> Result<(), LoadError> {
+        let start = options.address as usize;
+        // Never let a ROM overwrite the font set
+        if start < FONT_SET.len() || start >= MEMORY_SIZE {
+            return Err(LoadError::InvalidAddress(options.address));
+        }
+
+        let max = MEMORY_SIZE - start;
+        if rom.len() > max {
+            return Err(LoadError::RomTooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+        Ok(())
+    }
+
+    /// Resets the machine, loads a ROM and configures quirks, speed and start address the
+    /// way the database says it expects. Unknown ROMs run with the defaults, and fixed timing.
+    /// If the ROM can't be loaded, the machine is left untouched.
+    #[cfg(feature = "database")]
+    pub fn load_known(
+        &mut self,
+        rom: &[u8],
+        database: &RomDatabase,
+    ) -> Result<Option<RomInfo>, LoadError> {
+        let info = database.lookup(rom);
+        self.load_info(rom, info.as_ref())?;
+        Ok(info)
+    }
+
+    /// Like `load_known`, with the database entry looked up before, so reloading a ROM
+    /// doesn't search the database again.
+    #[cfg(feature = "database")]
+    pub fn load_info(&mut self, rom: &[u8], info: Option<&RomInfo>) -> Result<(), LoadError> {
+        let mut options = LoadOptions::default();
+        if let Some(info) = info {
+            options.address = info.start_address.unwrap_or(PROGRAM_START_ADDRESS);
         }
+        Self::check_load(rom, options)?;
+
+        self.reset();
+        self.timing = Timing::default();
+        self.quirks = info.map_or_else(Quirks::default, |info| info.quirks);
+        self.tickrate = info
+            .and_then(|info| info.tickrate)
+            .unwrap_or(DEFAULT_TICKRATE);
+        self.load_with(rom, options)
     }
 
     #[cfg(feature = "profile")]
-    fn profile(&self) {
-        println!("pc: {:x}", self.cpu.pc);
-        println!("i: {:x}", self.cpu.i);
+    fn profile(cpu: &Cpu) {
+        println!("pc: {:x}", cpu.pc);
+        println!("i: {:x}", cpu.i);
         println!("registers: ");
-        self.cpu.stack_register();
+        cpu.stack_register();
         println!("stack: ");
-        self.cpu.stack_profile()
+        cpu.stack_profile()
+    }
+
+    /// Runs one frame worth of instructions and ticks the timers once.
+    /// Panics on an instruction that can't run, see `try_run_frame`.
+    pub fn run_frame(&mut self) {
+        if let Err(err) = self.try_run_frame() {
+            panic!("Can't run instruction: {}", err);
+        }
     }
 
+    /// Like `run_frame`, but stops at an instruction that can't run, like an unknown
+    /// opcode, with the program counter on it and without ticking the timers.
+    pub fn try_run_frame(&mut self) -> Result<(), RunError> {
+        // The profiler has to see every instruction, which only the interpreter shows
+        let engine = if self.profiling.is_some() {
+            Engine::Interpreter
+        } else {
+            self.engine
+        };
+        match engine {
+            Engine::Interpreter => {
+                self.run_interpreted()?;
+                self.tick();
+            }
+            Engine::Threaded => {
+                self.run_threaded()?;
+                self.tick();
+            }
+            // Ticks the timers itself
+            Engine::Verify => self.run_verified()?,
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.end_frame(true);
+        }
+        Ok(())
+    }
+
+    fn run_interpreted(&mut self) -> Result<(), RunError> {
+        match self.timing {
+            Timing::Fixed => {
+                for _ in 0..self.tickrate {
+                    self.step()?;
+                }
+            }
+            Timing::CosmacVip => {
+                self.cycle_budget += VIP_FRAME_BUDGET as i64;
+                while self.cycle_budget > 0 && !self.waiting_vblank {
+                    self.cycle_budget -= self.step()? as i64;
+                }
+                // Waiting for vblank burns the rest of the frame
+                if self.waiting_vblank {
+                    self.cycle_budget = self.cycle_budget.min(0);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Panics on an instruction that can't run, see `try_emulate_cycle`.
     pub fn emulate_cycle(&mut self) {
+        if let Err(err) = self.try_emulate_cycle() {
+            panic!("Can't run instruction: {}", err);
+        }
+    }
+
+    /// Like `emulate_cycle`, but leaves the program counter on an instruction that can't run.
+    pub fn try_emulate_cycle(&mut self) -> Result<(), RunError> {
+        self.step()?;
+        if let Some(recording) = &mut self.recording {
+            recording.cycle();
+        }
+        Ok(())
+    }
+
+    /// Runs one instruction. Returns its cost in COSMAC VIP machine cycles
+    /// when that timing is in use, and 0 otherwise.
+    fn step(&mut self) -> Result<u32, RunError> {
+        // A draw is waiting for the next frame, nothing else runs until then
+        if self.waiting_vblank {
+            return Ok(0);
+        }
+
         #[cfg(feature = "profile")]
-        let old_state = self.clone();
+        let old_cpu = self.cpu.clone();
 
+        let pc = self.cpu.pc;
         let opcode = self.fetch_and_decode();
         #[cfg(feature = "profile")]
         {
             println!("==============");
             println!("opcode: {:?}", opcode);
-            old_state.profile();
+            Self::profile(&old_cpu);
             println!("==============");
         }
 
+        let opcode = opcode?;
+        self.check_stack(&opcode)?;
+        let cycles = match self.timing {
+            Timing::Fixed => 0,
+            Timing::CosmacVip => timing::vip_cycles(&opcode, &self.cpu),
+        };
+        self.run_instruction(opcode);
+        if let Some(profiling) = &mut self.profiling {
+            let cost = match self.timing {
+                Timing::Fixed => 1,
+                Timing::CosmacVip => cycles,
+            };
+            profiling.instruction(pc, opcode, cost as u64, &self.cpu);
+        }
+        Ok(cycles)
+    }
+
+    /// Calls with the stack full and returns with it empty can't run. Both engines check
+    /// before running anything, so they stop in the same state.
+    fn check_stack(&self, opcode: &Opcode) -> Result<(), RunError> {
         match opcode {
-            Ok(opcode) => self.run_instruction(opcode),
-            // Err(_) => self.cpu.pc += OPCODE_SIZE,
-            Err(err) => panic!("Unknown opcode: {:?}", err),
+            Opcode::_2nnn(_) if self.cpu.stack().len() == STACK_SIZE => {
+                Err(RunError::StackOverflow)
+            }
+            Opcode::_00ee if self.cpu.stack().is_empty() => Err(RunError::StackUnderflow),
+            _ => Ok(()),
         }
     }
 
+    /// Snapshot of the machine: memory, registers, screen, keys, quirks, speed and the
+    /// random number generator. The engine isn't saved.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+        for addr in 0..MEMORY_SIZE as u16 {
+            state.u8(self.bus.peek(addr));
+        }
+        self.cpu.save(&mut state);
+        self.display.save(&mut state);
+        let keys = (0..16).fold(0, |keys, key| {
+            keys | (self.keyboard.is_pressed(key) as u16) << key
+        });
+        state.u16(keys);
+
+        let quirks = [
+            self.quirks.shift,
+            self.quirks.memory_increment_by_x,
+            self.quirks.memory_leave_i_unchanged,
+            self.quirks.wrap,
+            self.quirks.jump,
+            self.quirks.vblank,
+            self.quirks.logic,
+        ];
+        for quirk in quirks {
+            state.bool(quirk);
+        }
+        state.u32(self.tickrate);
+        state.u8(match self.timing {
+            Timing::Fixed => 0,
+            Timing::CosmacVip => 1,
+        });
+        state.i64(self.cycle_budget);
+        state.bool(self.waiting_vblank);
+        for beep in [&self.beep, &self.frame_beep] {
+            state.bool(beep.is_some());
+            let Range { start, end } = beep.clone().unwrap_or_default();
+            state.u32(start);
+            state.u32(end);
+        }
+
+        state.bytes(&self.rng.get_seed());
+        state.u64(self.rng.get_stream());
+        state.u128(self.rng.get_word_pos());
+        state.finish()
+    }
+
+    /// Restores a snapshot from `save_state`. Nothing changes when it fails.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut state = StateReader::new(state)?;
+        let memory: [u8; MEMORY_SIZE] = state.array()?;
+        let cpu = Cpu::restore(&mut state)?;
+        let display = Display::restore(&mut state)?;
+        let keys = state.u16()?;
+
+        let quirks = Quirks {
+            shift: state.bool()?,
+            memory_increment_by_x: state.bool()?,
+            memory_leave_i_unchanged: state.bool()?,
+            wrap: state.bool()?,
+            jump: state.bool()?,
+            vblank: state.bool()?,
+            logic: state.bool()?,
+        };
+        let tickrate = state.u32()?;
+        let timing = match state.u8()? {
+            0 => Timing::Fixed,
+            1 => Timing::CosmacVip,
+            _ => return Err(StateError::Invalid("timing")),
+        };
+        let cycle_budget = state.i64()?;
+        let waiting_vblank = state.bool()?;
+        let mut beeps = [None, None];
+        for beep in &mut beeps {
+            let sounded = state.bool()?;
+            let span = state.u32()?..state.u32()?;
+            if span.start > span.end || span.end > VIP_FRAME_BUDGET {
+                return Err(StateError::Invalid("beep"));
+            }
+            *beep = sounded.then_some(span);
+        }
+        let [beep, frame_beep] = beeps;
+
+        let mut rng = ChaCha12Rng::from_seed(state.array()?);
+        rng.set_stream(state.u64()?);
+        rng.set_word_pos(state.u128()?);
+
+        for (addr, byte) in memory.iter().enumerate() {
+            self.bus.poke(addr as u16, *byte);
+        }
+        self.clear_caches();
+        self.cpu = cpu;
+        self.display = display;
+        for key in 0..16 {
+            self.keyboard.press(key, keys & (1 << key) != 0);
+        }
+        self.quirks = quirks;
+        self.tickrate = tickrate;
+        self.timing = timing;
+        self.cycle_budget = cycle_budget;
+        self.waiting_vblank = waiting_vblank;
+        self.rng = rng;
+        self.beep = beep;
+        self.frame_beep = frame_beep;
+        Ok(())
+    }
+
+    /// Hash of `save_state`. Machines with the same hash will run the same from here on.
+    pub fn state_hash(&self) -> u64 {
+        state::hash(self.save_state())
+    }
+
+    /// Frames left with the beeper on.
+    pub fn sound_timer(&self) -> u8 {
+        self.cpu.sound_timer
+    }
+
+    /// Whether the beeper sounded during the frame ended by the last tick of the timers.
+    /// Like on the COSMAC VIP, it sounds for as many ticks as the value given to Fx18.
+    pub fn beeped(&self) -> bool {
+        self.beep.is_some()
+    }
+
+    /// When the beeper sounded during the last frame, in cycles from its start out of
+    /// `VIP_FRAME_BUDGET`. With `Timing::CosmacVip` it starts and stops where Fx18 ran,
+    /// so `0..VIP_FRAME_BUDGET` is the whole frame. With `Timing::Fixed` instructions
+    /// have no place in the frame: the beeper sounds for whole frames, those ending with
+    /// the sound timer running.
+    pub fn beep(&self) -> Option<Range<u32>> {
+        self.beep.clone()
+    }
+
+    /// Fx18. `cycles_left` is the frame's budget once the instruction is paid for.
+    fn set_sound_timer(&mut self, value: u8, cycles_left: i64) {
+        if self.timing == Timing::CosmacVip {
+            let budget = VIP_FRAME_BUDGET as i64;
+            let at = (budget - cycles_left).clamp(0, budget) as u32;
+            // A beep stopped and started again in the same frame counts as one
+            let start = self.frame_beep.as_ref().map(|beep| beep.start);
+            match (self.cpu.sound_timer > 0, value > 0) {
+                (false, true) => self.frame_beep = Some(start.unwrap_or(at)..VIP_FRAME_BUDGET),
+                (true, false) => self.frame_beep = Some(start.unwrap_or(0)..at),
+                _ => {}
+            }
+        }
+        self.cpu.sound_timer = value;
+    }
+
     pub fn get_display(&self) -> &Display {
         &self.display
     }
 
     pub fn keypress(&mut self, key: u8, pressed: bool) {
+        if let Some(recording) = &mut self.recording {
+            if self.keyboard.is_pressed(key) != pressed {
+                recording.key(key, pressed);
+            }
+        }
         self.keyboard.press(key, pressed);
     }
 
     pub fn tick_timers(&mut self) {
+        self.tick();
+        if let Some(recording) = &mut self.recording {
+            recording.end_frame(false);
+        }
+    }
+
+    fn tick(&mut self) {
+        let running = self.cpu.sound_timer > 0;
+        let frame_beep = self.frame_beep.take();
+        self.beep = match self.timing {
+            Timing::Fixed => running.then_some(0..VIP_FRAME_BUDGET),
+            // Also when the timer was set some other way than Fx18, like a cheat
+            Timing::CosmacVip if running => {
+                Some(frame_beep.map_or(0, |beep| beep.start)..VIP_FRAME_BUDGET)
+            }
+            Timing::CosmacVip => frame_beep,
+        };
         self.cpu.tick_timers();
+        self.frame_beep = (self.cpu.sound_timer > 0).then_some(0..VIP_FRAME_BUDGET);
+        self.waiting_vblank = false;
+    }
+
+    /// Starts recording a movie from the current state. Restarts if already recording.
+    /// Loading a ROM or a state while recording isn't part of the movie.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Recording::new(self.save_state()));
+    }
+
+    /// The movie recorded since `start_recording`, if it was called.
+    pub fn stop_recording(&mut self) -> Option<Movie> {
+        let recording = self.recording.take()?;
+        Some(recording.finish(&self.display))
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Starts counting every instruction run, see `profiler`. Restarts if already
+    /// profiling. Frames run on the interpreter meanwhile, whatever the engine.
+    pub fn start_profiling(&mut self) {
+        self.profiling = Some(Box::new(Profiling::new()));
+    }
+
+    /// What ran since `start_profiling`, if it was called.
+    pub fn stop_profiling(&mut self) -> Option<Profile> {
+        Some(self.profiling.take()?.finish())
+    }
+
+    pub fn is_profiling(&self) -> bool {
+        self.profiling.is_some()
     }
 
     pub fn clean_keyboard(&mut self) {
+        if let Some(recording) = &mut self.recording {
+            for key in (0..16).filter(|key| self.keyboard.is_pressed(*key)) {
+                recording.key(key, false);
+            }
+        }
         self.keyboard.clean()
     }
 
     fn fetch_and_decode(&mut self) -> Result<Opcode, UnknownOpcodeError> {
+        match self.decode_cache.get(self.cpu.pc) {
+            Some(opcode) => Ok(opcode),
+            None => self.fetch_uncached(),
+        }
+    }
+
+    // Kept out of line so the hot path above stays small
+    #[cold]
+    fn fetch_uncached(&mut self) -> Result<Opcode, UnknownOpcodeError> {
+        let pc = self.cpu.pc;
         // Grab the next two bytes from memory (opcode is 2 bytes long)
-        let hi = self.ram.read(self.cpu.pc);
-        let lo = self.ram.read(self.cpu.pc + 1);
+        let hi = self.bus.fetch(pc);
+        let lo = self.bus.fetch(pc + 1);
         // Combine them. Same as doing: hi << 8 | lo
         let hex_opcode = u16::from_be_bytes([hi, lo]);
 
-        Opcode::try_from(hex_opcode)
+        let opcode = Opcode::decode(hex_opcode)?;
+        if self.bus.cacheable(pc) && self.bus.cacheable(pc + 1) {
+            self.decode_cache.insert(pc, opcode);
+        }
+        Ok(opcode)
+    }
+
+    /// Every write of the interpreter goes through here, so whatever opcode writes over
+    /// code, the decoded instructions and blocks covering it are dropped.
+    fn write_memory(&mut self, addr: u16, value: u8) {
+        self.bus.write(addr, value);
+        self.decode_cache.invalidate(addr);
+        self.blocks.invalidate(addr, 1);
     }
 
     fn run_instruction(&mut self, opcode: Opcode) {
-        let ram = &mut self.ram;
+        let ram = &mut self.bus;
         let cpu = &mut self.cpu;
         let display = &mut self.display;
         let keyboard = &mut self.keyboard;
+        let quirks = self.quirks;
 
         use Opcode as Op;
         match opcode {
@@ -318,16 +852,25 @@ impl Chip8 {
             Op::_8xy1(x, y) => {
                 let or = cpu.read_register(x) | cpu.read_register(y);
                 cpu.write_register(x, or);
+                if quirks.logic {
+                    cpu.write_register(0xF, 0);
+                }
                 cpu.pc += OPCODE_SIZE;
             }
             Op::_8xy2(x, y) => {
                 let and = cpu.read_register(x) & cpu.read_register(y);
                 cpu.write_register(x, and);
+                if quirks.logic {
+                    cpu.write_register(0xF, 0);
+                }
                 cpu.pc += OPCODE_SIZE;
             }
             Op::_8xy3(x, y) => {
                 let xor = cpu.read_register(x) ^ cpu.read_register(y);
                 cpu.write_register(x, xor);
+                if quirks.logic {
+                    cpu.write_register(0xF, 0);
+                }
                 cpu.pc += OPCODE_SIZE;
             }
             Op::_8xy4(x, y) => {
@@ -347,8 +890,12 @@ impl Chip8 {
                 cpu.write_register(0xF, borrow as u8);
                 cpu.pc += OPCODE_SIZE;
             }
-            Op::_8xy6(x, _) => {
-                let vx = cpu.read_register(x);
+            Op::_8xy6(x, y) => {
+                let vx = if quirks.shift {
+                    cpu.read_register(x)
+                } else {
+                    cpu.read_register(y)
+                };
                 let least_significant_bit = vx & 1;
                 cpu.write_register(x, vx >> 1);
                 cpu.write_register(0xF, least_significant_bit);
@@ -363,8 +910,12 @@ impl Chip8 {
                 cpu.write_register(0xF, borrow as u8);
                 cpu.pc += OPCODE_SIZE;
             }
-            Op::_8xye(x, _) => {
-                let vx = cpu.read_register(x);
+            Op::_8xye(x, y) => {
+                let vx = if quirks.shift {
+                    cpu.read_register(x)
+                } else {
+                    cpu.read_register(y)
+                };
                 let most_significant_bit = (vx >> 7) & 1;
                 cpu.write_register(x, vx << 1);
                 cpu.write_register(0xF, most_significant_bit);
@@ -383,46 +934,33 @@ impl Chip8 {
                 cpu.pc += OPCODE_SIZE;
             }
             Op::_Bnnn(nnn) => {
-                let v0 = cpu.read_register(0);
-                cpu.pc = nnn + v0 as u16;
+                // With the jump quirk, the high nibble of NNN also picks the register
+                let register = if quirks.jump { (nnn >> 8) as u8 } else { 0 };
+                let offset = cpu.read_register(register);
+                cpu.pc = nnn + offset as u16;
             }
             Op::_Cxnn(x, nn) => {
-                let random_number = rand::random::<u8>();
+                let random_number = self.rng.gen::<u8>();
                 cpu.write_register(x, random_number & nn);
                 cpu.pc += OPCODE_SIZE;
             }
             Op::_Dxyn(x, y, n) => {
-                let vx = cpu.read_register(x);
-                let vy = cpu.read_register(y);
+                // The starting position always wraps, the rest of the sprite may be clipped
+                let vx = cpu.read_register(x) as usize % SCREEN_WIDTH;
+                let vy = cpu.read_register(y) as usize % SCREEN_HEIGHT;
 
-                let mut flipped = false;
-
-                // Iterate over each row of our sprite
-                for y_line in 0..n {
-                    // Determine which memory address the row data is stored
-                    let addr = cpu.i + y_line as u16;
-                    let pixels = ram.read(addr);
-                    // Iterate over each column in our row
-                    for x_line in 0..8 {
-                        // Grab current pixel bit. If it's 1, we need to flip the pixel
-                        if (pixels & (0b1000_0000 >> x_line)) != 0 {
-                            // Modulo for wrapping effect
-                            let x = (vx + x_line) as usize % SCREEN_WIDTH;
-                            let y = (vy + y_line) as usize % SCREEN_HEIGHT;
-
-                            let idx = SCREEN_WIDTH * y + x;
-                            // Check if we're about to flip the pixel and set
-                            display.screen[idx] ^= true;
-                            flipped |= display.screen[idx];
-                        }
+                let mut collision = false;
+                for row in 0..n as usize {
+                    let y = vy + row;
+                    if y >= SCREEN_HEIGHT && !quirks.wrap {
+                        break;
                     }
+                    let sprite_row = ram.read(cpu.i + row as u16);
+                    collision |=
+                        display.draw_sprite_row(vx, y % SCREEN_HEIGHT, sprite_row, quirks.wrap);
                 }
-
-                if flipped {
-                    cpu.write_register(0xF, 0);
-                } else {
-                    cpu.write_register(0xF, 1);
-                }
+                cpu.write_register(0xF, collision as u8);
+                self.waiting_vblank = quirks.vblank;
                 cpu.pc += OPCODE_SIZE;
             }
             Op::_Ex9e(x) => {
@@ -463,8 +1001,11 @@ impl Chip8 {
                 cpu.pc += OPCODE_SIZE;
             }
             Op::_Fx18(x) => {
-                cpu.sound_timer = cpu.read_register(x);
-                cpu.pc += OPCODE_SIZE;
+                let value = cpu.read_register(x);
+                // The interpreter charges instructions after running them
+                let cost = timing::vip_cycles(&opcode, cpu) as i64;
+                self.set_sound_timer(value, self.cycle_budget - cost);
+                self.cpu.pc += OPCODE_SIZE;
             }
             Op::_Fx1e(x) => {
                 let vx = cpu.read_register(x);
@@ -477,27 +1018,26 @@ impl Chip8 {
                 cpu.i = vx as u16 * 5;
                 cpu.pc += OPCODE_SIZE;
             }
-            // TODO: faster way to do this?
             Op::_Fx33(x) => {
                 let vx = cpu.read_register(x);
                 let hundreds = vx / 100;
                 let tens = (vx / 10) % 10;
-                let ones = (vx % 10) as u8;
+                let ones = vx % 10;
 
-                ram.write(cpu.i, hundreds);
-                ram.write(cpu.i + 1, tens);
-                ram.write(cpu.i + 2, ones);
-
-                cpu.pc += OPCODE_SIZE;
+                let i = cpu.i;
+                self.write_memory(i, hundreds);
+                self.write_memory(i + 1, tens);
+                self.write_memory(i + 2, ones);
+                self.cpu.pc += OPCODE_SIZE;
             }
             Op::_Fx55(x) => {
+                let i = cpu.i;
                 for reg in 0..=x {
-                    let idx = cpu.i + reg as u16;
-                    let value = cpu.read_register(reg);
-                    ram.write(idx, value);
+                    let value = self.cpu.read_register(reg);
+                    self.write_memory(i + reg as u16, value);
                 }
-                cpu.i += x as u16 + 1;
-                cpu.pc += OPCODE_SIZE;
+                self.cpu.i += memory_increment(quirks, x);
+                self.cpu.pc += OPCODE_SIZE;
             }
             Op::_Fx65(x) => {
                 for reg in 0..=x {
@@ -505,9 +1045,29 @@ impl Chip8 {
                     let value = ram.read(idx);
                     cpu.write_register(reg, value);
                 }
-                cpu.i += x as u16 + 1;
+                cpu.i += memory_increment(quirks, x);
                 cpu.pc += OPCODE_SIZE;
             }
         };
     }
 }
+
+/// Seeded from the OS when there is one. Without std, Cxnn is deterministic until
+/// `seed` or `seed_from_rng` is called.
+fn initial_rng() -> ChaCha12Rng {
+    #[cfg(feature = "std")]
+    return ChaCha12Rng::from_entropy();
+    #[cfg(not(feature = "std"))]
+    return ChaCha12Rng::seed_from_u64(0);
+}
+
+/// How much Fx55 and Fx65 move I forward.
+fn memory_increment(quirks: Quirks, x: u8) -> u16 {
+    if quirks.memory_leave_i_unchanged {
+        0
+    } else if quirks.memory_increment_by_x {
+        x as u16
+    } else {
+        x as u16 + 1
+    }
+}
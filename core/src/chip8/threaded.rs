@@ -0,0 +1,538 @@
+//! The threaded engine: straight-line code is translated once into blocks of handler
+//! pointers with their operands already extracted, then run without fetching or decoding.
+//!
+//! Blocks end at anything that may not fall through to the next instruction (jumps, calls,
+//! skips, Fx0a), and at draws and memory writes. Branches have their own handlers, the
+//! rest run on the reference interpreter. Blocks overlapping written memory are dropped,
+//! so self-modifying code keeps working.
+
+use super::{Chip8, Engine, RunError, OPCODE_SIZE};
+use crate::bus::Bus;
+use crate::cache::DecodeCache;
+use crate::opcode::Opcode;
+use crate::ram::{Ram, MEMORY_SIZE};
+use crate::timing::{self, Timing, VIP_FRAME_BUDGET};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use rand::Rng;
+
+/// Longest run of instructions translated into one block.
+const MAX_BLOCK_LEN: usize = 64;
+
+#[derive(Clone, Copy)]
+struct Operands {
+    x: u8,
+    y: u8,
+    nn: u8,
+    nnn: u16,
+}
+
+impl From<u16> for Operands {
+    fn from(hex_opcode: u16) -> Self {
+        Operands {
+            x: ((hex_opcode & 0x0F00) >> 8) as u8,
+            y: ((hex_opcode & 0x00F0) >> 4) as u8,
+            nn: (hex_opcode & 0x00FF) as u8,
+            nnn: hex_opcode & 0x0FFF,
+        }
+    }
+}
+
+type Handler<B> = fn(&mut Chip8<B>, Operands);
+
+struct Instruction<B: Bus> {
+    handler: Handler<B>,
+    operands: Operands,
+    // Kept for the cycle cost
+    opcode: Opcode,
+}
+
+/// Instruction ending a block. Branches have a handler that sets the pc, anything else
+/// is left to `run_instruction`.
+struct Terminator<B: Bus> {
+    handler: Option<Handler<B>>,
+    operands: Operands,
+    opcode: Opcode,
+}
+
+struct Block<B: Bus> {
+    start: u16,
+    /// First address after the block.
+    end: u16,
+    body: Vec<Instruction<B>>,
+    /// Sits right after the body.
+    terminator: Option<Terminator<B>>,
+}
+
+// Derived impls would require `B: Clone`
+impl<B: Bus> Clone for Instruction<B> {
+    fn clone(&self) -> Self {
+        Instruction {
+            handler: self.handler,
+            operands: self.operands,
+            opcode: self.opcode,
+        }
+    }
+}
+
+impl<B: Bus> Clone for Terminator<B> {
+    fn clone(&self) -> Self {
+        Terminator {
+            handler: self.handler,
+            operands: self.operands,
+            opcode: self.opcode,
+        }
+    }
+}
+
+impl<B: Bus> Clone for Block<B> {
+    fn clone(&self) -> Self {
+        Block {
+            start: self.start,
+            end: self.end,
+            body: self.body.clone(),
+            terminator: self.terminator.clone(),
+        }
+    }
+}
+
+/// Translated blocks, by start address.
+pub(super) struct BlockCache<B: Bus> {
+    slots: Vec<Option<Box<Block<B>>>>,
+    /// The block taken out of its slot while it runs, and whether it was invalidated meanwhile.
+    running: Option<(u16, u16)>,
+    running_stale: bool,
+}
+
+impl<B: Bus> Default for BlockCache<B> {
+    fn default() -> Self {
+        BlockCache {
+            slots: (0..MEMORY_SIZE).map(|_| None).collect(),
+            running: None,
+            running_stale: false,
+        }
+    }
+}
+
+impl<B: Bus> Clone for BlockCache<B> {
+    fn clone(&self) -> Self {
+        BlockCache {
+            slots: self.slots.clone(),
+            running: self.running,
+            running_stale: self.running_stale,
+        }
+    }
+}
+
+impl<B: Bus> BlockCache<B> {
+    pub fn clear(&mut self) {
+        self.slots.fill_with(|| None);
+        self.running_stale = self.running.is_some();
+    }
+
+    /// Drops every block containing a byte in `start..start + len`.
+    pub fn invalidate(&mut self, start: u16, len: u16) {
+        let end = (start as usize + len as usize).min(MEMORY_SIZE);
+        // No block is longer than this, so nothing starting earlier can reach `start`
+        let reach = (MAX_BLOCK_LEN + 1) * OPCODE_SIZE as usize;
+        let first = (start as usize).saturating_sub(reach);
+        for slot in &mut self.slots[first.min(end)..end] {
+            if slot.as_ref().is_some_and(|block| block.end > start) {
+                *slot = None;
+            }
+        }
+        if let Some((block_start, block_end)) = self.running {
+            if (block_start as usize) < end && block_end > start {
+                self.running_stale = true;
+            }
+        }
+    }
+
+    /// Takes the block at `addr` out of the cache while it runs.
+    fn take(&mut self, addr: u16) -> Option<Box<Block<B>>> {
+        let block = self.slots.get_mut(addr as usize)?.take()?;
+        self.start_running(&block);
+        Some(block)
+    }
+
+    fn start_running(&mut self, block: &Block<B>) {
+        self.running = Some((block.start, block.end));
+        self.running_stale = false;
+    }
+
+    /// Puts the block that just ran back, unless the code it was translated from changed.
+    fn put_back(&mut self, block: Box<Block<B>>) {
+        self.running = None;
+        if !self.running_stale {
+            let start = block.start as usize;
+            self.slots[start] = Some(block);
+        }
+    }
+}
+
+/// Handlers for instructions that always fall through to the next one and don't write memory.
+fn handler<B: Bus>(opcode: &Opcode) -> Option<Handler<B>> {
+    use Opcode as Op;
+    let handler: Handler<B> = match opcode {
+        Op::_00e0 => |chip, _| chip.display.clear(),
+        Op::_6xnn(..) => |chip, op| chip.cpu.write_register(op.x, op.nn),
+        Op::_7xnn(..) => |chip, op| {
+            let vx = chip.cpu.read_register(op.x);
+            chip.cpu.write_register(op.x, vx.wrapping_add(op.nn));
+        },
+        Op::_8xy0(..) => |chip, op| {
+            let vy = chip.cpu.read_register(op.y);
+            chip.cpu.write_register(op.x, vy);
+        },
+        Op::_8xy1(..) => |chip, op| chip.logic(op, |vx, vy| vx | vy),
+        Op::_8xy2(..) => |chip, op| chip.logic(op, |vx, vy| vx & vy),
+        Op::_8xy3(..) => |chip, op| chip.logic(op, |vx, vy| vx ^ vy),
+        Op::_8xy4(..) => |chip, op| {
+            let vx = chip.cpu.read_register(op.x);
+            let vy = chip.cpu.read_register(op.y);
+            let (sum, overflow) = vx.overflowing_add(vy);
+            chip.cpu.write_register(op.x, sum);
+            chip.cpu.write_register(0xF, overflow as u8);
+        },
+        Op::_8xy5(..) => |chip, op| {
+            let vx = chip.cpu.read_register(op.x);
+            let vy = chip.cpu.read_register(op.y);
+            let (sub, overflow) = vx.overflowing_sub(vy);
+            chip.cpu.write_register(op.x, sub);
+            chip.cpu.write_register(0xF, !overflow as u8);
+        },
+        Op::_8xy6(..) => |chip, op| {
+            let value = chip.shift_source(op);
+            chip.cpu.write_register(op.x, value >> 1);
+            chip.cpu.write_register(0xF, value & 1);
+        },
+        Op::_8xy7(..) => |chip, op| {
+            let vx = chip.cpu.read_register(op.x);
+            let vy = chip.cpu.read_register(op.y);
+            let (sub, overflow) = vy.overflowing_sub(vx);
+            chip.cpu.write_register(op.x, sub);
+            chip.cpu.write_register(0xF, !overflow as u8);
+        },
+        Op::_8xye(..) => |chip, op| {
+            let value = chip.shift_source(op);
+            chip.cpu.write_register(op.x, value << 1);
+            chip.cpu.write_register(0xF, (value >> 7) & 1);
+        },
+        Op::_Annn(_) => |chip, op| chip.cpu.i = op.nnn,
+        Op::_Cxnn(..) => |chip, op| {
+            let random_number = chip.rng.gen::<u8>();
+            chip.cpu.write_register(op.x, random_number & op.nn);
+        },
+        Op::_Fx07(_) => |chip, op| chip.cpu.write_register(op.x, chip.cpu.delay_timer),
+        Op::_Fx15(_) => |chip, op| chip.cpu.delay_timer = chip.cpu.read_register(op.x),
+        Op::_Fx18(_) => |chip, op| {
+            let value = chip.cpu.read_register(op.x);
+            chip.set_sound_timer(value, chip.cycle_budget)
+        },
+        Op::_Fx1e(_) => |chip, op| {
+            let vx = chip.cpu.read_register(op.x);
+            chip.cpu.i = chip.cpu.i.wrapping_add(vx as u16);
+        },
+        Op::_Fx29(_) => |chip, op| chip.cpu.i = chip.cpu.read_register(op.x) as u16 * 5,
+        Op::_Fx65(_) => |chip, op| {
+            for reg in 0..=op.x {
+                let value = chip.bus.read(chip.cpu.i + reg as u16);
+                chip.cpu.write_register(reg, value);
+            }
+            chip.cpu.i += super::memory_increment(chip.quirks, op.x);
+        },
+        _ => return None,
+    };
+    Some(handler)
+}
+
+/// Handlers for branches, which run with the pc on the branch itself and leave it on
+/// the next instruction to run.
+fn branch_handler<B: Bus>(opcode: &Opcode) -> Option<Handler<B>> {
+    use Opcode as Op;
+    let handler: Handler<B> = match opcode {
+        Op::_00ee => |chip, _| chip.cpu.pc = chip.cpu.stack_pop() + OPCODE_SIZE,
+        Op::_1nnn(_) => |chip, op| chip.cpu.pc = op.nnn,
+        Op::_2nnn(_) => |chip, op| {
+            chip.cpu.stack_push(chip.cpu.pc);
+            chip.cpu.pc = op.nnn;
+        },
+        Op::_3xnn(..) => |chip, op| chip.skip_if(chip.cpu.read_register(op.x) == op.nn),
+        Op::_4xnn(..) => |chip, op| chip.skip_if(chip.cpu.read_register(op.x) != op.nn),
+        Op::_5xy0(..) => {
+            |chip, op| chip.skip_if(chip.cpu.read_register(op.x) == chip.cpu.read_register(op.y))
+        }
+        Op::_9xy0(..) => {
+            |chip, op| chip.skip_if(chip.cpu.read_register(op.x) != chip.cpu.read_register(op.y))
+        }
+        Op::_Ex9e(_) => {
+            |chip, op| chip.skip_if(chip.keyboard.is_pressed(chip.cpu.read_register(op.x)))
+        }
+        Op::_Exa1(_) => {
+            |chip, op| chip.skip_if(!chip.keyboard.is_pressed(chip.cpu.read_register(op.x)))
+        }
+        _ => return None,
+    };
+    Some(handler)
+}
+
+impl<B: Bus> Chip8<B> {
+    fn skip_if(&mut self, condition: bool) {
+        let skipped = if condition { 2 } else { 1 };
+        self.cpu.pc += skipped * OPCODE_SIZE;
+    }
+
+    fn logic(&mut self, op: Operands, f: fn(u8, u8) -> u8) {
+        let result = f(self.cpu.read_register(op.x), self.cpu.read_register(op.y));
+        self.cpu.write_register(op.x, result);
+        if self.quirks.logic {
+            self.cpu.write_register(0xF, 0);
+        }
+    }
+
+    fn shift_source(&self, op: Operands) -> u8 {
+        if self.quirks.shift {
+            self.cpu.read_register(op.x)
+        } else {
+            self.cpu.read_register(op.y)
+        }
+    }
+
+    /// Translates the code at `start`, or returns `None` when the first instruction
+    /// can't be translated or cached and the caller should fall back to `step`.
+    fn build_block(&self, start: u16) -> Option<Block<B>> {
+        let mut body = Vec::new();
+        let mut terminator = None;
+
+        let mut addr = start;
+        while body.len() < MAX_BLOCK_LEN
+            && (addr as usize) + 1 < MEMORY_SIZE
+            && self.bus.cacheable(addr)
+            && self.bus.cacheable(addr + 1)
+        {
+            let hex_opcode = u16::from_be_bytes([self.bus.peek(addr), self.bus.peek(addr + 1)]);
+            let Ok(opcode) = Opcode::decode(hex_opcode) else {
+                break;
+            };
+            let operands = Operands::from(hex_opcode);
+            addr += OPCODE_SIZE;
+            match handler(&opcode) {
+                Some(handler) => body.push(Instruction {
+                    handler,
+                    operands,
+                    opcode,
+                }),
+                None => {
+                    terminator = Some(Terminator {
+                        handler: branch_handler(&opcode),
+                        operands,
+                        opcode,
+                    });
+                    break;
+                }
+            }
+        }
+
+        if body.is_empty() && terminator.is_none() {
+            return None;
+        }
+        Some(Block {
+            start,
+            end: addr,
+            body,
+            terminator,
+        })
+    }
+
+    /// Charges `opcode` against the frame's budget, or returns false when the frame is over.
+    /// Like the interpreter, refuses an instruction that can't run before paying for it.
+    fn charge(&mut self, opcode: &Opcode, instructions_left: &mut u32) -> Result<bool, RunError> {
+        match self.timing {
+            Timing::Fixed => {
+                if *instructions_left == 0 {
+                    return Ok(false);
+                }
+                self.check_stack(opcode)?;
+                *instructions_left -= 1;
+            }
+            Timing::CosmacVip => {
+                if self.cycle_budget <= 0 {
+                    return Ok(false);
+                }
+                self.check_stack(opcode)?;
+                self.cycle_budget -= timing::vip_cycles(opcode, &self.cpu) as i64;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Runs `block` from its start, or returns false if the frame ran out of budget first.
+    fn run_block(
+        &mut self,
+        block: &Block<B>,
+        instructions_left: &mut u32,
+    ) -> Result<bool, RunError> {
+        let body_fits =
+            self.timing == Timing::Fixed && *instructions_left as usize > block.body.len();
+        if body_fits {
+            *instructions_left -= block.body.len() as u32;
+            for instruction in &block.body {
+                (instruction.handler)(self, instruction.operands);
+            }
+        } else {
+            for (i, instruction) in block.body.iter().enumerate() {
+                if !self.charge(&instruction.opcode, instructions_left)? {
+                    self.cpu.pc = block.start + i as u16 * OPCODE_SIZE;
+                    return Ok(false);
+                }
+                (instruction.handler)(self, instruction.operands);
+            }
+        }
+
+        self.cpu.pc = block.start + block.body.len() as u16 * OPCODE_SIZE;
+        let Some(terminator) = &block.terminator else {
+            return Ok(true);
+        };
+        if !self.charge(&terminator.opcode, instructions_left)? {
+            return Ok(false);
+        }
+        match terminator.handler {
+            Some(handler) => handler(self, terminator.operands),
+            // Also drops the blocks it writes over
+            None => self.run_instruction(terminator.opcode),
+        }
+        Ok(true)
+    }
+
+    /// Runs one frame worth of instructions from cached blocks, without ticking the timers.
+    pub(super) fn run_threaded(&mut self) -> Result<(), RunError> {
+        let mut instructions_left = self.tickrate;
+        if self.timing == Timing::CosmacVip {
+            self.cycle_budget += VIP_FRAME_BUDGET as i64;
+        }
+
+        while !self.waiting_vblank {
+            let pc = self.cpu.pc;
+            let block = match self.blocks.take(pc) {
+                Some(block) => Some(block),
+                None => self.build_block(pc).map(|block| {
+                    self.blocks.start_running(&block);
+                    Box::new(block)
+                }),
+            };
+
+            let Some(block) = block else {
+                // Uncacheable memory or an unknown opcode, the interpreter knows what to do
+                let over = match self.timing {
+                    Timing::Fixed => instructions_left == 0,
+                    Timing::CosmacVip => self.cycle_budget <= 0,
+                };
+                if over {
+                    break;
+                }
+                let cycles = self.step()?;
+                instructions_left = instructions_left.saturating_sub(1);
+                self.cycle_budget -= cycles as i64;
+                continue;
+            };
+
+            let finished = self.run_block(&block, &mut instructions_left);
+            self.blocks.put_back(block);
+            if !finished? {
+                break;
+            }
+        }
+
+        if self.timing == Timing::CosmacVip && self.waiting_vblank {
+            self.cycle_budget = self.cycle_budget.min(0);
+        }
+        Ok(())
+    }
+
+    /// Runs a frame with the threaded engine and the same frame on a copy using the
+    /// interpreter, then panics if they disagree, also on where they stop at an instruction
+    /// that can't run.
+    pub(super) fn run_verified(&mut self) -> Result<(), RunError> {
+        let mut reference = self.reference_copy();
+        let expected = reference.try_run_frame();
+
+        let result = self.run_threaded();
+        if result.is_ok() {
+            self.tick();
+        }
+
+        if let Some(difference) = self.difference(&reference) {
+            panic!(
+                "threaded engine diverged from the interpreter near pc {:#05x}: {}",
+                reference.cpu.pc, difference
+            );
+        }
+        if result != expected {
+            panic!(
+                "threaded engine stopped with {:?}, the interpreter with {:?}",
+                result, expected
+            );
+        }
+        result
+    }
+
+    /// Same state as `self`, on plain RAM and using the interpreter.
+    fn reference_copy(&self) -> Chip8<Ram> {
+        let mut ram = Ram::default();
+        for addr in 0..MEMORY_SIZE as u16 {
+            ram.write(addr, self.bus.peek(addr));
+        }
+        Chip8 {
+            bus: ram,
+            cpu: self.cpu.clone(),
+            display: self.display.clone(),
+            keyboard: self.keyboard.clone(),
+            quirks: self.quirks,
+            tickrate: self.tickrate,
+            timing: self.timing,
+            cycle_budget: self.cycle_budget,
+            waiting_vblank: self.waiting_vblank,
+            rng: self.rng.clone(),
+            decode_cache: DecodeCache::default(),
+            engine: Engine::Interpreter,
+            blocks: BlockCache::default(),
+            recording: None,
+            beep: self.beep.clone(),
+            frame_beep: self.frame_beep.clone(),
+            profiling: None,
+        }
+    }
+
+    fn difference(&self, reference: &Chip8<Ram>) -> Option<String> {
+        if self.cpu != reference.cpu {
+            return Some(format!("cpu {:?}, expected {:?}", self.cpu, reference.cpu));
+        }
+        if self.display != reference.display {
+            return Some("display differs".to_string());
+        }
+        if self.beep != reference.beep {
+            return Some(format!(
+                "beeper sounded {:?}, expected {:?}",
+                self.beep, reference.beep
+            ));
+        }
+        if let Some(addr) =
+            (0..MEMORY_SIZE as u16).find(|addr| self.bus.peek(*addr) != reference.bus.peek(*addr))
+        {
+            return Some(format!(
+                "memory at {:#05x} is {:#04x}, expected {:#04x}",
+                addr,
+                self.bus.peek(addr),
+                reference.bus.peek(addr)
+            ));
+        }
+        if self.cycle_budget != reference.cycle_budget
+            || self.waiting_vblank != reference.waiting_vblank
+        {
+            return Some("frame scheduling differs".to_string());
+        }
+        None
+    }
+}
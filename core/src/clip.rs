@@ -0,0 +1,275 @@
+//! Gameplay clips: sample the screen once per frame, then write an animated GIF or a
+//! Y4M video, for bug reports and docs.
+
+use crate::display::{Display, SCREEN_HEIGHT, SCREEN_WIDTH};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Frames per second of `Chip8::run_frame`.
+const FRAME_RATE: u32 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub background: [u8; 3],
+    pub foreground: [u8; 3],
+}
+
+impl Default for Palette {
+    /// The colors of the web page.
+    fn default() -> Self {
+        Palette {
+            background: [0x09, 0x09, 0x0b],
+            foreground: [0xe4, 0xe4, 0xe7],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClipOptions {
+    pub palette: Palette,
+    /// Width and height of a CHIP-8 pixel in the output.
+    pub scale: usize,
+    /// Frames dropped after each one kept, so 1 halves the frame rate.
+    pub frame_skip: u32,
+}
+
+impl Default for ClipOptions {
+    fn default() -> Self {
+        ClipOptions {
+            palette: Palette::default(),
+            scale: 4,
+            frame_skip: 0,
+        }
+    }
+}
+
+/// Screens sampled once per frame.
+#[derive(Debug, Clone)]
+pub struct Clip {
+    options: ClipOptions,
+    frames: Vec<[u64; SCREEN_HEIGHT]>,
+    // Frames seen by `capture`, kept or not
+    seen: u64,
+}
+
+impl Clip {
+    /// Panics if `options.scale` is 0, or makes the image wider than a GIF allows.
+    pub fn new(options: ClipOptions) -> Self {
+        assert!(
+            options.scale > 0 && SCREEN_WIDTH * options.scale <= u16::MAX as usize,
+            "scale {} is out of range",
+            options.scale
+        );
+        Clip {
+            options,
+            frames: Vec::new(),
+            seen: 0,
+        }
+    }
+
+    /// Call once per frame, e.g. after `run_frame`. Keeps the screen unless the frame is skipped.
+    pub fn capture(&mut self, display: &Display) {
+        if self.seen.is_multiple_of(self.options.frame_skip as u64 + 1) {
+            self.frames.push(*display.rows());
+        }
+        self.seen += 1;
+    }
+
+    pub fn options(&self) -> ClipOptions {
+        self.options
+    }
+
+    /// Frames kept so far.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    fn width(&self) -> usize {
+        SCREEN_WIDTH * self.options.scale
+    }
+
+    fn height(&self) -> usize {
+        SCREEN_HEIGHT * self.options.scale
+    }
+
+    /// The scaled frame, one byte per pixel, 1 for lit.
+    fn pixels(&self, rows: &[u64; SCREEN_HEIGHT]) -> Vec<u8> {
+        let scale = self.options.scale;
+        let mut pixels = Vec::with_capacity(self.width() * self.height());
+        for row in rows {
+            let line: Vec<u8> = (0..SCREEN_WIDTH)
+                .flat_map(|x| {
+                    let lit = (row >> (SCREEN_WIDTH - 1 - x)) & 1;
+                    std::iter::repeat_n(lit as u8, scale)
+                })
+                .collect();
+            for _ in 0..scale {
+                pixels.extend_from_slice(&line);
+            }
+        }
+        pixels
+    }
+
+    /// An animated GIF that loops forever. Runs of identical frames become one longer frame.
+    pub fn to_gif(&self) -> Vec<u8> {
+        let palette = self.options.palette;
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend_from_slice(&(self.width() as u16).to_le_bytes());
+        gif.extend_from_slice(&(self.height() as u16).to_le_bytes());
+        // Global color table of two colors
+        gif.extend_from_slice(&[0x80, 0, 0]);
+        gif.extend_from_slice(&palette.background);
+        gif.extend_from_slice(&palette.foreground);
+        // Loop forever
+        gif.extend_from_slice(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00");
+
+        // Delays are in hundredths of a second, rounded so they add up to the real time
+        let step = self.options.frame_skip as u64 + 1;
+        let centiseconds = |frames: u64| frames * step * 100 / FRAME_RATE as u64;
+        let mut start = 0;
+        while start < self.frames.len() {
+            let mut end = start + 1;
+            while end < self.frames.len() && self.frames[end] == self.frames[start] {
+                end += 1;
+            }
+            let delay = centiseconds(end as u64) - centiseconds(start as u64);
+
+            // Graphic control extension, then the image descriptor
+            gif.extend_from_slice(&[0x21, 0xF9, 0x04, 0x00]);
+            gif.extend_from_slice(&(delay.min(u16::MAX as u64) as u16).to_le_bytes());
+            gif.extend_from_slice(&[0x00, 0x00]);
+            gif.extend_from_slice(&[0x2C, 0, 0, 0, 0]);
+            gif.extend_from_slice(&(self.width() as u16).to_le_bytes());
+            gif.extend_from_slice(&(self.height() as u16).to_le_bytes());
+            gif.push(0x00);
+
+            gif.push(MIN_CODE_SIZE);
+            let data = lzw(&self.pixels(&self.frames[start]));
+            for block in data.chunks(255) {
+                gif.push(block.len() as u8);
+                gif.extend_from_slice(block);
+            }
+            gif.push(0x00);
+            start = end;
+        }
+        gif.push(0x3B);
+        gif
+    }
+
+    /// An uncompressed Y4M video, 4:4:4, at 60 frames per second divided by the frame skip.
+    /// ffmpeg and most players read it.
+    pub fn to_y4m(&self) -> Vec<u8> {
+        let header = alloc::format!(
+            "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C444\n",
+            self.width(),
+            self.height(),
+            FRAME_RATE,
+            self.options.frame_skip + 1
+        );
+        let colors = [
+            ycbcr(self.options.palette.background),
+            ycbcr(self.options.palette.foreground),
+        ];
+        let size = self.width() * self.height();
+        let mut y4m = header.into_bytes();
+        y4m.reserve(self.frames.len() * (6 + 3 * size));
+        for rows in &self.frames {
+            let pixels = self.pixels(rows);
+            y4m.extend_from_slice(b"FRAME\n");
+            for plane in [0, 1, 2] {
+                y4m.extend(pixels.iter().map(|pixel| colors[*pixel as usize][plane]));
+            }
+        }
+        y4m
+    }
+}
+
+/// Full range BT.601, as in JPEG.
+fn ycbcr([r, g, b]: [u8; 3]) -> [u8; 3] {
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+    let y = (299 * r + 587 * g + 114 * b + 500) / 1000;
+    let cb = 128 + (-168736 * r - 331264 * g + 500000 * b + 500000) / 1000000;
+    let cr = 128 + (500000 * r - 418688 * g - 81312 * b + 500000) / 1000000;
+    [y, cb, cr].map(|value| value.clamp(0, 255) as u8)
+}
+
+/// GIF doesn't allow less than 2 bits per pixel, even with two colors.
+const MIN_CODE_SIZE: u8 = 2;
+const MAX_CODES: usize = 4096;
+
+/// Variable-width LZW, as in GIF, of pixels that are 0 or 1.
+fn lzw(pixels: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << MIN_CODE_SIZE;
+    let end = clear + 1;
+    // Code for a string followed by each pixel value, 0 for none yet
+    let mut table = vec![[0u16; 2]; MAX_CODES];
+    let mut next = end + 1;
+    let mut size = MIN_CODE_SIZE as u32 + 1;
+    let mut bits = BitWriter::default();
+
+    bits.write(clear, size);
+    let Some((&first, rest)) = pixels.split_first() else {
+        bits.write(end, size);
+        return bits.finish();
+    };
+    let mut prefix = first as u16;
+    for &pixel in rest {
+        let code = table[prefix as usize][pixel as usize];
+        if code != 0 {
+            prefix = code;
+            continue;
+        }
+        bits.write(prefix, size);
+        if (next as usize) < MAX_CODES {
+            if next == 1 << size {
+                size += 1;
+            }
+            table[prefix as usize][pixel as usize] = next;
+            next += 1;
+        } else {
+            // The table is full, start over
+            bits.write(clear, size);
+            table.iter_mut().for_each(|codes| *codes = [0; 2]);
+            next = end + 1;
+            size = MIN_CODE_SIZE as u32 + 1;
+        }
+        prefix = pixel as u16;
+    }
+    bits.write(prefix, size);
+    if next == 1 << size && size < 12 {
+        size += 1;
+    }
+    bits.write(end, size);
+    bits.finish()
+}
+
+/// Packs codes least significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    len: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.buffer |= (code as u32) << self.len;
+        self.len += size;
+        while self.len >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.len -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
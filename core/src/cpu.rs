@@ -1,7 +1,10 @@
-const STACK_SIZE: usize = 16;
+use crate::ram::MEMORY_SIZE;
+use crate::state::{StateError, StateReader, StateWriter};
+
+pub(crate) const STACK_SIZE: usize = 16;
 pub const PROGRAM_START_ADDRESS: u16 = 0x200; // 512
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Cpu {
     pub delay_timer: u8,
     pub sound_timer: u8,
@@ -59,6 +62,55 @@ impl Cpu {
         self.stack[self.sp as usize]
     }
 
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp as usize]
+    }
+
+    pub(crate) fn save(&self, state: &mut StateWriter) {
+        state.u8(self.delay_timer);
+        state.u8(self.sound_timer);
+        state.u16(self.pc);
+        state.u8(self.sp);
+        state.u16(self.i);
+        for value in self.stack {
+            state.u16(value);
+        }
+        state.bytes(&self.register);
+    }
+
+    pub(crate) fn restore(state: &mut StateReader) -> Result<Cpu, StateError> {
+        let mut cpu = Cpu {
+            delay_timer: state.u8()?,
+            sound_timer: state.u8()?,
+            pc: state.u16()?,
+            sp: state.u8()?,
+            i: state.u16()?,
+            ..Cpu::default()
+        };
+        // Anything else would be found out on the next fetch or memory access, by a panic
+        if cpu.pc as usize > MEMORY_SIZE - 2 {
+            return Err(StateError::Invalid("pc"));
+        }
+        if cpu.i as usize >= MEMORY_SIZE {
+            return Err(StateError::Invalid("i"));
+        }
+        if cpu.sp as usize > STACK_SIZE {
+            return Err(StateError::Invalid("stack pointer"));
+        }
+        for value in &mut cpu.stack {
+            *value = state.u16()?;
+        }
+        if cpu
+            .stack()
+            .iter()
+            .any(|addr| *addr as usize > MEMORY_SIZE - 2)
+        {
+            return Err(StateError::Invalid("stack"));
+        }
+        cpu.register = state.array()?;
+        Ok(cpu)
+    }
+
     #[cfg(feature = "profile")]
     pub fn stack_profile(&self) {
         for (idx, value) in self.stack.iter().enumerate() {
@@ -0,0 +1,241 @@
+//! ROM metadata lookup, keyed by the SHA-1 of the ROM bytes.
+//!
+//! The data uses the same layout as the community
+//! [chip-8-database](https://github.com/chip-8/chip-8-database): `programs.json`,
+//! `sha1-hashes.json` and `platforms.json`. A small database covering the bundled
+//! ROMs is embedded, and the full one can be loaded from a local checkout.
+
+use crate::quirks::Quirks;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+const EMBEDDED_PROGRAMS: &str = include_str!("../data/chip-8-database/programs.json");
+const EMBEDDED_HASHES: &str = include_str!("../data/chip-8-database/sha1-hashes.json");
+const EMBEDDED_PLATFORMS: &str = include_str!("../data/chip-8-database/platforms.json");
+
+/// Platform assumed for ROMs whose entry doesn't list any.
+pub const DEFAULT_PLATFORM: &str = "modernChip8";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    roms: BTreeMap<String, RomEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RomEntry {
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: BTreeMap<String, QuirkOverrides>,
+    tickrate: Option<u32>,
+    start_address: Option<u16>,
+    #[serde(default)]
+    keys: BTreeMap<String, u8>,
+    colors: Option<Colors>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Platform {
+    id: String,
+    name: String,
+    default_tickrate: Option<u32>,
+    #[serde(default)]
+    quirks: QuirkOverrides,
+}
+
+/// Quirks as they appear in the database. Anything missing keeps its previous value.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuirkOverrides {
+    shift: Option<bool>,
+    memory_increment_by_x: Option<bool>,
+    memory_leave_i_unchanged: Option<bool>,
+    wrap: Option<bool>,
+    jump: Option<bool>,
+    vblank: Option<bool>,
+    logic: Option<bool>,
+}
+
+impl QuirkOverrides {
+    fn apply(&self, quirks: &mut Quirks) {
+        let fields = [
+            (self.shift, &mut quirks.shift),
+            (
+                self.memory_increment_by_x,
+                &mut quirks.memory_increment_by_x,
+            ),
+            (
+                self.memory_leave_i_unchanged,
+                &mut quirks.memory_leave_i_unchanged,
+            ),
+            (self.wrap, &mut quirks.wrap),
+            (self.jump, &mut quirks.jump),
+            (self.vblank, &mut quirks.vblank),
+            (self.logic, &mut quirks.logic),
+        ];
+        for (value, quirk) in fields {
+            if let Some(value) = value {
+                *quirk = value;
+            }
+        }
+    }
+}
+
+/// Colors suggested for a ROM, as CSS-style hex strings.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Colors {
+    /// Background first, then the color of lit pixels.
+    #[serde(default)]
+    pub pixels: Vec<String>,
+    pub buzzer: Option<String>,
+    pub silence: Option<String>,
+}
+
+/// Everything the database knows about a ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    /// Platform id the ROM targets, e.g. `originalChip8`. `DEFAULT_PLATFORM` when the
+    /// database doesn't say.
+    pub platform: String,
+    /// Human readable name of `platform`, when the platform is known.
+    pub platform_name: Option<String>,
+    /// Quirks of the platform, with the ROM's own overrides applied.
+    pub quirks: Quirks,
+    /// Recommended instructions per frame.
+    pub tickrate: Option<u32>,
+    pub start_address: Option<u16>,
+    /// What each key does, e.g. `up` => 5.
+    pub keys: BTreeMap<String, u8>,
+    pub colors: Option<Colors>,
+}
+
+#[derive(Debug)]
+pub enum DatabaseError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// `sha1-hashes.json` points at a program that doesn't exist.
+    InvalidIndex {
+        hash: String,
+        index: usize,
+    },
+}
+
+impl fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatabaseError::Io(err) => write!(f, "can't read database: {}", err),
+            DatabaseError::Json(err) => write!(f, "invalid database JSON: {}", err),
+            DatabaseError::InvalidIndex { hash, index } => {
+                write!(f, "hash {} points at missing program {}", hash, index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DatabaseError {}
+
+impl From<std::io::Error> for DatabaseError {
+    fn from(err: std::io::Error) -> Self {
+        DatabaseError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for DatabaseError {
+    fn from(err: serde_json::Error) -> Self {
+        DatabaseError::Json(err)
+    }
+}
+
+#[derive(Debug)]
+pub struct RomDatabase {
+    programs: Vec<Program>,
+    hashes: BTreeMap<String, usize>,
+    platforms: Vec<Platform>,
+}
+
+impl RomDatabase {
+    /// The database embedded in the crate. It only knows about the bundled ROMs.
+    pub fn embedded() -> Self {
+        RomDatabase::from_json(EMBEDDED_PROGRAMS, EMBEDDED_HASHES, EMBEDDED_PLATFORMS)
+            .expect("embedded database is valid")
+    }
+
+    /// Reads `programs.json`, `sha1-hashes.json` and `platforms.json` from `dir`,
+    /// e.g. the `database` directory of a chip-8-database checkout.
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self, DatabaseError> {
+        let dir = dir.as_ref();
+        let programs = std::fs::read_to_string(dir.join("programs.json"))?;
+        let hashes = std::fs::read_to_string(dir.join("sha1-hashes.json"))?;
+        let platforms = std::fs::read_to_string(dir.join("platforms.json"))?;
+        RomDatabase::from_json(&programs, &hashes, &platforms)
+    }
+
+    pub fn from_json(programs: &str, hashes: &str, platforms: &str) -> Result<Self, DatabaseError> {
+        let programs: Vec<Program> = serde_json::from_str(programs)?;
+        let hashes: BTreeMap<String, usize> = serde_json::from_str(hashes)?;
+        let platforms = serde_json::from_str(platforms)?;
+
+        if let Some((hash, index)) = hashes.iter().find(|(_, index)| **index >= programs.len()) {
+            return Err(DatabaseError::InvalidIndex {
+                hash: hash.clone(),
+                index: *index,
+            });
+        }
+
+        Ok(RomDatabase {
+            programs,
+            hashes,
+            platforms,
+        })
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<RomInfo> {
+        self.lookup_hash(&sha1_hex(rom))
+    }
+
+    /// Looks up a ROM by its lowercase hex SHA-1.
+    pub fn lookup_hash(&self, hash: &str) -> Option<RomInfo> {
+        let program = &self.programs[*self.hashes.get(hash)?];
+        let rom = program.roms.get(hash)?;
+        let platform_id = rom
+            .platforms
+            .first()
+            .map_or(DEFAULT_PLATFORM, String::as_str);
+        let platform = self.platforms.iter().find(|p| p.id == platform_id);
+
+        let mut quirks = Quirks::default();
+        if let Some(platform) = platform {
+            platform.quirks.apply(&mut quirks);
+        }
+        if let Some(overrides) = rom.quirky_platforms.get(platform_id) {
+            overrides.apply(&mut quirks);
+        }
+
+        Some(RomInfo {
+            title: program.title.clone(),
+            authors: program.authors.clone(),
+            platform: platform_id.to_string(),
+            platform_name: platform.map(|p| p.name.clone()),
+            quirks,
+            tickrate: rom.tickrate.or(platform.and_then(|p| p.default_tickrate)),
+            start_address: rom.start_address,
+            keys: rom.keys.clone(),
+            colors: rom.colors.clone(),
+        })
+    }
+}
+
+/// Lowercase hex SHA-1 of `rom`, the key used by the database.
+pub fn sha1_hex(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
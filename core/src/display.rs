@@ -1,26 +1,136 @@
+use crate::state::{StateError, StateReader, StateWriter};
+use std::fmt;
+
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
 
-#[derive(Debug, Clone)]
+/// The framebuffer, one `u64` per row. The most significant bit is the leftmost pixel,
+/// so a sprite byte lines up with the screen after a shift.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Display {
-    // 64px wide and 32px tall
-    pub screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    rows: [u64; SCREEN_HEIGHT],
 }
 
 impl Default for Display {
     fn default() -> Self {
         Display {
-            screen: [false; 64 * 32],
+            rows: [0; SCREEN_HEIGHT],
         }
     }
 }
 
 impl Display {
     pub fn clear(&mut self) {
-        self.screen = [false; 64 * 32];
+        self.rows = [0; SCREEN_HEIGHT];
+    }
+
+    /// Whether the pixel at `x`, `y` is lit. Panics if it's off the screen.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        assert!(x < SCREEN_WIDTH, "x {} is off the screen", x);
+        self.rows[y] & Self::mask(x) != 0
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        assert!(x < SCREEN_WIDTH, "x {} is off the screen", x);
+        if on {
+            self.rows[y] |= Self::mask(x);
+        } else {
+            self.rows[y] &= !Self::mask(x);
+        }
+    }
+
+    /// The packed rows, top to bottom.
+    pub fn rows(&self) -> &[u64; SCREEN_HEIGHT] {
+        &self.rows
+    }
+
+    /// Positions of the lit pixels, row by row.
+    pub fn lit_pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.rows.iter().enumerate().flat_map(|(y, row)| {
+            let mut bits = *row;
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let x = bits.leading_zeros() as usize;
+                bits &= !Self::mask(x);
+                Some((x, y))
+            })
+        })
+    }
+
+    /// XORs one sprite row onto row `y`, starting at column `x`. The part past the right
+    /// edge wraps around to the left when `wrap` is set, and is clipped otherwise.
+    /// Returns whether a lit pixel was turned off.
+    pub fn draw_sprite_row(&mut self, x: usize, y: usize, sprite_row: u8, wrap: bool) -> bool {
+        let sprite = (sprite_row as u64) << (SCREEN_WIDTH - 8);
+        let bits = if wrap {
+            sprite.rotate_right(x as u32)
+        } else {
+            sprite.checked_shr(x as u32).unwrap_or(0)
+        };
+        let row = &mut self.rows[y];
+        let collision = *row & bits != 0;
+        *row ^= bits;
+        collision
+    }
+
+    /// Moves everything down by `n` rows, blanking the rows uncovered at the top.
+    pub fn scroll_down(&mut self, n: usize) {
+        let n = n.min(SCREEN_HEIGHT);
+        self.rows.copy_within(..SCREEN_HEIGHT - n, n);
+        self.rows[..n].fill(0);
+    }
+
+    /// Moves everything up by `n` rows, blanking the rows uncovered at the bottom.
+    pub fn scroll_up(&mut self, n: usize) {
+        let n = n.min(SCREEN_HEIGHT);
+        self.rows.copy_within(n.., 0);
+        self.rows[SCREEN_HEIGHT - n..].fill(0);
     }
 
-    pub fn to_xy(&self, idx: usize) -> (usize, usize) {
-        (idx % SCREEN_WIDTH, idx / SCREEN_WIDTH)
+    /// Moves everything `n` pixels to the left, blanking the columns uncovered on the right.
+    pub fn scroll_left(&mut self, n: usize) {
+        for row in &mut self.rows {
+            *row = row.checked_shl(n as u32).unwrap_or(0);
+        }
+    }
+
+    /// Moves everything `n` pixels to the right, blanking the columns uncovered on the left.
+    pub fn scroll_right(&mut self, n: usize) {
+        for row in &mut self.rows {
+            *row = row.checked_shr(n as u32).unwrap_or(0);
+        }
+    }
+
+    pub(crate) fn save(&self, state: &mut StateWriter) {
+        for row in self.rows {
+            state.u64(row);
+        }
+    }
+
+    pub(crate) fn restore(state: &mut StateReader) -> Result<Display, StateError> {
+        let mut display = Display::default();
+        for row in &mut display.rows {
+            *row = state.u64()?;
+        }
+        Ok(display)
+    }
+
+    fn mask(x: usize) -> u64 {
+        1 << (SCREEN_WIDTH - 1 - x)
+    }
+}
+
+/// Draws the screen as text, `#` for lit pixels and `.` for the rest, one line per row.
+impl fmt::Display for Display {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for y in 0..SCREEN_HEIGHT {
+            for x in 0..SCREEN_WIDTH {
+                write!(f, "{}", if self.pixel(x, y) { '#' } else { '.' })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
     }
 }
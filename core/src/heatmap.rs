@@ -0,0 +1,334 @@
+//! Memory heatmaps: how often each byte was read, written and executed, to tell code,
+//! sprite data and variables apart in a ROM.
+//!
+//! ```
+//! use core::chip8::Chip8;
+//! use core::heatmap::HeatmapBus;
+//! use core::Ram;
+//!
+//! let mut chip8 = Chip8::with_bus(HeatmapBus::new(Ram::default()));
+//! chip8.load(&[0x12, 0x00]).unwrap();
+//! chip8.run_frame();
+//! assert_eq!(chip8.bus().heatmap().executes(0x200), 10);
+//! ```
+
+use crate::bus::{Access, Bus};
+use crate::ram::{Ram, MEMORY_SIZE};
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use std::fmt::{self, Write};
+
+/// Bytes per row of the image and of the text map, so memory is a 64x64 square.
+const ROW: usize = 64;
+
+/// What a byte was used for, from the accesses it got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Usage {
+    Untouched,
+    /// Only read, like sprites and constants.
+    Read,
+    /// Written, like variables.
+    Written,
+    Executed,
+    /// Executed and written: self-modifying code.
+    Modified,
+}
+
+impl Usage {
+    /// The character standing for it in `Heatmap::map`.
+    pub fn symbol(&self) -> char {
+        match self {
+            Usage::Untouched => '.',
+            Usage::Read => 'r',
+            Usage::Written => 'w',
+            Usage::Executed => 'x',
+            Usage::Modified => 'm',
+        }
+    }
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Usage::Untouched => "untouched",
+            Usage::Read => "read",
+            Usage::Written => "written",
+            Usage::Executed => "code",
+            Usage::Modified => "modified code",
+        };
+        f.pad(name)
+    }
+}
+
+/// Read, write and execute counts of every byte of memory. Executing counts both bytes
+/// of the instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heatmap {
+    reads: Vec<u64>,
+    writes: Vec<u64>,
+    executes: Vec<u64>,
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Heatmap {
+            reads: vec![0; MEMORY_SIZE],
+            writes: vec![0; MEMORY_SIZE],
+            executes: vec![0; MEMORY_SIZE],
+        }
+    }
+}
+
+impl Heatmap {
+    pub fn record(&mut self, access: Access, addr: u16) {
+        let counts = match access {
+            Access::Fetch => &mut self.executes,
+            Access::Read => &mut self.reads,
+            Access::Write => &mut self.writes,
+        };
+        if let Some(count) = counts.get_mut(addr as usize) {
+            *count += 1;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Heatmap::default();
+    }
+
+    pub fn reads(&self, addr: u16) -> u64 {
+        self.reads.get(addr as usize).copied().unwrap_or(0)
+    }
+
+    pub fn writes(&self, addr: u16) -> u64 {
+        self.writes.get(addr as usize).copied().unwrap_or(0)
+    }
+
+    pub fn executes(&self, addr: u16) -> u64 {
+        self.executes.get(addr as usize).copied().unwrap_or(0)
+    }
+
+    pub fn usage(&self, addr: u16) -> Usage {
+        match (self.executes(addr), self.writes(addr), self.reads(addr)) {
+            (0, 0, 0) => Usage::Untouched,
+            (0, 0, _) => Usage::Read,
+            (0, _, _) => Usage::Written,
+            (_, 0, _) => Usage::Executed,
+            _ => Usage::Modified,
+        }
+    }
+
+    /// A 64x64 RGB image, one pixel per byte and row by row from address 0: red for reads,
+    /// green for writes and blue for executes. Brightness grows with the log of the
+    /// count, relative to the busiest byte of each kind. Each pixel is `scale` pixels wide.
+    pub fn to_rgb(&self, scale: usize) -> Vec<u8> {
+        let brightness = |counts: &[u64]| {
+            let max = bits(counts.iter().copied().max().unwrap_or(0)).max(1);
+            move |count: u64| match bits(count) {
+                0 => 0,
+                bits => (64 + 191 * bits / max) as u8,
+            }
+        };
+        let (red, green, blue) = (
+            brightness(&self.reads),
+            brightness(&self.writes),
+            brightness(&self.executes),
+        );
+
+        let side = ROW * scale;
+        let mut rgb = Vec::with_capacity(side * side * 3);
+        for y in 0..side {
+            for x in 0..side {
+                let addr = y / scale * ROW + x / scale;
+                rgb.extend([
+                    red(self.reads[addr]),
+                    green(self.writes[addr]),
+                    blue(self.executes[addr]),
+                ]);
+            }
+        }
+        rgb
+    }
+
+    /// `to_rgb` as a PNG file. Panics if `scale` is 0.
+    pub fn to_png(&self, scale: usize) -> Vec<u8> {
+        assert!(scale > 0, "scale must be at least 1");
+        let side = (ROW * scale) as u32;
+        let rgb = self.to_rgb(scale);
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&side.to_be_bytes());
+        header.extend_from_slice(&side.to_be_bytes());
+        // 8 bits per channel, RGB, no interlacing
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        // Every row starts with filter type 0
+        let mut raw = Vec::with_capacity(rgb.len() + side as usize);
+        for row in rgb.chunks(side as usize * 3) {
+            raw.push(0);
+            raw.extend_from_slice(row);
+        }
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        png_chunk(&mut png, b"IHDR", &header);
+        png_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+        png_chunk(&mut png, b"IEND", &[]);
+        png
+    }
+
+    /// Memory as text, 64 bytes a line, with the `Usage::symbol` of every byte.
+    pub fn map(&self) -> String {
+        let mut map = String::new();
+        for start in (0..MEMORY_SIZE).step_by(ROW) {
+            let _ = write!(map, "{:#05X} ", start);
+            map.extend((start..start + ROW).map(|addr| self.usage(addr as u16).symbol()));
+            map.push('\n');
+        }
+        map
+    }
+
+    /// A table of the runs of bytes used the same way, with their counts.
+    pub fn table(&self) -> String {
+        let mut table = String::new();
+        let _ = writeln!(
+            table,
+            "{:5}  {:5}  {:13} {:>8} {:>10} {:>10}",
+            "start", "end", "usage", "reads", "writes", "executes"
+        );
+        let mut start = 0;
+        while start < MEMORY_SIZE {
+            let usage = self.usage(start as u16);
+            let mut end = start + 1;
+            while end < MEMORY_SIZE && self.usage(end as u16) == usage {
+                end += 1;
+            }
+            if usage != Usage::Untouched {
+                let sum = |counts: &[u64]| counts[start..end].iter().sum::<u64>();
+                let _ = writeln!(
+                    table,
+                    "{:#05X}  {:#05X}  {:13} {:8} {:10} {:10}",
+                    start,
+                    end - 1,
+                    usage,
+                    sum(&self.reads),
+                    sum(&self.writes),
+                    sum(&self.executes)
+                );
+            }
+            start = end;
+        }
+        table
+    }
+}
+
+/// Significant bits of `count`, a cheap log2 that works without `std`.
+fn bits(count: u64) -> u64 {
+    (u64::BITS - count.leading_zeros()) as u64
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// A zlib stream of uncompressed deflate blocks. Heatmaps are small enough.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        zlib.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        zlib.push(blocks.peek().is_none() as u8);
+        let len = block.len() as u16;
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), byte| {
+        let a = (a + *byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    zlib.extend_from_slice(&(b << 16 | a).to_be_bytes());
+    zlib
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| {
+            if crc & 1 == 1 {
+                crc >> 1 ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// Counts every interpreter access to `B` in a `Heatmap`. `peek` and `poke` aren't counted,
+/// so loading a ROM doesn't show up.
+#[derive(Debug, Clone, Default)]
+pub struct HeatmapBus<B: Bus = Ram> {
+    inner: B,
+    heatmap: Heatmap,
+}
+
+impl<B: Bus> HeatmapBus<B> {
+    pub fn new(inner: B) -> Self {
+        HeatmapBus {
+            inner,
+            heatmap: Heatmap::default(),
+        }
+    }
+
+    pub fn heatmap(&self) -> &Heatmap {
+        &self.heatmap
+    }
+
+    /// To `clear` it, e.g. after the title screen.
+    pub fn heatmap_mut(&mut self) -> &mut Heatmap {
+        &mut self.heatmap
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B: Bus> Bus for HeatmapBus<B> {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.heatmap.record(Access::Read, addr);
+        self.inner.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.heatmap.record(Access::Write, addr);
+        self.inner.write(addr, value)
+    }
+
+    fn fetch(&mut self, addr: u16) -> u8 {
+        self.heatmap.record(Access::Fetch, addr);
+        self.inner.fetch(addr)
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.inner.peek(addr)
+    }
+
+    fn poke(&mut self, addr: u16, value: u8) {
+        self.inner.poke(addr, value)
+    }
+
+    /// Every fetch has to reach the heatmap, so nothing is decoded once and reused.
+    fn cacheable(&self, _addr: u16) -> bool {
+        false
+    }
+}
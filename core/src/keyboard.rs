@@ -2,7 +2,7 @@ use std::ops::Deref;
 
 const NUM_KEYS: usize = 16;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Keyboard {
     keys: [bool; NUM_KEYS],
 }
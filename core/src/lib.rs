@@ -1,8 +1,36 @@
+//! A CHIP-8 interpreter. Without the default `std` feature it's `no_std` and only needs `alloc`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+// Keeps `std::` paths working in both builds. Only the `core` subset is used.
+#[cfg(not(feature = "std"))]
+extern crate core as std;
+
+pub mod audio;
+pub mod bus;
+mod cache;
+pub mod cheat;
 pub mod chip8;
+pub mod clip;
 mod cpu;
+#[cfg(feature = "database")]
+pub mod database;
 mod display;
+pub mod heatmap;
 mod keyboard;
+pub mod movie;
+#[cfg(feature = "std")]
+pub mod netplay;
+pub mod opcode;
+pub mod profiler;
+mod quirks;
 mod ram;
+pub mod state;
+pub mod timing;
 
+pub use display::Display;
 pub use display::SCREEN_HEIGHT;
 pub use display::SCREEN_WIDTH;
+pub use quirks::Quirks;
+pub use ram::{Ram, MEMORY_SIZE};
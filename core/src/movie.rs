@@ -0,0 +1,310 @@
+//! Input movies: everything needed to replay a session bit for bit, for bug reports and regression tests.
+//!
+//! A movie starts from a save state, which holds the ROM, the configuration and the
+//! random number generator, and lists every key change with the frame and cycle it
+//! happened on. Replaying it ends with a check of the screen.
+
+use crate::bus::Bus;
+use crate::chip8::{Chip8, RunError};
+use crate::display::Display;
+use crate::state::{hash, StateError, StateReader, StateWriter};
+use alloc::vec::Vec;
+use std::fmt;
+
+/// First bytes of every movie.
+pub const MOVIE_MAGIC: [u8; 4] = *b"C8MV";
+/// Bumped whenever the layout changes. Older movies are rejected.
+pub const MOVIE_VERSION: u8 = 1;
+
+/// A key going down or up. `frame` counts from the start of the recording, and
+/// `cycle` is the number of instructions run with `emulate_cycle` since that frame began.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub frame: u64,
+    pub cycle: u32,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// A frame that wasn't simply `run_frame`: `cycles` calls to `emulate_cycle`, then
+/// `run_frame` if `full_frame`, or `tick_timers` otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SteppedFrame {
+    frame: u64,
+    cycles: u32,
+    full_frame: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieError {
+    /// Doesn't start with `MOVIE_MAGIC`, so it isn't a movie.
+    BadMagic,
+    /// Saved by a version of the emulator with a different layout.
+    UnsupportedVersion(u8),
+    /// The movie or the save state it starts from can't be read.
+    Corrupt(StateError),
+    /// The replay ended on a different screen than the recording.
+    Desync { expected: u64, actual: u64 },
+    /// The replay reached an instruction that can't run.
+    Run(RunError),
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::BadMagic => write!(f, "not a movie"),
+            MovieError::UnsupportedVersion(version) => write!(
+                f,
+                "movie version {} is not supported, expected {}",
+                version, MOVIE_VERSION
+            ),
+            MovieError::Corrupt(error) => write!(f, "movie is corrupt: {}", error),
+            MovieError::Desync { expected, actual } => write!(
+                f,
+                "replay desynced: screen hash is {:016x}, expected {:016x}",
+                actual, expected
+            ),
+            MovieError::Run(error) => write!(f, "replay stopped: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl From<StateError> for MovieError {
+    fn from(error: StateError) -> Self {
+        MovieError::Corrupt(error)
+    }
+}
+
+impl From<RunError> for MovieError {
+    fn from(error: RunError) -> Self {
+        MovieError::Run(error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    start: Vec<u8>,
+    keys: Vec<KeyEvent>,
+    stepped: Vec<SteppedFrame>,
+    frames: u64,
+    // Instructions run with `emulate_cycle` after the last whole frame
+    end_cycles: u32,
+    screen_hash: u64,
+}
+
+impl Movie {
+    /// Whole frames recorded.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn keys(&self) -> &[KeyEvent] {
+        &self.keys
+    }
+
+    /// Hash of the screen when the recording stopped.
+    pub fn screen_hash(&self) -> u64 {
+        self.screen_hash
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut movie = StateWriter::with_header(MOVIE_MAGIC, MOVIE_VERSION);
+        movie.u32(self.start.len() as u32);
+        movie.bytes(&self.start);
+        movie.u64(self.frames);
+        movie.u32(self.end_cycles);
+        movie.u64(self.screen_hash);
+
+        movie.u32(self.keys.len() as u32);
+        for event in &self.keys {
+            movie.u64(event.frame);
+            movie.u32(event.cycle);
+            movie.u8(event.key);
+            movie.bool(event.pressed);
+        }
+        movie.u32(self.stepped.len() as u32);
+        for stepped in &self.stepped {
+            movie.u64(stepped.frame);
+            movie.u32(stepped.cycles);
+            movie.bool(stepped.full_frame);
+        }
+        movie.finish()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Movie, MovieError> {
+        let mut movie = StateReader::with_header(bytes, MOVIE_MAGIC, MOVIE_VERSION).map_err(
+            |error| match error {
+                StateError::BadMagic => MovieError::BadMagic,
+                StateError::UnsupportedVersion(version) => MovieError::UnsupportedVersion(version),
+                error => MovieError::Corrupt(error),
+            },
+        )?;
+        let len = movie.u32()? as usize;
+        let start = movie.slice(len)?.to_vec();
+        let frames = movie.u64()?;
+        let end_cycles = movie.u32()?;
+        let screen_hash = movie.u64()?;
+
+        let mut keys: Vec<KeyEvent> = Vec::new();
+        for _ in 0..movie.u32()? {
+            let event = KeyEvent {
+                frame: movie.u64()?,
+                cycle: movie.u32()?,
+                key: movie.u8()?,
+                pressed: movie.bool()?,
+            };
+            if event.key > 0xF {
+                return Err(StateError::Invalid("key").into());
+            }
+            if keys
+                .last()
+                .is_some_and(|last| (last.frame, last.cycle) > (event.frame, event.cycle))
+            {
+                return Err(StateError::Invalid("key order").into());
+            }
+            keys.push(event);
+        }
+        let mut stepped = Vec::new();
+        for _ in 0..movie.u32()? {
+            stepped.push(SteppedFrame {
+                frame: movie.u64()?,
+                cycles: movie.u32()?,
+                full_frame: movie.bool()?,
+            });
+        }
+
+        Ok(Movie {
+            start,
+            keys,
+            stepped,
+            frames,
+            end_cycles,
+            screen_hash,
+        })
+    }
+
+    /// Loads the starting state into `chip8` and plays the movie on it, the same way it
+    /// was driven while recording. Fails if the screen doesn't end up the same, or if an
+    /// instruction can't run.
+    pub fn replay<B: Bus>(&self, chip8: &mut Chip8<B>) -> Result<(), MovieError> {
+        self.replay_with(chip8, |_| {})
+    }
+
+    /// Like `replay`, calling `on_frame` after every frame, e.g. to capture the screen
+    /// or the sound.
+    pub fn replay_with<B: Bus>(
+        &self,
+        chip8: &mut Chip8<B>,
+        mut on_frame: impl FnMut(&Chip8<B>),
+    ) -> Result<(), MovieError> {
+        chip8.load_state(&self.start)?;
+        let mut keys = self.keys.iter().peekable();
+        let mut stepped = self.stepped.iter().peekable();
+
+        for frame in 0..=self.frames {
+            let (cycles, full_frame) = if frame == self.frames {
+                (self.end_cycles, false)
+            } else {
+                match stepped.next_if(|stepped| stepped.frame == frame) {
+                    Some(stepped) => (stepped.cycles, stepped.full_frame),
+                    None => (0, true),
+                }
+            };
+
+            for cycle in 0..=cycles {
+                while let Some(event) =
+                    keys.next_if(|event| (event.frame, event.cycle) <= (frame, cycle))
+                {
+                    chip8.keypress(event.key, event.pressed);
+                }
+                if cycle < cycles {
+                    chip8.try_emulate_cycle()?;
+                }
+            }
+
+            if frame == self.frames {
+                break;
+            }
+            if full_frame {
+                chip8.try_run_frame()?;
+            } else {
+                chip8.tick_timers();
+            }
+            on_frame(chip8);
+        }
+
+        let actual = screen_hash(chip8.get_display());
+        if actual != self.screen_hash {
+            return Err(MovieError::Desync {
+                expected: self.screen_hash,
+                actual,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Keeps track of the position and key changes while `Chip8` records.
+#[derive(Debug, Clone)]
+pub(crate) struct Recording {
+    start: Vec<u8>,
+    frame: u64,
+    cycle: u32,
+    keys: Vec<KeyEvent>,
+    stepped: Vec<SteppedFrame>,
+}
+
+impl Recording {
+    pub fn new(start: Vec<u8>) -> Self {
+        Recording {
+            start,
+            frame: 0,
+            cycle: 0,
+            keys: Vec::new(),
+            stepped: Vec::new(),
+        }
+    }
+
+    pub fn key(&mut self, key: u8, pressed: bool) {
+        self.keys.push(KeyEvent {
+            frame: self.frame,
+            cycle: self.cycle,
+            key,
+            pressed,
+        });
+    }
+
+    pub fn cycle(&mut self) {
+        self.cycle += 1;
+    }
+
+    pub fn end_frame(&mut self, full_frame: bool) {
+        if self.cycle > 0 || !full_frame {
+            self.stepped.push(SteppedFrame {
+                frame: self.frame,
+                cycles: self.cycle,
+                full_frame,
+            });
+        }
+        self.frame += 1;
+        self.cycle = 0;
+    }
+
+    pub fn finish(self, display: &Display) -> Movie {
+        Movie {
+            start: self.start,
+            keys: self.keys,
+            stepped: self.stepped,
+            frames: self.frame,
+            end_cycles: self.cycle,
+            screen_hash: screen_hash(display),
+        }
+    }
+}
+
+/// Hash of the rows, top to bottom.
+fn screen_hash(display: &Display) -> u64 {
+    hash(display.rows().iter().flat_map(|row| row.to_be_bytes()))
+}
@@ -0,0 +1,260 @@
+//! Two-player netplay: two machines run in lockstep over TCP, each peer pressing its own keys.
+//!
+//! The host sends its save state when the peers connect, so both start from the same
+//! memory, settings and random seed. After that only inputs travel: every frame each peer
+//! sends the keys it owns, to be pressed `delay` frames later on both machines, along
+//! with a hash of its state to catch desyncs.
+
+use crate::chip8::{Chip8, RunError};
+use crate::state::StateError;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+/// First bytes sent by both peers.
+pub const NETPLAY_MAGIC: [u8; 4] = *b"C8NP";
+/// Bumped whenever the protocol changes. Both peers must use the same one.
+pub const NETPLAY_VERSION: u8 = 1;
+/// Longest input delay, in frames: 5 seconds. Larger delays are refused rather than
+/// buffered, whichever peer asks for them.
+pub const MAX_NETPLAY_DELAY: u32 = 5 * 60;
+
+#[derive(Debug)]
+pub enum NetplayError {
+    Io(io::Error),
+    /// The peers don't speak the same protocol, want the same keys, or fell out of step.
+    Protocol(&'static str),
+    /// The host's save state couldn't be loaded.
+    State(StateError),
+    /// The machines were in different states at the start of `frame`.
+    Desync {
+        frame: u64,
+        local: u64,
+        remote: u64,
+    },
+    /// The game reached an instruction that can't run.
+    Run(RunError),
+}
+
+impl fmt::Display for NetplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetplayError::Io(error) => write!(f, "connection failed: {}", error),
+            NetplayError::Protocol(reason) => write!(f, "netplay failed: {}", reason),
+            NetplayError::State(error) => write!(f, "{}", error),
+            NetplayError::Desync {
+                frame,
+                local,
+                remote,
+            } => write!(
+                f,
+                "desync at frame {}: state hash is {:016x} here and {:016x} on the peer",
+                frame, local, remote
+            ),
+            NetplayError::Run(error) => write!(f, "the game stopped: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for NetplayError {}
+
+impl From<io::Error> for NetplayError {
+    fn from(error: io::Error) -> Self {
+        NetplayError::Io(error)
+    }
+}
+
+/// One side of a netplay session. Both sides must call `run_frame` the same number of times.
+pub struct Netplay {
+    chip8: Chip8,
+    stream: TcpStream,
+    local_keys: u16,
+    remote_keys: u16,
+    delay: u32,
+    frame: u64,
+    // Local inputs waiting for their frame, the first one is for the current frame
+    local_inputs: VecDeque<u16>,
+    // Hashes of recent frames not yet compared with the peer's, oldest first
+    hashes: VecDeque<(u64, u64)>,
+}
+
+impl Netplay {
+    /// Starts a session on an accepted connection. The peer's machine becomes a copy of
+    /// `chip8`. `keys` is a bitmask of the keys pressed from this side, bit 0 for key 0,
+    /// and inputs take `delay` frames to apply, to hide the latency, at most `MAX_NETPLAY_DELAY`.
+    pub fn host(
+        mut stream: TcpStream,
+        chip8: Chip8,
+        keys: u16,
+        delay: u32,
+    ) -> Result<Self, NetplayError> {
+        check_delay(delay)?;
+        stream.set_nodelay(true)?;
+        let state = chip8.save_state();
+        let mut hello = Vec::with_capacity(state.len() + 15);
+        hello.extend_from_slice(&NETPLAY_MAGIC);
+        hello.push(NETPLAY_VERSION);
+        hello.extend_from_slice(&delay.to_be_bytes());
+        hello.extend_from_slice(&keys.to_be_bytes());
+        hello.extend_from_slice(&(state.len() as u32).to_be_bytes());
+        hello.extend_from_slice(&state);
+        stream.write_all(&hello)?;
+
+        read_header(&mut stream)?;
+        let remote_keys = u16::from_be_bytes(read_array(&mut stream)?);
+        Netplay::new(stream, chip8, keys, remote_keys, delay)
+    }
+
+    /// Joins a session on a connection to the host. `chip8` is overwritten with the host's
+    /// state, only its engine is kept.
+    pub fn join(mut stream: TcpStream, mut chip8: Chip8, keys: u16) -> Result<Self, NetplayError> {
+        stream.set_nodelay(true)?;
+        read_header(&mut stream)?;
+        let delay = u32::from_be_bytes(read_array(&mut stream)?);
+        check_delay(delay)?;
+        let remote_keys = u16::from_be_bytes(read_array(&mut stream)?);
+        let len = u32::from_be_bytes(read_array(&mut stream)?) as usize;
+        let mut state = Vec::new();
+        (&mut stream).take(len as u64).read_to_end(&mut state)?;
+        if state.len() < len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        chip8.load_state(&state).map_err(NetplayError::State)?;
+
+        let mut reply = Vec::with_capacity(7);
+        reply.extend_from_slice(&NETPLAY_MAGIC);
+        reply.push(NETPLAY_VERSION);
+        reply.extend_from_slice(&keys.to_be_bytes());
+        stream.write_all(&reply)?;
+        Netplay::new(stream, chip8, keys, remote_keys, delay)
+    }
+
+    fn new(
+        stream: TcpStream,
+        chip8: Chip8,
+        local_keys: u16,
+        remote_keys: u16,
+        delay: u32,
+    ) -> Result<Self, NetplayError> {
+        if local_keys & remote_keys != 0 {
+            return Err(NetplayError::Protocol(
+                "both peers want some of the same keys",
+            ));
+        }
+        Ok(Netplay {
+            chip8,
+            stream,
+            local_keys,
+            remote_keys,
+            delay,
+            frame: 0,
+            local_inputs: VecDeque::from(vec![0; delay as usize]),
+            hashes: VecDeque::new(),
+        })
+    }
+
+    /// Runs the next frame, once the peer's inputs for it have arrived. `pressed` is a
+    /// bitmask of keys held down here, keys owned by the peer are ignored.
+    pub fn run_frame(&mut self, pressed: u16) -> Result<(), NetplayError> {
+        let hash = self.chip8.state_hash();
+        let input = pressed & self.local_keys;
+        let mut message = [0; 18];
+        message[..8].copy_from_slice(&(self.frame + self.delay as u64).to_be_bytes());
+        message[8..10].copy_from_slice(&input.to_be_bytes());
+        message[10..].copy_from_slice(&hash.to_be_bytes());
+        self.stream.write_all(&message)?;
+        self.local_inputs.push_back(input);
+        self.hashes.push_back((self.frame, hash));
+
+        let remote = if self.frame < self.delay as u64 {
+            0
+        } else {
+            self.receive()?
+        };
+        let local = self.local_inputs.pop_front().expect("one input per frame");
+
+        for key in 0..16 {
+            let bit = 1 << key;
+            let keys = if self.local_keys & bit != 0 {
+                local
+            } else {
+                remote & self.remote_keys
+            };
+            self.chip8.keypress(key, keys & bit != 0);
+        }
+        self.chip8.try_run_frame().map_err(NetplayError::Run)?;
+        self.frame += 1;
+        Ok(())
+    }
+
+    /// Reads the peer's input for the current frame and checks the hash that came with it.
+    fn receive(&mut self) -> Result<u16, NetplayError> {
+        let message: [u8; 18] = read_array(&mut self.stream)?;
+        let frame = u64::from_be_bytes(message[..8].try_into().unwrap());
+        let keys = u16::from_be_bytes(message[8..10].try_into().unwrap());
+        let remote = u64::from_be_bytes(message[10..].try_into().unwrap());
+        if frame != self.frame {
+            return Err(NetplayError::Protocol("peer is on another frame"));
+        }
+
+        // Sent at the start of the peer's frame `frame - delay`
+        let hashed = frame - self.delay as u64;
+        while let Some((old, _)) = self.hashes.front() {
+            if *old >= hashed {
+                break;
+            }
+            self.hashes.pop_front();
+        }
+        if let Some((_, local)) = self.hashes.pop_front() {
+            if local != remote {
+                return Err(NetplayError::Desync {
+                    frame: hashed,
+                    local,
+                    remote,
+                });
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Frames run so far.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    /// Changes made here must be made on the peer too, or the session desyncs.
+    pub fn chip8_mut(&mut self) -> &mut Chip8 {
+        &mut self.chip8
+    }
+}
+
+fn check_delay(delay: u32) -> Result<(), NetplayError> {
+    if delay > MAX_NETPLAY_DELAY {
+        return Err(NetplayError::Protocol("input delay is too long"));
+    }
+    Ok(())
+}
+
+fn read_header(stream: &mut TcpStream) -> Result<(), NetplayError> {
+    let header: [u8; 5] = read_array(stream)?;
+    if header[..4] != NETPLAY_MAGIC {
+        return Err(NetplayError::Protocol(
+            "peer isn't a CHIP-8 netplay session",
+        ));
+    }
+    if header[4] != NETPLAY_VERSION {
+        return Err(NetplayError::Protocol("peer uses another protocol version"));
+    }
+    Ok(())
+}
+
+fn read_array<const N: usize>(stream: &mut TcpStream) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    stream.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
@@ -0,0 +1,446 @@
+use crate::bus::Bus;
+use crate::ram::MEMORY_SIZE;
+use alloc::vec::Vec;
+use std::fmt::{self, Debug};
+
+/// A decoded CHIP-8 instruction. Variants are named after their encoding, operands are X, Y, N, NN or NNN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    /// Clears the screen.
+    _00e0,
+    /// Returns from a subroutine.
+    _00ee,
+    /// Jumps to address NNN.
+    _1nnn(u16),
+    /// Calls subroutine at NNN.
+    _2nnn(u16),
+    /// Skips the next instruction if VX equals NN. (Usually the next instruction is a jump to skip a code block);
+    _3xnn(u8, u8),
+    /// Skips the next instruction if VX does not equal NN. (Usually the next instruction is a jump to skip a code block);
+    _4xnn(u8, u8),
+    /// Skips the next instruction if VX equals VY. (Usually the next instruction is a jump to skip a code block);
+    /// Like 9XY0, the low nibble must be 0: 5XYN with any other N is an unknown opcode.
+    _5xy0(u8, u8),
+    /// Sets VX to NN.
+    _6xnn(u8, u8),
+    /// Adds NN to VX. (Carry flag is not changed)
+    _7xnn(u8, u8),
+    /// Sets VX to the value of VY.
+    _8xy0(u8, u8),
+    /// Sets VX to VX or VY. (Bitwise OR operation)
+    _8xy1(u8, u8),
+    /// Sets VX to VX and VY. (Bitwise AND operation)
+    _8xy2(u8, u8),
+    /// Sets VX to VX xor VY. (Bitwise XOR operation)
+    _8xy3(u8, u8),
+    /// Adds VY to VX. VF is set to 1 when there's a carry, and to 0 when there isn't.
+    _8xy4(u8, u8),
+    /// VY is subtracted from VX. VF is set to 0 when there's a borrow, and 1 when there isn't.
+    _8xy5(u8, u8),
+    /// Shifts VX right by one. VF is set to the value of the least significant bit of VX before the shift.
+    _8xy6(u8, u8),
+    /// Sets VX to VY minus VX. VF is set to 0 when there's a borrow, and 1 when there isn't.
+    _8xy7(u8, u8),
+    /// Shifts VX left by one. VF is set to the value of the most significant bit of VX before the shift.
+    _8xye(u8, u8),
+    /// Skips the next instruction if VX doesn't equal VY. (Usually the next instruction is a jump to skip a code block);
+    _9xy0(u8, u8),
+    /// Sets I to the address NNN.
+    _Annn(u16),
+    /// Jumps to the address NNN plus V0.
+    _Bnnn(u16),
+    /// Sets VX to the result of a bitwise and operation on a random number (Typically: 0 to 255) and NN.
+    _Cxnn(u8, u8),
+    /// Draws a sprite at coordinate (VX, VY) that has a width of 8 pixels and a height of N pixels. Each row of 8 pixels is read as bit-coded starting from memory location I; I value doesn't change after the execution of this instruction. As described above, VF is set to 1 if any screen pixels are flipped from set to unset when the sprite is drawn, and to 0 if that doesn't happen.
+    _Dxyn(u8, u8, u8),
+    /// Skips the next instruction if the key stored in VX is pressed. (Usually the next instruction is a jump to skip a code block);
+    _Ex9e(u8),
+    /// Skips the next instruction if the key stored in VX isn't pressed. (Usually the next instruction is a jump to skip a code block);
+    _Exa1(u8),
+    /// Sets VX to the value of the delay timer.
+    _Fx07(u8),
+    /// A key press is awaited, and then stored in VX. (Blocking Operation. All instruction halted until next key event);
+    _Fx0a(u8),
+    /// Sets the delay timer to VX.
+    _Fx15(u8),
+    /// Sets the sound timer to VX.
+    _Fx18(u8),
+    /// Adds VX to I. (VF is not affected)
+    _Fx1e(u8),
+    /// Sets I to the location of the sprite for the character in VX. Characters 0-F (in hexadecimal) are represented by a 4x5 font.;
+    _Fx29(u8),
+    /// Stores the binary-coded decimal representation of VX, with the most significant of three digits at the address in I, the middle digit at I plus 1, and the least significant digit at I plus 2. (In other words, take the decimal representation of VX, place the hundreds digit in memory at location in I, the tens digit at location I+1, and the ones digit at location I+2.);
+    _Fx33(u8),
+    /// Stores from V0 to VX (including VX) in memory, starting at address I. The offset from I is increased by 1 for each value written, but I itself is left unmodified.
+    _Fx55(u8),
+    /// Fills from V0 to VX (including VX) with values from memory, starting at address I. The offset from I is increased by 1 for each value written, but I itself is left unmodified.
+    _Fx65(u8),
+}
+
+/// The value doesn't encode any known instruction.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct UnknownOpcodeError(pub u16);
+
+impl Debug for UnknownOpcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UnknownOpcodeError({:#06x})", self.0)
+    }
+}
+
+impl fmt::Display for UnknownOpcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown opcode {:#06x}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownOpcodeError {}
+
+impl TryFrom<u16> for Opcode {
+    type Error = UnknownOpcodeError;
+
+    fn try_from(hex_opcode: u16) -> Result<Self, Self::Error> {
+        use Opcode::*;
+        let hex_nibbles = (
+            ((hex_opcode & 0xF000) >> 12),
+            ((hex_opcode & 0x0F00) >> 8),
+            ((hex_opcode & 0x00F0) >> 4),
+            (hex_opcode & 0x000F),
+        );
+
+        let nnn = hex_opcode & 0x0FFF;
+        let nn = (hex_opcode & 0x00FF) as u8;
+        let x = hex_nibbles.1 as u8;
+        let y = hex_nibbles.2 as u8;
+        let n = hex_nibbles.3 as u8;
+
+        match hex_nibbles {
+            (0, 0, 0xE, 0) => Ok(_00e0),
+            (0, 0, 0xE, 0xE) => Ok(_00ee),
+            (1, _, _, _) => Ok(_1nnn(nnn)),
+            (2, _, _, _) => Ok(_2nnn(nnn)),
+            (3, _, _, _) => Ok(_3xnn(x, nn)),
+            (4, _, _, _) => Ok(_4xnn(x, nn)),
+            (5, _, _, 0) => Ok(_5xy0(x, y)),
+            (6, _, _, _) => Ok(_6xnn(x, nn)),
+            (7, _, _, _) => Ok(_7xnn(x, nn)),
+            (8, _, _, 0) => Ok(_8xy0(x, y)),
+            (8, _, _, 1) => Ok(_8xy1(x, y)),
+            (8, _, _, 2) => Ok(_8xy2(x, y)),
+            (8, _, _, 3) => Ok(_8xy3(x, y)),
+            (8, _, _, 4) => Ok(_8xy4(x, y)),
+            (8, _, _, 5) => Ok(_8xy5(x, y)),
+            (8, _, _, 6) => Ok(_8xy6(x, y)),
+            (8, _, _, 7) => Ok(_8xy7(x, y)),
+            (8, _, _, 0xE) => Ok(_8xye(x, y)),
+            (9, _, _, 0) => Ok(_9xy0(x, y)),
+            (0xA, _, _, _) => Ok(_Annn(nnn)),
+            (0xB, _, _, _) => Ok(_Bnnn(nnn)),
+            (0xC, _, _, _) => Ok(_Cxnn(x, nn)),
+            (0xD, _, _, _) => Ok(_Dxyn(x, y, n)),
+            (0xE, _, 9, 0xE) => Ok(_Ex9e(x)),
+            (0xE, _, 0xA, 0x1) => Ok(_Exa1(x)),
+            (0xF, _, 0, 7) => Ok(_Fx07(x)),
+            (0xF, _, 0, 0xA) => Ok(_Fx0a(x)),
+            (0xF, _, 1, 5) => Ok(_Fx15(x)),
+            (0xF, _, 1, 8) => Ok(_Fx18(x)),
+            (0xF, _, 1, 0xE) => Ok(_Fx1e(x)),
+            (0xF, _, 2, 9) => Ok(_Fx29(x)),
+            (0xF, _, 3, 3) => Ok(_Fx33(x)),
+            (0xF, _, 5, 5) => Ok(_Fx55(x)),
+            (0xF, _, 6, 5) => Ok(_Fx65(x)),
+            _ => Err(UnknownOpcodeError(hex_opcode)),
+        }
+    }
+}
+
+impl From<Opcode> for u16 {
+    fn from(opcode: Opcode) -> Self {
+        opcode.encode()
+    }
+}
+
+const VF: u16 = 1 << 0xF;
+
+/// One bit per register, V0 is bit 0.
+fn registers(from: u8, to: u8) -> u16 {
+    (from..=to & 0xF).fold(0, |mask, register| mask | 1 << register)
+}
+
+impl Opcode {
+    pub fn decode(hex_opcode: u16) -> Result<Opcode, UnknownOpcodeError> {
+        Opcode::try_from(hex_opcode)
+    }
+
+    /// The encoding of the variant, like `"8XY4"`, for reports.
+    pub fn pattern(&self) -> &'static str {
+        use Opcode::*;
+        match self {
+            _00e0 => "00E0",
+            _00ee => "00EE",
+            _1nnn(_) => "1NNN",
+            _2nnn(_) => "2NNN",
+            _3xnn(..) => "3XNN",
+            _4xnn(..) => "4XNN",
+            _5xy0(..) => "5XY0",
+            _6xnn(..) => "6XNN",
+            _7xnn(..) => "7XNN",
+            _8xy0(..) => "8XY0",
+            _8xy1(..) => "8XY1",
+            _8xy2(..) => "8XY2",
+            _8xy3(..) => "8XY3",
+            _8xy4(..) => "8XY4",
+            _8xy5(..) => "8XY5",
+            _8xy6(..) => "8XY6",
+            _8xy7(..) => "8XY7",
+            _8xye(..) => "8XYE",
+            _9xy0(..) => "9XY0",
+            _Annn(_) => "ANNN",
+            _Bnnn(_) => "BNNN",
+            _Cxnn(..) => "CXNN",
+            _Dxyn(..) => "DXYN",
+            _Ex9e(_) => "EX9E",
+            _Exa1(_) => "EXA1",
+            _Fx07(_) => "FX07",
+            _Fx0a(_) => "FX0A",
+            _Fx15(_) => "FX15",
+            _Fx18(_) => "FX18",
+            _Fx1e(_) => "FX1E",
+            _Fx29(_) => "FX29",
+            _Fx33(_) => "FX33",
+            _Fx55(_) => "FX55",
+            _Fx65(_) => "FX65",
+        }
+    }
+
+    /// Operands are cut to the width of their field, 4 bits for X, Y and N and 12 bits
+    /// for NNN, so an out of range operand can't turn into another instruction.
+    pub fn encode(&self) -> u16 {
+        use Opcode::*;
+        let nibble = |operand: u8| (operand & 0xF) as u16;
+        let xy = |base: u16, x: u8, y: u8| base | nibble(x) << 8 | nibble(y) << 4;
+        let xnn = |base: u16, x: u8, nn: u8| base | nibble(x) << 8 | nn as u16;
+        let x = |base: u16, x: u8| base | nibble(x) << 8;
+        let nnn = |base: u16, nnn: u16| base | nnn & 0x0FFF;
+
+        match *self {
+            _00e0 => 0x00E0,
+            _00ee => 0x00EE,
+            _1nnn(addr) => nnn(0x1000, addr),
+            _2nnn(addr) => nnn(0x2000, addr),
+            _3xnn(vx, nn) => xnn(0x3000, vx, nn),
+            _4xnn(vx, nn) => xnn(0x4000, vx, nn),
+            _5xy0(vx, vy) => xy(0x5000, vx, vy),
+            _6xnn(vx, nn) => xnn(0x6000, vx, nn),
+            _7xnn(vx, nn) => xnn(0x7000, vx, nn),
+            _8xy0(vx, vy) => xy(0x8000, vx, vy),
+            _8xy1(vx, vy) => xy(0x8001, vx, vy),
+            _8xy2(vx, vy) => xy(0x8002, vx, vy),
+            _8xy3(vx, vy) => xy(0x8003, vx, vy),
+            _8xy4(vx, vy) => xy(0x8004, vx, vy),
+            _8xy5(vx, vy) => xy(0x8005, vx, vy),
+            _8xy6(vx, vy) => xy(0x8006, vx, vy),
+            _8xy7(vx, vy) => xy(0x8007, vx, vy),
+            _8xye(vx, vy) => xy(0x800E, vx, vy),
+            _9xy0(vx, vy) => xy(0x9000, vx, vy),
+            _Annn(addr) => nnn(0xA000, addr),
+            _Bnnn(addr) => nnn(0xB000, addr),
+            _Cxnn(vx, nn) => xnn(0xC000, vx, nn),
+            _Dxyn(vx, vy, n) => xy(0xD000, vx, vy) | nibble(n),
+            _Ex9e(vx) => x(0xE09E, vx),
+            _Exa1(vx) => x(0xE0A1, vx),
+            _Fx07(vx) => x(0xF007, vx),
+            _Fx0a(vx) => x(0xF00A, vx),
+            _Fx15(vx) => x(0xF015, vx),
+            _Fx18(vx) => x(0xF018, vx),
+            _Fx1e(vx) => x(0xF01E, vx),
+            _Fx29(vx) => x(0xF029, vx),
+            _Fx33(vx) => x(0xF033, vx),
+            _Fx55(vx) => x(0xF055, vx),
+            _Fx65(vx) => x(0xF065, vx),
+        }
+    }
+
+    /// The X operand, for instructions that have one.
+    pub fn x(&self) -> Option<u8> {
+        use Opcode::*;
+        match *self {
+            _3xnn(x, _) | _4xnn(x, _) | _6xnn(x, _) | _7xnn(x, _) | _Cxnn(x, _) => Some(x),
+            _5xy0(x, _) | _8xy0(x, _) | _8xy1(x, _) | _8xy2(x, _) | _8xy3(x, _) => Some(x),
+            _8xy4(x, _) | _8xy5(x, _) | _8xy6(x, _) | _8xy7(x, _) | _8xye(x, _) => Some(x),
+            _9xy0(x, _) | _Dxyn(x, _, _) => Some(x),
+            _Ex9e(x) | _Exa1(x) | _Fx07(x) | _Fx0a(x) | _Fx15(x) | _Fx18(x) | _Fx1e(x) => Some(x),
+            _Fx29(x) | _Fx33(x) | _Fx55(x) | _Fx65(x) => Some(x),
+            _00e0 | _00ee | _1nnn(_) | _2nnn(_) | _Annn(_) | _Bnnn(_) => None,
+        }
+    }
+
+    /// The Y operand, for instructions that have one.
+    pub fn y(&self) -> Option<u8> {
+        use Opcode::*;
+        match *self {
+            _5xy0(_, y) | _8xy0(_, y) | _8xy1(_, y) | _8xy2(_, y) | _8xy3(_, y) => Some(y),
+            _8xy4(_, y) | _8xy5(_, y) | _8xy6(_, y) | _8xy7(_, y) | _8xye(_, y) => Some(y),
+            _9xy0(_, y) | _Dxyn(_, y, _) => Some(y),
+            _ => None,
+        }
+    }
+
+    /// Registers the instruction may read, one bit per register with V0 as bit 0.
+    /// Quirk-dependent reads are included, e.g. VY for the shifts and VX for Bnnn.
+    pub fn registers_read(&self) -> u16 {
+        use Opcode::*;
+        // Masked, operands of hand-built opcodes can be out of range
+        let x = self.x().map_or(0, |x| 1 << (x & 0xF));
+        let y = self.y().map_or(0, |y| 1 << (y & 0xF));
+        match *self {
+            _00e0 | _00ee | _1nnn(_) | _2nnn(_) | _Annn(_) => 0,
+            _6xnn(..) | _8xy0(..) | _Cxnn(..) => y,
+            // V0, or VX with the jump quirk
+            _Bnnn(nnn) => 1 | 1 << (nnn >> 8 & 0xF),
+            _Fx07(_) | _Fx0a(_) | _Fx65(_) => 0,
+            _Fx55(vx) => registers(0, vx),
+            _ => x | y,
+        }
+    }
+
+    /// Registers the instruction may write, one bit per register with V0 as bit 0.
+    /// Quirk-dependent writes are included, e.g. VF for 8xy1 with the logic quirk.
+    pub fn registers_written(&self) -> u16 {
+        use Opcode::*;
+        let x = self.x().map_or(0, |x| 1 << (x & 0xF));
+        match *self {
+            _6xnn(..) | _7xnn(..) | _8xy0(..) | _Cxnn(..) | _Fx07(_) | _Fx0a(_) => x,
+            _8xy1(..) | _8xy2(..) | _8xy3(..) | _8xy4(..) | _8xy5(..) => x | VF,
+            _8xy6(..) | _8xy7(..) | _8xye(..) => x | VF,
+            _Dxyn(..) => VF,
+            _Fx65(vx) => registers(0, vx),
+            _ => 0,
+        }
+    }
+
+    pub fn reads_vx(&self) -> bool {
+        self.x()
+            .is_some_and(|x| self.registers_read() & 1 << (x & 0xF) != 0)
+    }
+
+    pub fn writes_vx(&self) -> bool {
+        self.x()
+            .is_some_and(|x| self.registers_written() & 1 << (x & 0xF) != 0)
+    }
+
+    pub fn writes_vf(&self) -> bool {
+        self.registers_written() & VF != 0
+    }
+
+    /// Whether the instruction may continue anywhere but the next instruction:
+    /// jumps, calls, returns, skips, and Fx0a, which repeats until a key is pressed.
+    pub fn is_branch(&self) -> bool {
+        use Opcode::*;
+        matches!(
+            self,
+            _00ee
+                | _1nnn(_)
+                | _2nnn(_)
+                | _3xnn(..)
+                | _4xnn(..)
+                | _5xy0(..)
+                | _9xy0(..)
+                | _Bnnn(_)
+                | _Ex9e(_)
+                | _Exa1(_)
+                | _Fx0a(_)
+        )
+    }
+
+    /// Whether the instruction reads or writes memory at I.
+    pub fn touches_memory(&self) -> bool {
+        use Opcode::*;
+        matches!(self, _Dxyn(..) | _Fx33(_) | _Fx55(_) | _Fx65(_))
+    }
+
+    /// Whether the instruction writes memory at I.
+    pub fn writes_memory(&self) -> bool {
+        use Opcode::*;
+        matches!(self, _Fx33(_) | _Fx55(_))
+    }
+}
+
+/// Canonical mnemonics, in the style of Cowgod's technical reference.
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Opcode::*;
+        match *self {
+            _00e0 => write!(f, "CLS"),
+            _00ee => write!(f, "RET"),
+            _1nnn(nnn) => write!(f, "JP {:#05X}", nnn),
+            _2nnn(nnn) => write!(f, "CALL {:#05X}", nnn),
+            _3xnn(x, nn) => write!(f, "SE V{:X}, {:#04X}", x, nn),
+            _4xnn(x, nn) => write!(f, "SNE V{:X}, {:#04X}", x, nn),
+            _5xy0(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            _6xnn(x, nn) => write!(f, "LD V{:X}, {:#04X}", x, nn),
+            _7xnn(x, nn) => write!(f, "ADD V{:X}, {:#04X}", x, nn),
+            _8xy0(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            _8xy1(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            _8xy2(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            _8xy3(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            _8xy4(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            _8xy5(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            _8xy6(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            _8xy7(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            _8xye(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            _9xy0(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            _Annn(nnn) => write!(f, "LD I, {:#05X}", nnn),
+            _Bnnn(nnn) => write!(f, "JP V0, {:#05X}", nnn),
+            _Cxnn(x, nn) => write!(f, "RND V{:X}, {:#04X}", x, nn),
+            _Dxyn(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {:#03X}", x, y, n),
+            _Ex9e(x) => write!(f, "SKP V{:X}", x),
+            _Exa1(x) => write!(f, "SKNP V{:X}", x),
+            _Fx07(x) => write!(f, "LD V{:X}, DT", x),
+            _Fx0a(x) => write!(f, "LD V{:X}, K", x),
+            _Fx15(x) => write!(f, "LD DT, V{:X}", x),
+            _Fx18(x) => write!(f, "LD ST, V{:X}", x),
+            _Fx1e(x) => write!(f, "ADD I, V{:X}", x),
+            _Fx29(x) => write!(f, "LD F, V{:X}", x),
+            _Fx33(x) => write!(f, "LD B, V{:X}", x),
+            _Fx55(x) => write!(f, "LD [I], V{:X}", x),
+            _Fx65(x) => write!(f, "LD V{:X}, [I]", x),
+        }
+    }
+}
+
+/// An instruction read from memory, as shown by a debugger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disassembled {
+    pub addr: u16,
+    pub word: u16,
+    /// `None` for data or unknown instructions.
+    pub opcode: Option<Opcode>,
+}
+
+impl fmt::Display for Disassembled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#05X}  {:04X}  ", self.addr, self.word)?;
+        match self.opcode {
+            Some(opcode) => write!(f, "{}", opcode),
+            None => write!(f, "DW {:#06X}", self.word),
+        }
+    }
+}
+
+/// Decodes `count` instructions from `start`, stopping at the end of memory. Reads with
+/// `peek`, so it has no side effects.
+pub fn disassemble(bus: &impl Bus, start: u16, count: usize) -> Vec<Disassembled> {
+    (start as usize..MEMORY_SIZE - 1)
+        .step_by(2)
+        .take(count)
+        .map(|addr| {
+            let addr = addr as u16;
+            let word = u16::from_be_bytes([bus.peek(addr), bus.peek(addr + 1)]);
+            Disassembled {
+                addr,
+                word,
+                opcode: Opcode::decode(word).ok(),
+            }
+        })
+        .collect()
+}
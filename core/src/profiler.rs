@@ -0,0 +1,247 @@
+//! Execution profiler: how often each instruction runs, where the time goes in
+//! subroutines, and how much of it is spent polling the delay timer.
+//!
+//! Costs are in cycles: COSMAC VIP machine cycles with that timing, one per instruction
+//! otherwise.
+
+use crate::bus::Bus;
+use crate::cpu::Cpu;
+use crate::opcode::{disassemble, Opcode};
+use crate::ram::MEMORY_SIZE;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use std::fmt::Write;
+
+/// Longest polling loop recognized, counting the Fx07 that reads the delay timer.
+const MAX_WAIT_LOOP: u32 = 4;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Subroutine {
+    pub calls: u64,
+    /// Spent from the first instruction of the subroutine to its RET, including the
+    /// subroutines it calls.
+    pub cycles: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Profile {
+    executions: Vec<u64>,
+    opcodes: BTreeMap<&'static str, u64>,
+    subroutines: BTreeMap<u16, Subroutine>,
+    // Cycles of the polling loops, by the address of their Fx07
+    busy_wait: BTreeMap<u16, u64>,
+    instructions: u64,
+    cycles: u64,
+}
+
+impl Profile {
+    /// Instructions run while profiling.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Times the instruction at `addr` ran.
+    pub fn executions(&self, addr: u16) -> u64 {
+        self.executions.get(addr as usize).copied().unwrap_or(0)
+    }
+
+    /// Instructions run per opcode pattern, like `"8XY4"`.
+    pub fn opcodes(&self) -> &BTreeMap<&'static str, u64> {
+        &self.opcodes
+    }
+
+    /// Subroutines by address, as called by 2NNN.
+    pub fn subroutines(&self) -> &BTreeMap<u16, Subroutine> {
+        &self.subroutines
+    }
+
+    /// Cycles spent in loops that poll the delay timer until it reaches 0, by the address
+    /// of the Fx07 reading it.
+    pub fn busy_wait(&self) -> &BTreeMap<u16, u64> {
+        &self.busy_wait
+    }
+
+    /// The `count` most run addresses, the most run first.
+    pub fn hotspots(&self, count: usize) -> Vec<(u16, u64)> {
+        let mut hotspots: Vec<(u16, u64)> = self
+            .executions
+            .iter()
+            .enumerate()
+            .filter(|(_, runs)| **runs > 0)
+            .map(|(addr, runs)| (addr as u16, *runs))
+            .collect();
+        hotspots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hotspots.truncate(count);
+        hotspots
+    }
+
+    /// A text report: totals, the `top` hotspots, opcodes, subroutines, then the
+    /// disassembly of every instruction that ran with its count. `bus` should hold the
+    /// program that was profiled.
+    pub fn report(&self, bus: &impl Bus, top: usize) -> String {
+        let share = |part: u64, total: u64| part as f64 * 100.0 / total.max(1) as f64;
+        let line = |addr: u16| disassemble(bus, addr, 1)[0];
+        let waited: u64 = self.busy_wait.values().sum();
+        let mut report = String::new();
+        // Writing to a String can't fail
+        let _ = (|| -> std::fmt::Result {
+            writeln!(
+                report,
+                "{} instructions, {} cycles",
+                self.instructions, self.cycles
+            )?;
+            writeln!(
+                report,
+                "{} cycles ({:.1}%) busy-waiting on the delay timer",
+                waited,
+                share(waited, self.cycles)
+            )?;
+            for (addr, cycles) in &self.busy_wait {
+                writeln!(report, "  {:8} cycles polling at {:#05X}", cycles, addr)?;
+            }
+
+            writeln!(report, "\nHotspots")?;
+            for (addr, runs) in self.hotspots(top) {
+                writeln!(
+                    report,
+                    "{:10} {:5.1}%  {}",
+                    runs,
+                    share(runs, self.instructions),
+                    line(addr)
+                )?;
+            }
+
+            writeln!(report, "\nOpcodes")?;
+            let mut opcodes: Vec<_> = self.opcodes.iter().collect();
+            opcodes.sort_by(|a, b| b.1.cmp(a.1));
+            for (pattern, runs) in opcodes {
+                writeln!(
+                    report,
+                    "{:10} {:5.1}%  {}",
+                    runs,
+                    share(*runs, self.instructions),
+                    pattern
+                )?;
+            }
+
+            writeln!(report, "\nSubroutines")?;
+            for (addr, subroutine) in &self.subroutines {
+                writeln!(
+                    report,
+                    "  {:#05X}  {:8} calls  {:10} cycles {:5.1}%",
+                    addr,
+                    subroutine.calls,
+                    subroutine.cycles,
+                    share(subroutine.cycles, self.cycles)
+                )?;
+            }
+
+            writeln!(report, "\nCoverage")?;
+            let mut previous: Option<u16> = None;
+            for addr in (0..MEMORY_SIZE as u16).filter(|addr| self.executions(*addr) > 0) {
+                if previous.is_some_and(|previous| previous + 2 != addr) {
+                    writeln!(report, "{:>10}", "...")?;
+                }
+                writeln!(report, "{:10}  {}", self.executions(addr), line(addr))?;
+                previous = Some(addr);
+            }
+            Ok(())
+        })();
+        report
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Wait {
+    addr: u16,
+    instructions: u32,
+    cycles: u64,
+}
+
+/// Collects a `Profile` while `Chip8` profiles.
+#[derive(Debug, Clone)]
+pub(crate) struct Profiling {
+    profile: Profile,
+    // Subroutines being run, the innermost last
+    calls: Vec<u16>,
+    // A possible polling loop, since the last Fx07 that read a running timer
+    wait: Option<Wait>,
+}
+
+impl Profiling {
+    pub fn new() -> Self {
+        Profiling {
+            profile: Profile {
+                executions: vec![0; MEMORY_SIZE],
+                opcodes: BTreeMap::new(),
+                subroutines: BTreeMap::new(),
+                busy_wait: BTreeMap::new(),
+                instructions: 0,
+                cycles: 0,
+            },
+            calls: Vec::new(),
+            wait: None,
+        }
+    }
+
+    /// Counts `opcode` at `pc`, which just ran.
+    pub fn instruction(&mut self, pc: u16, opcode: Opcode, cycles: u64, cpu: &Cpu) {
+        let profile = &mut self.profile;
+        profile.instructions += 1;
+        profile.cycles += cycles;
+        if let Some(runs) = profile.executions.get_mut(pc as usize) {
+            *runs += 1;
+        }
+        *profile.opcodes.entry(opcode.pattern()).or_default() += 1;
+
+        // Recursive calls are only charged once
+        for (depth, addr) in self.calls.iter().enumerate() {
+            if !self.calls[..depth].contains(addr) {
+                profile.subroutines.entry(*addr).or_default().cycles += cycles;
+            }
+        }
+        match opcode {
+            Opcode::_2nnn(addr) => {
+                self.calls.push(addr);
+                profile.subroutines.entry(addr).or_default().calls += 1;
+            }
+            Opcode::_00ee => {
+                self.calls.pop();
+            }
+            _ => {}
+        }
+
+        match opcode {
+            Opcode::_Fx07(x) => {
+                // Back at the same read: the loop since the last one was waiting
+                if let Some(wait) = self.wait.filter(|wait| wait.addr == pc) {
+                    *profile.busy_wait.entry(pc).or_default() += wait.cycles;
+                }
+                self.wait = (cpu.read_register(x) > 0).then_some(Wait {
+                    addr: pc,
+                    instructions: 1,
+                    cycles,
+                });
+            }
+            _ => {
+                if let Some(wait) = &mut self.wait {
+                    wait.instructions += 1;
+                    wait.cycles += cycles;
+                    if wait.instructions > MAX_WAIT_LOOP {
+                        self.wait = None;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn finish(self) -> Profile {
+        self.profile
+    }
+}
@@ -0,0 +1,35 @@
+/// Behaviours that differ between CHIP-8 interpreters.
+///
+/// Field names follow the chip-8-database quirk names. By default only `shift` and
+/// `wrap` are on, which is close to what most modern games expect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8xy6 and 8xyE shift VX in place and ignore VY, instead of shifting VY into VX.
+    pub shift: bool,
+    /// Fx55 and Fx65 increment I by X instead of X + 1.
+    pub memory_increment_by_x: bool,
+    /// Fx55 and Fx65 leave I unchanged.
+    pub memory_leave_i_unchanged: bool,
+    /// Sprites wrap around the screen edges instead of being clipped.
+    pub wrap: bool,
+    /// Bnnn jumps to XNN plus VX instead of NNN plus V0.
+    pub jump: bool,
+    /// Dxyn waits for the next frame before drawing, so at most one sprite is drawn per frame.
+    pub vblank: bool,
+    /// 8xy1, 8xy2 and 8xy3 reset VF to 0.
+    pub logic: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            shift: true,
+            memory_increment_by_x: false,
+            memory_leave_i_unchanged: false,
+            wrap: true,
+            jump: false,
+            vblank: false,
+            logic: false,
+        }
+    }
+}
@@ -1,4 +1,4 @@
-const MEMORY_SIZE: usize = 4096;
+pub const MEMORY_SIZE: usize = 4096;
 
 #[derive(Debug, Clone)]
 pub struct Ram {
@@ -14,17 +14,15 @@ impl Default for Ram {
 }
 
 impl Ram {
-    pub fn load_fontset(&mut self, font_set: [u8; 80]) {
-        for i in 0..font_set.len() {
-            self.memory[i] = font_set[i]
-        }
-    }
-
-    pub fn write(&mut self, addr: u16, value: u8) -> () {
+    pub fn write(&mut self, addr: u16, value: u8) {
         self.memory[addr as usize] = value;
     }
 
     pub fn read(&self, addr: u16) -> u8 {
         self.memory[addr as usize]
     }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.memory
+    }
 }
@@ -0,0 +1,167 @@
+//! Save states: a versioned binary snapshot of everything that affects how a program runs.
+
+use alloc::vec::Vec;
+use std::fmt;
+
+/// First bytes of every save state.
+pub const STATE_MAGIC: [u8; 4] = *b"C8ST";
+/// Bumped whenever the layout changes. Older states are rejected.
+pub const STATE_VERSION: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    /// Doesn't start with `STATE_MAGIC`, so it isn't a save state.
+    BadMagic,
+    /// Saved by a version of the emulator with a different layout.
+    UnsupportedVersion(u8),
+    /// Ends before all the fields were read.
+    Truncated,
+    /// A field has a value the emulator can't be in.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) => write!(
+                f,
+                "save state version {} is not supported, expected {}",
+                version, STATE_VERSION
+            ),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Invalid(field) => write!(f, "save state has an invalid {}", field),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+/// 64-bit FNV-1a, stable across platforms and versions.
+pub(crate) fn hash(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    bytes.into_iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Appends fields in big endian.
+pub(crate) struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        StateWriter::with_header(STATE_MAGIC, STATE_VERSION)
+    }
+
+    /// For other formats built from the same fields, like movies.
+    pub fn with_header(magic: [u8; 4], version: u8) -> Self {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&magic);
+        bytes.push(version);
+        StateWriter { bytes }
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    pub fn i64(&mut self, value: i64) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    pub fn u128(&mut self, value: u128) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads fields back in the order `StateWriter` wrote them.
+pub(crate) struct StateReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self, StateError> {
+        StateReader::with_header(bytes, STATE_MAGIC, STATE_VERSION)
+    }
+
+    pub fn with_header(bytes: &'a [u8], magic: [u8; 4], version: u8) -> Result<Self, StateError> {
+        let mut reader = StateReader { bytes };
+        if reader.array::<4>().map_err(|_| StateError::BadMagic)? != magic {
+            return Err(StateError::BadMagic);
+        }
+        match reader.u8()? {
+            found if found == version => Ok(reader),
+            found => Err(StateError::UnsupportedVersion(found)),
+        }
+    }
+
+    pub fn slice(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.bytes.len() < len {
+            return Err(StateError::Truncated);
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        Ok(self.slice(N)?.try_into().unwrap())
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Invalid("flag")),
+        }
+    }
+
+    pub fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    pub fn i64(&mut self) -> Result<i64, StateError> {
+        Ok(i64::from_be_bytes(self.array()?))
+    }
+
+    pub fn u128(&mut self) -> Result<u128, StateError> {
+        Ok(u128::from_be_bytes(self.array()?))
+    }
+}
@@ -0,0 +1,81 @@
+use crate::cpu::Cpu;
+use crate::opcode::Opcode;
+
+/// Machine cycles of the COSMAC VIP in one 60 Hz frame (1.76 MHz clock, 8 clocks per machine cycle).
+pub const VIP_CYCLES_PER_FRAME: u32 = 3668;
+/// Cycles taken every frame by the display DMA (8 bytes for each of the 128 scanlines)
+/// and the interrupt routine that ticks the timers.
+pub const VIP_DISPLAY_CYCLES_PER_FRAME: u32 = 1024 + 104;
+/// Cycles left for the interpreter in every frame.
+pub const VIP_FRAME_BUDGET: u32 = VIP_CYCLES_PER_FRAME - VIP_DISPLAY_CYCLES_PER_FRAME;
+
+/// Fetching, decoding and dispatching an instruction.
+const FETCH_CYCLES: u32 = 16;
+
+/// How `Chip8::run_frame` decides how much code runs in one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timing {
+    /// Every instruction costs the same, and each frame runs `tickrate` of them.
+    #[default]
+    Fixed,
+    /// Every instruction is charged what it took on the COSMAC VIP, and each frame
+    /// spends `VIP_FRAME_BUDGET` cycles. Overspending is paid back in the next frame.
+    CosmacVip,
+}
+
+/// Approximate COSMAC VIP machine cycles spent on `opcode`, including the fetch.
+/// Must be called before the instruction runs, since some costs depend on registers.
+///
+/// The figures follow Laurence Scotford's walkthrough of the original interpreter, rounded
+/// to whole machine cycles. Draws, screen clears and BCD conversion are the slow ones.
+pub(crate) fn vip_cycles(opcode: &Opcode, cpu: &Cpu) -> u32 {
+    use Opcode as Op;
+    let execute = match *opcode {
+        // Clears the 256 bytes of display memory one by one
+        Op::_00e0 => 678,
+        Op::_00ee => 10,
+        Op::_1nnn(_) => 12,
+        Op::_2nnn(_) => 26,
+        Op::_3xnn(..) | Op::_4xnn(..) => 10,
+        Op::_5xy0(..) | Op::_9xy0(..) => 14,
+        Op::_6xnn(..) => 6,
+        Op::_7xnn(..) => 10,
+        // The VIP builds and runs a small subroutine for every arithmetic instruction
+        Op::_8xy0(..)
+        | Op::_8xy1(..)
+        | Op::_8xy2(..)
+        | Op::_8xy3(..)
+        | Op::_8xy4(..)
+        | Op::_8xy5(..)
+        | Op::_8xy6(..)
+        | Op::_8xy7(..)
+        | Op::_8xye(..) => 44,
+        Op::_Annn(_) => 12,
+        Op::_Bnnn(_) => 22,
+        Op::_Cxnn(..) => 36,
+        Op::_Dxyn(x, _, n) => draw_cycles(cpu.read_register(x), n),
+        Op::_Ex9e(_) | Op::_Exa1(_) => 14,
+        Op::_Fx07(_) | Op::_Fx15(_) | Op::_Fx18(_) => 10,
+        // Charged for every poll while no key is pressed
+        Op::_Fx0a(_) => 10,
+        Op::_Fx1e(_) => 18,
+        Op::_Fx29(_) => 20,
+        Op::_Fx33(x) => bcd_cycles(cpu.read_register(x)),
+        Op::_Fx55(x) | Op::_Fx65(x) => 14 + 14 * (x as u32 + 1),
+    };
+    FETCH_CYCLES + execute
+}
+
+/// Each sprite row is shifted into place one bit at a time, so the cost grows with
+/// the height and with how far X is from a byte boundary.
+fn draw_cycles(vx: u8, n: u8) -> u32 {
+    let shift = (vx % 8) as u32;
+    let row = if shift == 0 { 16 } else { 26 + 4 * shift };
+    26 + n as u32 * row
+}
+
+/// Digits are found by repeated subtraction, so bigger numbers take longer.
+fn bcd_cycles(vx: u8) -> u32 {
+    let digits = (vx / 100 + (vx / 10) % 10 + vx % 10) as u32;
+    80 + 16 * digits
+}
@@ -0,0 +1,168 @@
+use core::audio::{Beeper, Tone};
+use core::chip8::{Chip8, Engine};
+use core::timing::{Timing, VIP_FRAME_BUDGET};
+
+// 10 samples a frame, and a period of 4 samples
+const TONE: Tone = Tone {
+    frequency: 150,
+    volume: 100,
+    sample_rate: 600,
+};
+
+/// Frames with the beeper on.
+fn beeping_frames(beeper: &Beeper) -> Vec<usize> {
+    let per_frame = (TONE.sample_rate / 60) as usize;
+    beeper
+        .samples()
+        .chunks(per_frame)
+        .enumerate()
+        .filter(|(_, samples)| samples.iter().any(|sample| *sample != 0))
+        .map(|(frame, _)| frame)
+        .collect()
+}
+
+#[test]
+fn beeps_last_as_many_ticks_as_fx18_sets() {
+    // V0 = 3, ST = V0, loop
+    let rom = [0x60, 0x03, 0xF0, 0x18, 0x12, 0x04];
+    for engine in [Engine::Interpreter, Engine::Threaded, Engine::Verify] {
+        let mut chip8 = Chip8::with_engine(engine);
+        chip8.load(&rom).unwrap();
+        let mut beeper = Beeper::new(TONE);
+        for _ in 0..6 {
+            chip8.run_frame();
+            beeper.capture(&chip8);
+        }
+        assert_eq!(beeper.samples().len(), 60);
+        assert_eq!(
+            beeper.samples()[..12],
+            [100, 100, -100, -100, 100, 100, -100, -100, 100, 100, -100, -100]
+        );
+        assert_eq!(beeping_frames(&beeper), [0, 1, 2], "{:?}", engine);
+    }
+}
+
+#[test]
+fn frames_add_up_to_the_sample_rate() {
+    let mut beeper = Beeper::new(Tone {
+        sample_rate: 22050,
+        ..Tone::default()
+    });
+    for frame in 0..60 {
+        beeper.frame(frame % 2 == 0);
+    }
+    assert_eq!(beeper.samples().len(), 22050);
+
+    let wav = beeper.to_wav();
+    assert_eq!(&wav[..4], b"RIFF");
+    assert_eq!(&wav[8..16], b"WAVEfmt ");
+    assert_eq!(u32::from_le_bytes(wav[24..28].try_into().unwrap()), 22050);
+    assert_eq!(&wav[36..40], b"data");
+    assert_eq!(u32::from_le_bytes(wav[40..44].try_into().unwrap()), 44100);
+    assert_eq!(wav.len(), 44 + 44100);
+    assert_eq!(i16::from_le_bytes([wav[44], wav[45]]), 6000);
+}
+
+#[test]
+fn replayed_movies_sound_the_same() {
+    // V0 = 2, wait for a key, ST = V0, wait for its release, repeat
+    let rom = [
+        0x60, 0x02, 0xF1, 0x0A, 0xF0, 0x18, 0xE1, 0xA1, 0x12, 0x06, 0x12, 0x02,
+    ];
+    let mut chip8 = Chip8::new();
+    chip8.load(&rom).unwrap();
+    chip8.start_recording();
+    let mut live = Beeper::new(TONE);
+    for frame in 0..30 {
+        match frame {
+            5 | 20 => chip8.keypress(0x7, true),
+            7 | 23 => chip8.keypress(0x7, false),
+            _ => {}
+        }
+        chip8.run_frame();
+        live.capture(&chip8);
+    }
+    let movie = chip8.stop_recording().unwrap();
+    assert_eq!(beeping_frames(&live), [5, 6, 20, 21]);
+
+    let mut replayed = Beeper::new(TONE);
+    movie
+        .replay_with(&mut Chip8::new(), |chip8| replayed.capture(chip8))
+        .unwrap();
+    assert_eq!(replayed.samples(), live.samples());
+}
+
+/// Beeps of each frame run of `rom` with COSMAC VIP timing, on every engine.
+fn vip_beeps(rom: &[u8], frames: usize) -> Vec<Vec<Option<std::ops::Range<u32>>>> {
+    [Engine::Interpreter, Engine::Threaded, Engine::Verify]
+        .into_iter()
+        .map(|engine| {
+            let mut chip8 = Chip8::with_engine(engine);
+            chip8.set_timing(Timing::CosmacVip);
+            chip8.load(rom).unwrap();
+            (0..frames)
+                .map(|_| {
+                    chip8.run_frame();
+                    chip8.beep()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn vip_timing_starts_beeps_where_fx18_ran() {
+    // V0 = 3 then ST = V0, done 22 + 26 cycles into the frame, then loop
+    let rom = [0x60, 0x03, 0xF0, 0x18, 0x12, 0x04];
+    let full = Some(0..VIP_FRAME_BUDGET);
+    for beeps in vip_beeps(&rom, 4) {
+        assert_eq!(
+            beeps,
+            [Some(48..VIP_FRAME_BUDGET), full.clone(), full.clone(), None]
+        );
+    }
+
+    let mut chip8 = Chip8::new();
+    chip8.set_timing(Timing::CosmacVip);
+    chip8.load(&rom).unwrap();
+    let mut beeper = Beeper::new(Tone::default());
+    chip8.run_frame();
+    beeper.capture(&chip8);
+    // 735 samples a frame at 44.1 kHz
+    let silent = 48 * 735 / VIP_FRAME_BUDGET as usize;
+    assert!(beeper.samples()[..silent].iter().all(|sample| *sample == 0));
+    assert_eq!(beeper.samples()[silent], 6000);
+}
+
+#[test]
+fn vip_timing_stops_beeps_where_fx18_clears_the_timer() {
+    // ST = 5 after 48 cycles, ST = 0 after 96, then loop
+    let rom = [0x60, 0x05, 0xF0, 0x18, 0x61, 0x00, 0xF1, 0x18, 0x12, 0x08];
+    for beeps in vip_beeps(&rom, 2) {
+        assert_eq!(beeps, [Some(48..96), None]);
+    }
+
+    // Fixed timing has no clock within the frame, so the timer wasn't running at its end
+    let mut chip8 = Chip8::new();
+    chip8.load(&rom).unwrap();
+    chip8.run_frame();
+    assert!(!chip8.beeped());
+}
+
+#[test]
+fn save_states_keep_the_last_beep() {
+    // V0 = 1, ST = V0, loop: the only beep is in the first frame
+    let rom = [0x60, 0x01, 0xF0, 0x18, 0x12, 0x04];
+    let mut chip8 = Chip8::new();
+    chip8.set_timing(Timing::CosmacVip);
+    chip8.load(&rom).unwrap();
+    chip8.run_frame();
+    assert!(chip8.beeped());
+
+    let mut loaded = Chip8::new();
+    loaded.load_state(&chip8.save_state()).unwrap();
+    assert_eq!(loaded.beep(), chip8.beep());
+    let mut beeper = Beeper::new(TONE);
+    beeper.capture(&loaded);
+    assert!(beeper.samples().iter().any(|sample| *sample != 0));
+}
@@ -0,0 +1,128 @@
+use core::bus::{Access, Bus, Device, MappedBus};
+use core::chip8::Chip8;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Remembers what was written and reads back a counter of the reads.
+#[derive(Default)]
+struct Port {
+    reads: u8,
+    writes: Rc<RefCell<Vec<(u16, u8)>>>,
+}
+
+impl Device for Port {
+    fn read(&mut self, _offset: u16) -> u8 {
+        self.reads += 1;
+        self.reads
+    }
+
+    fn write(&mut self, offset: u16, value: u8) {
+        self.writes.borrow_mut().push((offset, value));
+    }
+}
+
+#[test]
+fn protected_addresses_ignore_writes_but_not_pokes() {
+    let mut bus = MappedBus::new();
+    bus.protect(0x300..0x302);
+    bus.write(0x300, 1);
+    bus.write(0x301, 2);
+    bus.write(0x302, 3);
+    assert_eq!([0x300, 0x301, 0x302].map(|addr| bus.peek(addr)), [0, 0, 3]);
+
+    bus.poke(0x300, 4);
+    assert_eq!(bus.read(0x300), 4);
+
+    // LD V0, 0x42, LD I, 0x300, LD [I], V0
+    let mut chip8 = Chip8::with_bus(bus);
+    chip8.load(&[0x60, 0x42, 0xA3, 0x00, 0xF0, 0x55]).unwrap();
+    for _ in 0..3 {
+        chip8.emulate_cycle();
+    }
+    assert_eq!(chip8.bus().peek(0x300), 4);
+}
+
+#[test]
+fn mapped_devices_get_reads_and_writes_relative_to_their_range() {
+    let writes = Rc::new(RefCell::new(Vec::new()));
+    let mut bus = MappedBus::new();
+    bus.map(
+        0xF00..0xF10,
+        Port {
+            reads: 0,
+            writes: writes.clone(),
+        },
+    );
+
+    // LD V0, 0x07, LD I, 0xF02, LD [I], V0, LD I, 0xF02, LD V1, [I]
+    let mut chip8 = Chip8::with_bus(bus);
+    chip8
+        .load(&[0x60, 0x07, 0xAF, 0x02, 0xF0, 0x55, 0xAF, 0x02, 0xF1, 0x65])
+        .unwrap();
+    for _ in 0..5 {
+        chip8.emulate_cycle();
+    }
+    assert_eq!(*writes.borrow(), [(2, 0x07)]);
+    assert_eq!((chip8.register(0), chip8.register(1)), (1, 2));
+    // Outside the device, memory is RAM
+    assert_eq!(chip8.bus().peek(0xEFF), 0);
+
+    // Pokes, like the ones loading a state, leave the device alone
+    let state = chip8.save_state();
+    chip8.load_state(&state).unwrap();
+    chip8.bus_mut().poke(0xF03, 0x09);
+    assert_eq!(*writes.borrow(), [(2, 0x07)]);
+}
+
+#[test]
+fn observers_see_every_access() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut bus = MappedBus::new();
+    bus.protect(0x300..0x301);
+    let seen = log.clone();
+    bus.observe(move |access, addr, value| seen.borrow_mut().push((access, addr, value)));
+
+    // LD V0, 0x09, LD I, 0x300, LD [I], V0, LD V0, [I]
+    let mut chip8 = Chip8::with_bus(bus);
+    chip8
+        .load(&[0x60, 0x09, 0xA3, 0x00, 0xF0, 0x55, 0xF0, 0x65])
+        .unwrap();
+    assert!(log.borrow().is_empty(), "loading pokes memory");
+    for _ in 0..4 {
+        chip8.emulate_cycle();
+    }
+
+    use Access::*;
+    assert_eq!(
+        *log.borrow(),
+        [
+            (Fetch, 0x200, 0x60),
+            (Fetch, 0x201, 0x09),
+            (Fetch, 0x202, 0xA3),
+            (Fetch, 0x203, 0x00),
+            (Fetch, 0x204, 0xF0),
+            (Fetch, 0x205, 0x55),
+            // Reported with the value attempted, even though it's protected
+            (Write, 0x300, 0x09),
+            (Fetch, 0x206, 0xF0),
+            (Fetch, 0x207, 0x65),
+            // Fx55 moved I past what it stored
+            (Read, 0x301, 0x00),
+        ]
+    );
+}
+
+#[test]
+fn mapped_and_observed_memory_isnt_cacheable() {
+    let mut bus = MappedBus::new();
+    assert!(bus.cacheable(0x200));
+    bus.protect(0x200..0x300);
+    assert!(bus.cacheable(0x200));
+
+    bus.map(0xF00..0xF10, Port::default());
+    assert!(!bus.cacheable(0xF00) && !bus.cacheable(0xF0F));
+    assert!(bus.cacheable(0xF10) && bus.cacheable(0x200));
+
+    bus.observe(|_, _, _| {});
+    assert!(!bus.cacheable(0x200));
+}
@@ -0,0 +1,126 @@
+use core::bus::Bus;
+use core::cheat::{Cheat, CheatError, Cheats, Condition, RamSearch, Target};
+use core::chip8::{Chip8, Engine};
+
+// Counts key presses at 0x300: wait for a key and its release, V0 += 1, store V0 at 0x300, loop
+const PRESS_COUNTER: [u8; 14] = [
+    0xF1, 0x0A, 0xE1, 0xA1, 0x12, 0x02, 0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00,
+];
+
+fn press(chip8: &mut Chip8, key: u8) {
+    chip8.keypress(key, true);
+    chip8.run_frame();
+    chip8.keypress(key, false);
+    chip8.run_frame();
+}
+
+#[test]
+fn search_narrows_down_a_counter() {
+    let mut chip8 = Chip8::new();
+    chip8.load(&PRESS_COUNTER).unwrap();
+    chip8.run_frame();
+
+    let mut search = RamSearch::new(chip8.bus());
+    assert_eq!(search.candidates().len(), 4096);
+    chip8.run_frame();
+    search.filter(chip8.bus(), Condition::Unchanged);
+
+    press(&mut chip8, 0x5);
+    assert_eq!(search.filter(chip8.bus(), Condition::IncreasedBy(1)), 1);
+    assert_eq!(search.candidates(), &[0x300]);
+    assert_eq!(search.previous(0x300), 1);
+
+    press(&mut chip8, 0x5);
+    assert_eq!(search.filter(chip8.bus(), Condition::Equals(2)), 1);
+    assert_eq!(search.filter(chip8.bus(), Condition::Decreased), 0);
+}
+
+#[test]
+fn cheats_freeze_memory_and_registers() {
+    // V0 = 5, V1 += 1, loop
+    let rom = [0x60, 0x05, 0x71, 0x01, 0x12, 0x00];
+    let mut chip8 = Chip8::with_engine(Engine::Threaded);
+    chip8.load(&rom).unwrap();
+    chip8.run_frame();
+    assert_eq!(chip8.register(0), 5);
+
+    let cheats = Cheats {
+        cheats: vec![
+            // Rewrites the immediate of `V0 = 5`, which is already cached
+            Cheat::from_code("0x201 9").unwrap(),
+            Cheat::from_code("V1 0").unwrap(),
+            Cheat {
+                enabled: false,
+                ..Cheat::from_code("VA 1").unwrap()
+            },
+        ],
+    };
+    cheats.apply(&mut chip8);
+    assert_eq!(chip8.register(1), 0);
+    chip8.run_frame();
+    assert_eq!(chip8.register(0), 9);
+    // Ten instructions a frame, three per loop, starting after the increment
+    assert!(chip8.register(1) <= 4);
+    assert_eq!(chip8.register(0xA), 0);
+}
+
+#[test]
+fn cheat_files_round_trip() {
+    let text = "# Pong\non 0x2F4 0 Right score digit\n\noff   VE  0x0A   Left   scores\n";
+    let cheats = Cheats::parse(text).unwrap();
+    assert_eq!(
+        cheats.cheats[1],
+        Cheat {
+            name: "Left   scores".to_string(),
+            target: Target::Register(0xE),
+            value: 10,
+            enabled: false,
+        }
+    );
+    assert_eq!(Cheats::parse(&cheats.to_string()), Ok(cheats.clone()));
+
+    assert_eq!(
+        Cheats::parse("on 0x2F4 0\nmaybe 0x2F4 0"),
+        Err(CheatError::Line(2, "on/off"))
+    );
+    assert_eq!(
+        Cheats::parse("on 0x1000 0"),
+        Err(CheatError::Line(1, "address"))
+    );
+    assert_eq!(
+        Cheats::parse("on VG 0"),
+        Err(CheatError::Line(1, "register"))
+    );
+    assert_eq!(
+        Cheats::parse("on V1 256"),
+        Err(CheatError::Line(1, "value"))
+    );
+    assert_eq!(Cheat::from_code("V1"), Err(CheatError::Missing("value")));
+}
+
+#[test]
+#[cfg(feature = "database")]
+fn cheats_are_saved_per_rom() {
+    let cheats = Cheats::parse("on VE 0 Scores stay at 0").unwrap();
+    let dir = std::env::temp_dir().join(format!("chip8-cheats-{}", std::process::id()));
+    let rom = [0x12, 0x00];
+    assert_eq!(Cheats::load_for(&dir, &rom).unwrap(), Cheats::default());
+    cheats.save_for(&dir, &rom).unwrap();
+    assert!(Cheats::path(&dir, &rom).ends_with(format!("{}.cht", core::database::sha1_hex(&rom))));
+    assert_eq!(Cheats::load_for(&dir, &rom).unwrap(), cheats);
+    assert_eq!(
+        Cheats::load_for(&dir, &[0x00, 0xE0]).unwrap(),
+        Cheats::default()
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn search_works_on_any_bus() {
+    let mut ram = core::Ram::default();
+    let mut search = RamSearch::new(&ram);
+    Bus::poke(&mut ram, 0x10, 200);
+    assert_eq!(search.filter(&ram, Condition::Changed), 1);
+    Bus::poke(&mut ram, 0x10, 190);
+    assert_eq!(search.filter(&ram, Condition::DecreasedBy(10)), 1);
+}
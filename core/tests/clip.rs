@@ -0,0 +1,205 @@
+use core::chip8::Chip8;
+use core::clip::{Clip, ClipOptions, Palette};
+use core::{Display, SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::path::PathBuf;
+
+/// A minimal GIF reader: the palette, then each frame's delay and pixels.
+fn decode_gif(gif: &[u8]) -> (Vec<u8>, Vec<(u16, Vec<u8>)>) {
+    assert_eq!(&gif[..6], b"GIF89a");
+    let width = u16::from_le_bytes([gif[6], gif[7]]) as usize;
+    let height = u16::from_le_bytes([gif[8], gif[9]]) as usize;
+    let palette = gif[13..19].to_vec();
+    let mut at = 19;
+    let mut frames = Vec::new();
+    let mut delay = 0;
+    loop {
+        match gif[at] {
+            0x21 => {
+                if gif[at + 1] == 0xF9 {
+                    delay = u16::from_le_bytes([gif[at + 4], gif[at + 5]]);
+                }
+                at += 2;
+                while gif[at] != 0 {
+                    at += gif[at] as usize + 1;
+                }
+                at += 1;
+            }
+            0x2C => {
+                at += 10;
+                let min_size = gif[at] as u32;
+                at += 1;
+                let mut data = Vec::new();
+                while gif[at] != 0 {
+                    let len = gif[at] as usize;
+                    data.extend_from_slice(&gif[at + 1..at + 1 + len]);
+                    at += len + 1;
+                }
+                at += 1;
+                let pixels = decode_lzw(&data, min_size);
+                assert_eq!(pixels.len(), width * height);
+                frames.push((delay, pixels));
+            }
+            0x3B => return (palette, frames),
+            byte => panic!("unexpected block {:#04x}", byte),
+        }
+    }
+}
+
+fn decode_lzw(data: &[u8], min_size: u32) -> Vec<u8> {
+    let clear = 1usize << min_size;
+    let end = clear + 1;
+    let mut table: Vec<Vec<u8>> = Vec::new();
+    let mut size = min_size + 1;
+    let mut previous: Option<usize> = None;
+    let mut output = Vec::new();
+    let (mut buffer, mut len, mut at) = (0u32, 0u32, 0);
+    loop {
+        while len < size {
+            buffer |= (data[at] as u32) << len;
+            at += 1;
+            len += 8;
+        }
+        let code = (buffer & ((1 << size) - 1)) as usize;
+        buffer >>= size;
+        len -= size;
+
+        if code == clear {
+            table = (0..clear).map(|value| vec![value as u8]).collect();
+            table.extend([vec![], vec![]]);
+            size = min_size + 1;
+            previous = None;
+            continue;
+        }
+        if code == end {
+            return output;
+        }
+        let entry = match (code < table.len(), previous) {
+            (true, _) => table[code].clone(),
+            (false, Some(previous)) => {
+                let mut entry = table[previous].clone();
+                entry.push(table[previous][0]);
+                entry
+            }
+            (false, None) => panic!("code {} before the table has it", code),
+        };
+        if let Some(previous) = previous {
+            if table.len() < 4096 {
+                let mut new = table[previous].clone();
+                new.push(entry[0]);
+                table.push(new);
+                if table.len() == 1 << size && size < 12 {
+                    size += 1;
+                }
+            }
+        }
+        output.extend_from_slice(&entry);
+        previous = Some(code);
+    }
+}
+
+fn screen(chip8: &Chip8, scale: usize) -> Vec<u8> {
+    let display = chip8.get_display();
+    let mut pixels = Vec::new();
+    for y in 0..SCREEN_HEIGHT * scale {
+        for x in 0..SCREEN_WIDTH * scale {
+            pixels.push(display.pixel(x / scale, y / scale) as u8);
+        }
+    }
+    pixels
+}
+
+#[test]
+fn gifs_hold_every_kept_frame() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..");
+    let rom = std::fs::read(root.join("roms/pong.ch8")).expect("bundled ROM exists");
+    let mut chip8 = Chip8::new();
+    chip8.seed(1);
+    chip8.load(&rom).unwrap();
+
+    let palette = Palette {
+        background: [1, 2, 3],
+        foreground: [250, 251, 252],
+    };
+    let mut clip = Clip::new(ClipOptions {
+        palette,
+        scale: 3,
+        frame_skip: 1,
+    });
+    let mut expected = Vec::new();
+    for frame in 0..240 {
+        chip8.run_frame();
+        clip.capture(chip8.get_display());
+        if frame % 2 == 0 {
+            expected.push(screen(&chip8, 3));
+        }
+    }
+    assert_eq!(clip.len(), 120);
+
+    let (colors, frames) = decode_gif(&clip.to_gif());
+    assert_eq!(colors, [1, 2, 3, 250, 251, 252]);
+    // Repeated frames are merged, their delays add up to the four seconds recorded
+    assert!(frames.len() < 120);
+    assert_eq!(
+        frames.iter().map(|(delay, _)| *delay as u32).sum::<u32>(),
+        400
+    );
+    let mut shown = frames.iter().flat_map(|(delay, pixels)| {
+        // Each kept frame lasts 1/30 s, about 3.33 hundredths
+        std::iter::repeat_n(pixels, (*delay as usize * 3 + 5) / 10)
+    });
+    for (index, pixels) in expected.iter().enumerate() {
+        assert_eq!(shown.next(), Some(pixels), "frame {}", index);
+    }
+}
+
+#[test]
+fn large_gifs_restart_the_code_table() {
+    // Noise fills the 4096 codes many times over
+    let mut display = Display::default();
+    let mut seed = 0x1234_5678u32;
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            display.set_pixel(x, y, seed >> 16 & 1 == 1);
+        }
+    }
+    let mut clip = Clip::new(ClipOptions {
+        scale: 5,
+        ..ClipOptions::default()
+    });
+    clip.capture(&display);
+    let (_, frames) = decode_gif(&clip.to_gif());
+    let expected: Vec<u8> = (0..SCREEN_HEIGHT * 5)
+        .flat_map(|y| (0..SCREEN_WIDTH * 5).map(move |x| (x, y)))
+        .map(|(x, y)| display.pixel(x / 5, y / 5) as u8)
+        .collect();
+    assert_eq!(frames, [(1, expected)]);
+}
+
+#[test]
+fn y4m_has_a_frame_per_kept_screen() {
+    let mut display = Display::default();
+    display.set_pixel(0, 0, true);
+    let mut clip = Clip::new(ClipOptions {
+        palette: Palette {
+            background: [0, 0, 0],
+            foreground: [255, 255, 255],
+        },
+        scale: 1,
+        frame_skip: 2,
+    });
+    for _ in 0..7 {
+        clip.capture(&display);
+    }
+    let y4m = clip.to_y4m();
+    let header = b"YUV4MPEG2 W64 H32 F60:3 Ip A1:1 C444\n";
+    assert!(y4m.starts_with(header));
+    let frame = 6 + 3 * 64 * 32;
+    assert_eq!(y4m.len(), header.len() + 3 * frame);
+    let first = &y4m[header.len()..header.len() + frame];
+    assert_eq!(&first[..6], b"FRAME\n");
+    // Y, then Cb and Cr: white on black, with no color
+    assert_eq!(&first[6..8], &[255, 0]);
+    assert_eq!(&first[6 + 2048..6 + 2050], &[128, 128]);
+    assert_eq!(&first[6 + 4096..6 + 4098], &[128, 128]);
+}
@@ -0,0 +1,94 @@
+//! Runs the bundled test ROMs headless and compares the screen with checked-in golden images.
+//!
+//! Run with `UPDATE_GOLDEN=1` to rewrite the golden images after an intended change.
+
+use core::chip8::Chip8;
+use std::path::PathBuf;
+
+const SEED: u64 = 0xC8;
+
+fn run(rom: &str, frames: usize) -> String {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..");
+    let rom = std::fs::read(root.join("roms").join(rom)).expect("bundled ROM exists");
+
+    let mut chip8 = Chip8::new();
+    chip8.seed(SEED);
+    chip8.load(&rom).expect("ROM fits in memory");
+    for _ in 0..frames {
+        chip8.run_frame();
+    }
+    chip8.get_display().to_string()
+}
+
+fn assert_golden(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.txt", name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("missing golden image {:?}, run with UPDATE_GOLDEN=1", path));
+    if expected != actual {
+        panic!(
+            "{} doesn't match its golden image\n{}",
+            name,
+            diff(&expected, actual)
+        );
+    }
+}
+
+/// Expected and actual screens side by side, with differing rows marked and
+/// differing pixels shown as `+` (only lit in actual) or `-` (only lit in expected).
+fn diff(expected: &str, actual: &str) -> String {
+    let mut out = String::new();
+    let width = expected.lines().next().map_or(0, str::len);
+    out += &format!(
+        "   {:<w$} | {:<w$} | diff\n",
+        "expected",
+        "actual",
+        w = width
+    );
+
+    let mut actual_lines = actual.lines();
+    for expected_line in expected.lines() {
+        let actual_line = actual_lines.next().unwrap_or("");
+        let marks: String = expected_line
+            .chars()
+            .zip(actual_line.chars())
+            .map(|pair| match pair {
+                ('.', '#') => '+',
+                ('#', '.') => '-',
+                _ => ' ',
+            })
+            .collect();
+        let marker = if expected_line == actual_line {
+            ' '
+        } else {
+            '>'
+        };
+        out += &format!(
+            "{}  {} | {} | {}\n",
+            marker, expected_line, actual_line, marks
+        );
+    }
+    out
+}
+
+#[test]
+fn chip8_logo() {
+    assert_golden("1-chip8-logo", &run("1-chip8-logo.ch8", 60));
+}
+
+#[test]
+fn corax_plus() {
+    assert_golden("3-corax+", &run("3-corax+.ch8", 120));
+}
+
+#[test]
+fn flags() {
+    assert_golden("4-flags", &run("4-flags.ch8", 120));
+}
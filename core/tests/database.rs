@@ -0,0 +1,184 @@
+#![cfg(feature = "database")]
+
+use core::bus::Bus;
+use core::chip8::{Chip8, DEFAULT_TICKRATE};
+use core::database::{sha1_hex, DatabaseError, RomDatabase, DEFAULT_PLATFORM};
+use core::timing::Timing;
+use core::Quirks;
+use std::path::PathBuf;
+
+const PLATFORMS: &str = r#"[
+    {
+        "id": "originalChip8",
+        "name": "Cosmac VIP",
+        "defaultTickrate": 15,
+        "quirks": { "shift": false, "wrap": false, "vblank": true, "logic": true }
+    },
+    {
+        "id": "modernChip8",
+        "name": "Modern CHIP-8",
+        "defaultTickrate": 12,
+        "quirks": { "shift": false, "wrap": false }
+    }
+]"#;
+
+const SPIN: [u8; 2] = [0x12, 0x00];
+const NOTHING: [u8; 2] = [0x00, 0xE0];
+
+fn database() -> RomDatabase {
+    let programs = format!(
+        r##"[
+            {{
+                "title": "Spin",
+                "authors": ["Someone"],
+                "roms": {{
+                    "{spin}": {{
+                        "platforms": ["originalChip8"],
+                        "quirkyPlatforms": {{ "originalChip8": {{ "jump": true, "vblank": false }} }},
+                        "startAddress": 1536,
+                        "keys": {{ "up": 5 }},
+                        "colors": {{ "pixels": ["#000000", "#ffffff"] }}
+                    }}
+                }}
+            }},
+            {{
+                "title": "Nothing",
+                "roms": {{ "{nothing}": {{ "tickrate": 40 }} }}
+            }}
+        ]"##,
+        spin = sha1_hex(&SPIN),
+        nothing = sha1_hex(&NOTHING)
+    );
+    let hashes = format!(
+        r#"{{ "{}": 0, "{}": 1 }}"#,
+        sha1_hex(&SPIN),
+        sha1_hex(&NOTHING)
+    );
+    RomDatabase::from_json(&programs, &hashes, PLATFORMS).unwrap()
+}
+
+#[test]
+fn lookups_merge_the_rom_with_its_platform() {
+    let info = database().lookup(&SPIN).unwrap();
+    assert_eq!(info.title, "Spin");
+    assert_eq!(info.authors, ["Someone"]);
+    assert_eq!(info.platform, "originalChip8");
+    assert_eq!(info.platform_name.as_deref(), Some("Cosmac VIP"));
+    // The platform's tickrate, since the ROM has none
+    assert_eq!(info.tickrate, Some(15));
+    assert_eq!(info.start_address, Some(0x600));
+    assert_eq!(info.keys["up"], 5);
+    assert_eq!(info.colors.as_ref().unwrap().pixels, ["#000000", "#ffffff"]);
+
+    // Platform quirks first, then the overrides of the ROM on that platform
+    assert_eq!(
+        info.quirks,
+        Quirks {
+            shift: false,
+            wrap: false,
+            logic: true,
+            jump: true,
+            vblank: false,
+            ..Quirks::default()
+        }
+    );
+
+    assert_eq!(database().lookup_hash(&sha1_hex(&SPIN)), Some(info));
+    assert_eq!(database().lookup(&[0x12, 0x02]), None);
+}
+
+#[test]
+fn roms_without_a_platform_get_the_default_one() {
+    let info = database().lookup(&NOTHING).unwrap();
+    assert_eq!(info.title, "Nothing");
+    assert_eq!(info.platform, DEFAULT_PLATFORM);
+    assert_eq!(info.platform_name.as_deref(), Some("Modern CHIP-8"));
+    assert_eq!(info.tickrate, Some(40));
+    assert!(!info.quirks.shift && !info.quirks.wrap);
+}
+
+#[test]
+fn hashes_must_point_at_programs() {
+    let hashes = r#"{ "0000": 3 }"#;
+    match RomDatabase::from_json("[]", hashes, "[]") {
+        Err(DatabaseError::InvalidIndex { hash, index }) => {
+            assert_eq!((hash.as_str(), index), ("0000", 3))
+        }
+        other => panic!("expected an invalid index, got {:?}", other),
+    }
+    assert!(matches!(
+        RomDatabase::from_json("{", "{}", "[]"),
+        Err(DatabaseError::Json(_))
+    ));
+}
+
+#[test]
+fn the_embedded_database_knows_the_bundled_roms() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..");
+    let pong = std::fs::read(root.join("roms/pong.ch8")).unwrap();
+    let info = RomDatabase::embedded().lookup(&pong).unwrap();
+    assert_eq!(info.title, "Pong");
+    assert_eq!(info.platform, "originalChip8");
+    assert!(info.quirks.vblank);
+}
+
+#[test]
+fn load_known_applies_the_database_and_resets_for_unknown_roms() {
+    let database = database();
+    let mut chip8 = Chip8::new();
+    let info = chip8.load_known(&SPIN, &database).unwrap().unwrap();
+    assert_eq!(chip8.quirks(), info.quirks);
+    assert_eq!(chip8.tickrate(), 15);
+    assert_eq!(chip8.pc(), 0x600);
+
+    assert_eq!(chip8.load_known(&[0x12, 0x02], &database), Ok(None));
+    assert_eq!(chip8.quirks(), Quirks::default());
+    assert_eq!(chip8.tickrate(), DEFAULT_TICKRATE);
+    assert_eq!(chip8.pc(), 0x200);
+}
+
+#[test]
+fn nothing_carries_over_from_the_previous_rom() {
+    let database = database();
+    // Sets V0, I, both timers, calls a subroutine that draws, and holds a key
+    let first = [
+        0x60, 0x3C, 0xA2, 0x0E, 0xF0, 0x15, 0xF0, 0x18, 0x22, 0x0C, 0x12, 0x0A, 0xD0, 0x05, 0xF0,
+        0x90,
+    ];
+    let mut chip8 = Chip8::new();
+    chip8.load_known(&first, &database).unwrap();
+    chip8.keypress(0, true);
+    chip8.set_timing(Timing::CosmacVip).set_tickrate(1);
+    for _ in 0..6 {
+        chip8.emulate_cycle();
+    }
+    assert_eq!(chip8.stack().len(), 1);
+    assert!(chip8.get_display().lit_pixels().next().is_some());
+
+    // SKP V0, which skips while key 0 is held
+    let second = [0xE0, 0x9E, 0x12, 0x00, 0x12, 0x04];
+    chip8.load_known(&second, &database).unwrap();
+    assert_eq!(chip8.pc(), 0x200);
+    assert!((0..16).all(|register| chip8.register(register) == 0));
+    assert_eq!(chip8.i(), 0);
+    assert!(chip8.stack().is_empty());
+    assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (0, 0));
+    assert!(chip8.get_display().lit_pixels().next().is_none());
+    assert_eq!(chip8.timing(), Timing::Fixed);
+    assert_eq!(chip8.tickrate(), DEFAULT_TICKRATE);
+    // Nothing is left of the first ROM past the end of the second
+    assert_eq!(chip8.bus().peek(0x20A), 0);
+
+    chip8.emulate_cycle();
+    assert_eq!(chip8.pc(), 0x202, "key 0 was released");
+}
+
+#[test]
+fn roms_that_dont_fit_leave_the_machine_running() {
+    let mut chip8 = Chip8::new();
+    chip8.load_known(&SPIN, &database()).unwrap();
+    chip8.set_register(0, 7);
+    let rom = vec![0; 0x1000];
+    assert!(chip8.load_known(&rom, &database()).is_err());
+    assert_eq!((chip8.pc(), chip8.register(0)), (0x600, 7));
+}
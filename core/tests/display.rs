@@ -0,0 +1,89 @@
+use core::chip8::{Chip8, Engine};
+use core::{Display, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+#[test]
+fn sprite_rows_are_xored() {
+    let mut display = Display::default();
+    assert!(!display.draw_sprite_row(2, 5, 0b1100_0000, true));
+    assert!(display.pixel(2, 5));
+    assert!(display.pixel(3, 5));
+    assert!(!display.pixel(4, 5));
+
+    // Partly overlapping turns the shared pixel off, and that's a collision
+    assert!(display.draw_sprite_row(3, 5, 0b1100_0000, true));
+    assert!(display.pixel(2, 5));
+    assert!(!display.pixel(3, 5));
+    assert!(display.pixel(4, 5));
+}
+
+#[test]
+fn sprite_rows_wrap_or_clip_at_the_right_edge() {
+    let mut wrapped = Display::default();
+    wrapped.draw_sprite_row(SCREEN_WIDTH - 4, 0, 0xFF, true);
+    assert_eq!(wrapped.rows()[0], 0xF00000000000000F);
+
+    let mut clipped = Display::default();
+    clipped.draw_sprite_row(SCREEN_WIDTH - 4, 0, 0xFF, false);
+    assert_eq!(clipped.rows()[0], 0x000000000000000F);
+}
+
+#[test]
+fn lit_pixels_match_pixel() {
+    let mut display = Display::default();
+    for (x, y) in [(0, 0), (63, 0), (10, 7), (31, 31)] {
+        display.set_pixel(x, y, true);
+    }
+    let lit: Vec<_> = display.lit_pixels().collect();
+    assert_eq!(lit, [(0, 0), (63, 0), (10, 7), (31, 31)]);
+
+    let count = (0..SCREEN_HEIGHT)
+        .flat_map(|y| (0..SCREEN_WIDTH).map(move |x| (x, y)))
+        .filter(|(x, y)| display.pixel(*x, *y))
+        .count();
+    assert_eq!(count, lit.len());
+}
+
+#[test]
+fn scrolling_blanks_what_it_uncovers() {
+    let mut display = Display::default();
+    display.set_pixel(0, 0, true);
+    display.set_pixel(63, 31, true);
+
+    display.scroll_down(4);
+    assert!(display.pixel(0, 4));
+    assert_eq!(display.lit_pixels().count(), 1);
+
+    display.scroll_right(4);
+    assert!(display.pixel(4, 4));
+
+    display.scroll_left(8);
+    assert_eq!(display.lit_pixels().count(), 0);
+
+    display.set_pixel(5, 2, true);
+    display.scroll_up(2);
+    assert!(display.pixel(5, 0));
+    display.scroll_up(SCREEN_HEIGHT);
+    assert_eq!(display.lit_pixels().count(), 0);
+}
+
+#[test]
+fn dxyn_sets_vf_only_when_a_lit_pixel_is_turned_off() {
+    let rom = [
+        0xF0, 0x29, // 0x200: LD F, V0
+        0xD0, 0x05, // 0x202: DRW V0, V0, 5
+        0x81, 0xF0, // 0x204: LD V1, VF
+        0xD0, 0x05, // 0x206: DRW V0, V0, 5
+        0x82, 0xF0, // 0x208: LD V2, VF
+        0x12, 0x0A, // 0x20A: JP 0x20A
+    ];
+    for engine in [Engine::Interpreter, Engine::Threaded, Engine::Verify] {
+        let mut chip8 = Chip8::with_engine(engine);
+        chip8.load(&rom).unwrap();
+        // Overwritten by the first draw
+        chip8.set_register(0xF, 1);
+        chip8.run_frame();
+        assert_eq!(chip8.register(1), 0, "{:?}: first draw", engine);
+        assert_eq!(chip8.register(2), 1, "{:?}: same sprite again", engine);
+        assert_eq!(chip8.get_display().lit_pixels().count(), 0, "{:?}", engine);
+    }
+}
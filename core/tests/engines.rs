@@ -0,0 +1,114 @@
+use core::chip8::{Chip8, Engine, RunError};
+use core::opcode::UnknownOpcodeError;
+use core::timing::Timing;
+use core::Quirks;
+use std::path::PathBuf;
+
+const ROMS: [&str; 4] = [
+    "1-chip8-logo.ch8",
+    "3-corax+.ch8",
+    "4-flags.ch8",
+    "pong.ch8",
+];
+
+fn rom(name: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../roms")
+        .join(name);
+    std::fs::read(path).expect("bundled ROM exists")
+}
+
+/// Runs `rom` with `Engine::Verify`, which panics as soon as a frame differs from the interpreter.
+fn verify(name: &str, quirks: Quirks, timing: Timing) {
+    let mut chip8 = Chip8::with_engine(Engine::Verify);
+    chip8.seed(7).set_quirks(quirks).set_timing(timing);
+    chip8.load(&rom(name)).unwrap();
+    for frame in 0..300u32 {
+        // Mash the paddles so Pong takes different paths
+        chip8.keypress(1, frame % 40 < 20);
+        chip8.keypress(0xD, frame % 70 < 30);
+        chip8.run_frame();
+    }
+}
+
+#[test]
+fn threaded_matches_interpreter() {
+    for name in ROMS {
+        verify(name, Quirks::default(), Timing::Fixed);
+    }
+}
+
+#[test]
+fn threaded_matches_interpreter_with_vip_timing_and_quirks() {
+    let vip = Quirks {
+        shift: false,
+        wrap: false,
+        vblank: true,
+        logic: true,
+        ..Quirks::default()
+    };
+    for name in ROMS {
+        verify(name, vip, Timing::CosmacVip);
+    }
+}
+
+#[test]
+fn engines_draw_the_same_screen() {
+    for name in ROMS {
+        let [interpreter, threaded] = [Engine::Interpreter, Engine::Threaded].map(|engine| {
+            let mut chip8 = Chip8::with_engine(engine);
+            chip8.seed(7);
+            chip8.load(&rom(name)).unwrap();
+            for _ in 0..120 {
+                chip8.run_frame();
+            }
+            chip8.get_display().to_string()
+        });
+        assert_eq!(interpreter, threaded, "{}", name);
+    }
+}
+
+#[test]
+fn unknown_opcodes_stop_every_engine_on_them() {
+    // Sets the delay timer, counts in V0 and runs into 5XY1, which isn't an instruction
+    let rom = [0x61, 0x05, 0xF1, 0x15, 0x70, 0x01, 0x70, 0x01, 0x50, 0x11];
+    for engine in [Engine::Interpreter, Engine::Threaded, Engine::Verify] {
+        let mut chip8 = Chip8::with_engine(engine);
+        chip8.load(&rom).unwrap();
+        assert_eq!(
+            chip8.try_run_frame(),
+            Err(RunError::UnknownOpcode(UnknownOpcodeError(0x5011))),
+            "{:?}",
+            engine
+        );
+        assert_eq!(chip8.pc(), 0x208, "{:?}", engine);
+        assert_eq!(chip8.register(0), 2, "{:?}", engine);
+        // The frame didn't finish, so the timers didn't tick
+        assert_eq!(chip8.delay_timer(), 5, "{:?}", engine);
+        assert_eq!(
+            chip8.try_emulate_cycle(),
+            Err(RunError::UnknownOpcode(UnknownOpcodeError(0x5011)))
+        );
+        assert_eq!(chip8.pc(), 0x208, "{:?}", engine);
+    }
+}
+
+#[test]
+fn stack_overflows_and_underflows_stop_every_engine_on_the_instruction() {
+    // Calls itself forever, then a return with nothing to return to
+    let cases: [(&[u8], RunError, u16); 2] = [
+        (&[0x22, 0x00], RunError::StackOverflow, 0x200),
+        (&[0x60, 0x01, 0x00, 0xEE], RunError::StackUnderflow, 0x202),
+    ];
+    for (rom, error, pc) in cases {
+        for timing in [Timing::Fixed, Timing::CosmacVip] {
+            for engine in [Engine::Interpreter, Engine::Threaded, Engine::Verify] {
+                let mut chip8 = Chip8::with_engine(engine);
+                chip8.set_timing(timing).load(rom).unwrap();
+                let result = (0..3).try_for_each(|_| chip8.try_run_frame());
+                assert_eq!(result, Err(error), "{:?} {:?}", engine, timing);
+                assert_eq!(chip8.pc(), pc, "{:?} {:?}", engine, timing);
+            }
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use core::chip8::{Chip8, Engine};
+use core::heatmap::{HeatmapBus, Usage};
+use core::Ram;
+
+// I = 0x20A, V0 = 123, store its digits at I, read them back into V0-V2, then spin at 0x208.
+const DIGITS: [u8; 13] = [
+    0xA2, 0x0A, 0x60, 0x7B, 0xF0, 0x33, 0xF2, 0x65, 0x12, 0x08, 0x00, 0x00, 0x00,
+];
+
+fn run() -> Chip8<HeatmapBus> {
+    // Fetches are counted whatever the engine
+    let mut chip8 = Chip8::with_bus(HeatmapBus::new(Ram::default()));
+    chip8.set_engine(Engine::Threaded);
+    chip8.load(&DIGITS).unwrap();
+    chip8.run_frame();
+    chip8
+}
+
+#[test]
+fn heatmaps_count_reads_writes_and_executes() {
+    let chip8 = run();
+    let heatmap = chip8.bus().heatmap();
+    assert_eq!(heatmap.executes(0x200), 1);
+    assert_eq!(heatmap.executes(0x201), 1);
+    assert_eq!(heatmap.executes(0x208), 6);
+    assert_eq!((heatmap.reads(0x20A), heatmap.writes(0x20A)), (1, 1));
+    assert_eq!(heatmap.reads(0x20D), 0);
+    assert_eq!(heatmap.usage(0x206), Usage::Executed);
+    assert_eq!(heatmap.usage(0x20C), Usage::Written);
+    assert_eq!(heatmap.usage(0x20D), Usage::Untouched);
+
+    assert_eq!(
+        heatmap.table(),
+        "start  end    usage            reads     writes   executes\n\
+         0x200  0x209  code                 0          0         20\n\
+         0x20A  0x20C  written              3          3          0\n"
+    );
+    let map = heatmap.map();
+    assert_eq!(map.lines().count(), 64);
+    assert!(map.contains("\n0x200 xxxxxxxxxxwww...."));
+
+    let mut chip8 = chip8;
+    chip8.bus_mut().heatmap_mut().clear();
+    assert_eq!(chip8.bus().heatmap().executes(0x208), 0);
+}
+
+#[test]
+fn heatmaps_render_one_channel_per_access() {
+    let chip8 = run();
+    let heatmap = chip8.bus().heatmap();
+    let rgb = heatmap.to_rgb(1);
+    assert_eq!(rgb.len(), 64 * 64 * 3);
+    let pixel = |addr: usize| &rgb[addr * 3..addr * 3 + 3];
+    // Brightness goes by significant bits: 1 run out of 6 at most is a third of the way
+    assert_eq!(pixel(0x200), [0, 0, 127]);
+    assert_eq!(pixel(0x208), [0, 0, 255]);
+    assert_eq!(pixel(0x20A), [255, 255, 0]);
+    assert_eq!(pixel(0x20D), [0, 0, 0]);
+
+    let png = heatmap.to_png(2);
+    assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR"));
+    assert_eq!(png[16..24], [0, 0, 0, 128, 0, 0, 0, 128]);
+    assert!(png.ends_with(b"IEND\xAE\x42\x60\x82"));
+    // Stored blocks keep the rows as they are, each after its filter byte
+    let scaled = heatmap.to_rgb(2);
+    let row = [&[0][..], &scaled[..128 * 3]].concat();
+    assert!(png.windows(row.len()).any(|window| window == row));
+}
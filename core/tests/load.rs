@@ -0,0 +1,88 @@
+use core::bus::Bus;
+use core::chip8::{Chip8, LoadError, LoadOptions};
+use core::MEMORY_SIZE;
+
+/// Bytes taken by the font set at the bottom of memory.
+const FONT_SIZE: u16 = 80;
+
+#[test]
+fn oversized_roms_are_rejected() {
+    let mut chip8 = Chip8::new();
+    let rom = vec![0xAA; MEMORY_SIZE - 0x200 + 1];
+    assert_eq!(
+        chip8.load(&rom),
+        Err(LoadError::RomTooLarge {
+            size: rom.len(),
+            max: MEMORY_SIZE - 0x200
+        })
+    );
+    // Nothing was written
+    assert_eq!(chip8.bus().peek(0x200), 0);
+}
+
+#[test]
+fn a_rom_can_fill_memory_exactly() {
+    let mut chip8 = Chip8::new();
+    let rom = vec![0xAA; MEMORY_SIZE - 0x200];
+    chip8.load(&rom).unwrap();
+    assert_eq!(chip8.bus().peek(0x200), 0xAA);
+    assert_eq!(chip8.bus().peek(MEMORY_SIZE as u16 - 1), 0xAA);
+}
+
+#[test]
+fn roms_cant_overwrite_the_font_or_start_outside_memory() {
+    let mut chip8 = Chip8::new();
+    for address in [0, FONT_SIZE - 1, MEMORY_SIZE as u16, u16::MAX] {
+        let options = LoadOptions {
+            address,
+            ..LoadOptions::default()
+        };
+        assert_eq!(
+            chip8.load_with(&[0x12, 0x00], options),
+            Err(LoadError::InvalidAddress(address))
+        );
+    }
+    // The font of 0 is untouched
+    assert_eq!(chip8.bus().peek(0), 0xF0);
+
+    let options = LoadOptions {
+        address: FONT_SIZE,
+        ..LoadOptions::default()
+    };
+    assert_eq!(chip8.load_with(&[0x12, 0x50], options), Ok(()));
+}
+
+#[test]
+fn roms_load_at_a_custom_address() {
+    let mut chip8 = Chip8::new();
+    let options = LoadOptions {
+        address: 0x600,
+        ..LoadOptions::default()
+    };
+    // LD V0, 0x2A, then spin
+    chip8.load_with(&[0x60, 0x2A, 0x16, 0x02], options).unwrap();
+    assert_eq!(chip8.pc(), 0x600);
+    assert_eq!(chip8.bus().peek(0x200), 0);
+
+    chip8.emulate_cycle();
+    assert_eq!(chip8.register(0), 0x2A);
+    assert_eq!(chip8.pc(), 0x602);
+}
+
+#[test]
+fn clear_memory_drops_the_previous_rom_but_not_the_font() {
+    let mut chip8 = Chip8::new();
+    chip8.load(&[0x11; 16]).unwrap();
+
+    chip8.load(&[0x22; 4]).unwrap();
+    assert_eq!(chip8.bus().peek(0x204), 0x11, "left over without clearing");
+
+    let options = LoadOptions {
+        clear_memory: true,
+        ..LoadOptions::default()
+    };
+    chip8.load_with(&[0x33; 4], options).unwrap();
+    assert_eq!(chip8.bus().peek(0x203), 0x33);
+    assert_eq!(chip8.bus().peek(0x204), 0);
+    assert_eq!(chip8.bus().peek(0), 0xF0);
+}
@@ -0,0 +1,149 @@
+use core::chip8::{Chip8, Engine, RunError};
+use core::movie::{KeyEvent, Movie, MovieError};
+use core::opcode::UnknownOpcodeError;
+use core::state::StateError;
+use std::path::PathBuf;
+
+fn pong() -> Vec<u8> {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..");
+    std::fs::read(root.join("roms/pong.ch8")).expect("bundled ROM exists")
+}
+
+/// Pong with a paddle moving up and down, recorded from an unseeded machine.
+fn record_pong(frames: usize) -> (Movie, String) {
+    let mut chip8 = Chip8::new();
+    chip8.load(&pong()).unwrap();
+    chip8.run_frame();
+    chip8.start_recording();
+    for frame in 0..frames {
+        // Reported every frame like a frontend does, only the changes are kept
+        chip8.keypress(1, frame % 40 < 20);
+        chip8.keypress(4, frame % 40 >= 20);
+        chip8.run_frame();
+    }
+    let movie = chip8.stop_recording().unwrap();
+    (movie, chip8.get_display().to_string())
+}
+
+#[test]
+fn replays_reproduce_the_recording() {
+    let (movie, screen) = record_pong(300);
+    assert_eq!(movie.frames(), 300);
+    assert_eq!(movie.keys().len(), 29);
+    assert_eq!(
+        movie.keys()[1],
+        KeyEvent {
+            frame: 20,
+            cycle: 0,
+            key: 1,
+            pressed: false
+        }
+    );
+
+    let bytes = movie.to_bytes();
+    let movie = Movie::from_bytes(&bytes).unwrap();
+    assert_eq!(movie.to_bytes(), bytes);
+
+    // Fresh machines with their own random seeds, the movie brings its own
+    for engine in [Engine::Interpreter, Engine::Threaded] {
+        let mut chip8 = Chip8::with_engine(engine);
+        movie.replay(&mut chip8).unwrap();
+        assert_eq!(chip8.get_display().to_string(), screen);
+    }
+}
+
+#[test]
+fn stepping_one_cycle_at_a_time_is_recorded() {
+    // Waits for a key, then draws its digit and waits again
+    let rom = [0xF0, 0x0A, 0xF0, 0x29, 0xD0, 0x15, 0x12, 0x00];
+    let mut chip8 = Chip8::new();
+    chip8.load(&rom).unwrap();
+    chip8.start_recording();
+
+    chip8.run_frame();
+    chip8.keypress(0xA, true);
+    for _ in 0..3 {
+        chip8.emulate_cycle();
+    }
+    chip8.keypress(0xA, false);
+    chip8.emulate_cycle();
+    chip8.tick_timers();
+    chip8.keypress(0x3, true);
+    chip8.emulate_cycle();
+    chip8.run_frame();
+    chip8.clean_keyboard();
+    chip8.run_frame();
+    chip8.emulate_cycle();
+    chip8.emulate_cycle();
+
+    let movie = chip8.stop_recording().unwrap();
+    assert!(!chip8.is_recording());
+    assert_eq!(movie.frames(), 4);
+    assert_eq!(
+        movie.keys()[1],
+        KeyEvent {
+            frame: 1,
+            cycle: 3,
+            key: 0xA,
+            pressed: false
+        }
+    );
+
+    let mut replayed = Chip8::new();
+    movie.replay(&mut replayed).unwrap();
+    assert_eq!(replayed.save_state(), chip8.save_state());
+}
+
+#[test]
+fn broken_replays_are_reported() {
+    let (movie, _) = record_pong(120);
+    let mut bytes = movie.to_bytes();
+
+    assert_eq!(Movie::from_bytes(b"C8ST"), Err(MovieError::BadMagic));
+    assert_eq!(
+        Movie::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(MovieError::Corrupt(StateError::Truncated))
+    );
+    bytes[4] += 1;
+    assert_eq!(
+        Movie::from_bytes(&bytes),
+        Err(MovieError::UnsupportedVersion(bytes[4]))
+    );
+
+    // Without the last key change the paddle ends up elsewhere
+    let bytes = movie.to_bytes();
+    let keys = movie.keys().len();
+    let mut edited = bytes.clone();
+    let count_at = edited.len() - 4 - 14 * keys - 4;
+    edited[count_at..count_at + 4].copy_from_slice(&(keys as u32 - 1).to_be_bytes());
+    edited.drain(count_at + 4 + 14 * (keys - 1)..count_at + 4 + 14 * keys);
+    let edited = Movie::from_bytes(&edited).unwrap();
+    assert_eq!(edited.keys(), &movie.keys()[..keys - 1]);
+    assert!(matches!(
+        edited.replay(&mut Chip8::new()),
+        Err(MovieError::Desync { expected, .. }) if expected == movie.screen_hash()
+    ));
+}
+
+#[test]
+fn replays_stopping_on_an_instruction_are_reported() {
+    let mut chip8 = Chip8::new();
+    chip8.load(&[0x12, 0x00]).unwrap();
+    chip8.start_recording();
+    chip8.run_frame();
+    chip8.run_frame();
+    let mut bytes = chip8.stop_recording().unwrap().to_bytes();
+
+    // The jump at 0x200 of the starting state, past the movie and state headers
+    let at = 5 + 4 + 5 + 0x200;
+    assert_eq!(bytes[at..at + 2], [0x12, 0x00]);
+    bytes[at..at + 2].copy_from_slice(&[0x50, 0x11]);
+    let movie = Movie::from_bytes(&bytes).unwrap();
+    assert_eq!(
+        movie.replay(&mut chip8),
+        Err(MovieError::Run(RunError::UnknownOpcode(
+            UnknownOpcodeError(0x5011)
+        )))
+    );
+    assert_eq!(chip8.pc(), 0x200);
+}
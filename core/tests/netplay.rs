@@ -0,0 +1,183 @@
+use core::chip8::{Chip8, Engine, RunError};
+use core::netplay::{Netplay, NetplayError, MAX_NETPLAY_DELAY, NETPLAY_MAGIC, NETPLAY_VERSION};
+use core::opcode::UnknownOpcodeError;
+use std::io::Write;
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::thread;
+
+// Pong's paddles: 1 and 4 on the left, C and D on the right
+const LEFT: u16 = 1 << 0x1 | 1 << 0x4;
+const RIGHT: u16 = 1 << 0xC | 1 << 0xD;
+
+fn pong() -> Chip8 {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..");
+    let rom = std::fs::read(root.join("roms/pong.ch8")).expect("bundled ROM exists");
+    let mut chip8 = Chip8::new();
+    chip8.seed(5);
+    chip8.load(&rom).unwrap();
+    chip8
+}
+
+/// A connected host and guest on loopback, with the host's side built on another thread.
+fn connect(
+    host: impl FnOnce(TcpStream) -> Result<Netplay, NetplayError> + Send + 'static,
+    guest: impl FnOnce(TcpStream) -> Result<Netplay, NetplayError>,
+) -> (
+    thread::JoinHandle<Result<Netplay, NetplayError>>,
+    Result<Netplay, NetplayError>,
+) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let host = thread::spawn(move || host(listener.accept()?.0));
+    let guest = guest(TcpStream::connect(address).unwrap());
+    (host, guest)
+}
+
+fn paddle(frame: u64, keys: [u8; 2]) -> u16 {
+    let key = keys[(frame / 25 % 2) as usize];
+    1 << key
+}
+
+#[test]
+fn peers_stay_in_lockstep() {
+    let (host, guest) = connect(
+        |stream| {
+            let mut netplay = Netplay::host(stream, pong(), LEFT, 2)?;
+            for frame in 0..300 {
+                // Keys of the other player are ignored
+                netplay.run_frame(paddle(frame, [0x1, 0x4]) | 1 << 0xC)?;
+            }
+            Ok(netplay)
+        },
+        |stream| {
+            let mut netplay = Netplay::join(stream, Chip8::with_engine(Engine::Threaded), RIGHT)?;
+            for frame in 0..300 {
+                netplay.run_frame(paddle(frame + 10, [0xC, 0xD]))?;
+            }
+            Ok(netplay)
+        },
+    );
+    let host = host.join().unwrap().unwrap();
+    let guest = guest.unwrap();
+    assert_eq!(host.frame(), 300);
+    assert_eq!(host.chip8().save_state(), guest.chip8().save_state());
+
+    // Same inputs, with the delay, on a single machine
+    let mut expected = pong();
+    for frame in 0..300u64 {
+        let (left, right) = match frame.checked_sub(2) {
+            Some(sent) => (paddle(sent, [0x1, 0x4]), paddle(sent + 10, [0xC, 0xD])),
+            None => (0, 0),
+        };
+        for key in 0..16 {
+            expected.keypress(key, (left | right) & 1 << key != 0);
+        }
+        expected.run_frame();
+    }
+    assert_eq!(expected.save_state(), host.chip8().save_state());
+}
+
+#[test]
+fn desyncs_are_detected() {
+    let (host, guest) = connect(
+        |stream| {
+            let mut netplay = Netplay::host(stream, pong(), LEFT, 1)?;
+            for _ in 0..10 {
+                netplay.run_frame(0)?;
+            }
+            // A change the peer doesn't know about
+            netplay.chip8_mut().bus_mut().write(0x300, 1);
+            for _ in 0..10 {
+                netplay.run_frame(0)?;
+            }
+            Ok(netplay)
+        },
+        |stream| {
+            let mut netplay = Netplay::join(stream, Chip8::new(), RIGHT)?;
+            for _ in 0..20 {
+                netplay.run_frame(0)?;
+            }
+            Ok(netplay)
+        },
+    );
+    assert!(matches!(guest, Err(NetplayError::Desync { frame: 10, .. })));
+    assert!(matches!(
+        host.join().unwrap(),
+        Err(NetplayError::Desync { frame: 10, .. }) | Err(NetplayError::Io(_))
+    ));
+}
+
+#[test]
+fn games_that_stop_are_reported() {
+    let (host, guest) = connect(
+        |stream| {
+            let mut chip8 = Chip8::new();
+            chip8.load(&[0x60, 0x01, 0x50, 0x11]).unwrap();
+            let mut netplay = Netplay::host(stream, chip8, LEFT, 1)?;
+            netplay.run_frame(0)?;
+            Ok(netplay)
+        },
+        |stream| {
+            let mut netplay = Netplay::join(stream, Chip8::new(), RIGHT)?;
+            netplay.run_frame(0)?;
+            Ok(netplay)
+        },
+    );
+    for result in [host.join().unwrap(), guest] {
+        assert!(matches!(
+            result,
+            Err(NetplayError::Run(RunError::UnknownOpcode(
+                UnknownOpcodeError(0x5011)
+            )))
+        ));
+    }
+}
+
+#[test]
+fn peers_cant_share_keys() {
+    let (host, guest) = connect(
+        |stream| Netplay::host(stream, pong(), LEFT, 2),
+        |stream| Netplay::join(stream, Chip8::new(), LEFT | RIGHT),
+    );
+    assert!(matches!(guest, Err(NetplayError::Protocol(_))));
+    assert!(matches!(
+        host.join().unwrap(),
+        Err(NetplayError::Protocol(_))
+    ));
+}
+
+#[test]
+fn delays_over_the_maximum_are_refused() {
+    // A host asking for a delay that would take gigabytes of inputs
+    let (host, guest) = connect(
+        |mut stream| {
+            let mut hello = NETPLAY_MAGIC.to_vec();
+            hello.push(NETPLAY_VERSION);
+            hello.extend_from_slice(&u32::MAX.to_be_bytes());
+            hello.extend_from_slice(&LEFT.to_be_bytes());
+            hello.extend_from_slice(&0u32.to_be_bytes());
+            stream.write_all(&hello)?;
+            Err(NetplayError::Protocol("sent a bad hello"))
+        },
+        |stream| Netplay::join(stream, Chip8::new(), RIGHT),
+    );
+    assert!(matches!(guest, Err(NetplayError::Protocol(_))));
+    let _ = host.join().unwrap();
+
+    let (host, guest) = connect(
+        |stream| Netplay::host(stream, pong(), LEFT, MAX_NETPLAY_DELAY + 1),
+        |stream| Netplay::join(stream, Chip8::new(), RIGHT),
+    );
+    assert!(matches!(
+        host.join().unwrap(),
+        Err(NetplayError::Protocol(_))
+    ));
+    assert!(matches!(guest, Err(NetplayError::Io(_))));
+
+    let (host, guest) = connect(
+        |stream| Netplay::host(stream, pong(), LEFT, MAX_NETPLAY_DELAY),
+        |stream| Netplay::join(stream, Chip8::new(), RIGHT),
+    );
+    assert!(host.join().unwrap().is_ok() && guest.is_ok());
+}
@@ -0,0 +1,157 @@
+use core::chip8::Chip8;
+use core::opcode::{disassemble, Opcode, UnknownOpcodeError};
+
+#[test]
+fn every_valid_encoding_round_trips() {
+    let mut valid = 0;
+    for hex_opcode in 0..=u16::MAX {
+        if let Ok(opcode) = Opcode::decode(hex_opcode) {
+            assert_eq!(
+                opcode.encode(),
+                hex_opcode,
+                "{:#06x} decoded as {:?}",
+                hex_opcode,
+                opcode
+            );
+            valid += 1;
+        }
+    }
+    // 2 without operands, 10 using the low 12 bits, 11 with X and Y, 11 with X only
+    assert_eq!(valid, 2 + 10 * 4096 + 11 * 256 + 11 * 16);
+}
+
+#[test]
+fn unknown_opcodes_are_rejected() {
+    for hex_opcode in [0x0000, 0x00E1, 0x5121, 0x800F, 0x9001, 0xE09F, 0xF0FF] {
+        assert_eq!(
+            Opcode::decode(hex_opcode),
+            Err(UnknownOpcodeError(hex_opcode))
+        );
+    }
+}
+
+#[test]
+fn only_5xy0_skips_on_equal_registers() {
+    assert_eq!(Opcode::decode(0x5120), Ok(Opcode::_5xy0(1, 2)));
+    for n in 1..=0xF {
+        let hex_opcode = 0x5120 | n;
+        assert_eq!(
+            Opcode::decode(hex_opcode),
+            Err(UnknownOpcodeError(hex_opcode))
+        );
+    }
+}
+
+#[test]
+fn encoding_cuts_operands_to_their_field() {
+    assert_eq!(Opcode::_6xnn(0x1F, 0x00).encode(), 0x6F00);
+    assert_eq!(Opcode::_8xy4(0x12, 0x34).encode(), 0x8244);
+    assert_eq!(Opcode::_Annn(0xF123).encode(), 0xA123);
+    assert_eq!(Opcode::_2nnn(0xFFFF).encode(), 0x2FFF);
+    assert_eq!(Opcode::_Dxyn(0, 0, 0x1F).encode(), 0xD00F);
+    assert_eq!(Opcode::_Fx65(0x10).encode(), 0xF065);
+}
+
+#[test]
+fn display_uses_canonical_mnemonics() {
+    let cases = [
+        (0x00E0, "CLS"),
+        (0x00EE, "RET"),
+        (0x1228, "JP 0x228"),
+        (0x2ABC, "CALL 0xABC"),
+        (0x3A2F, "SE VA, 0x2F"),
+        (0x5120, "SE V1, V2"),
+        (0x6C3F, "LD VC, 0x3F"),
+        (0x8126, "SHR V1, V2"),
+        (0xA2EA, "LD I, 0x2EA"),
+        (0xB300, "JP V0, 0x300"),
+        (0xD015, "DRW V0, V1, 0x5"),
+        (0xE0A1, "SKNP V0"),
+        (0xF00A, "LD V0, K"),
+        (0xFE33, "LD B, VE"),
+        (0xF255, "LD [I], V2"),
+        (0xF265, "LD V2, [I]"),
+    ];
+    for (hex_opcode, mnemonic) in cases {
+        assert_eq!(Opcode::decode(hex_opcode).unwrap().to_string(), mnemonic);
+    }
+}
+
+#[test]
+fn metadata() {
+    let add = Opcode::decode(0x8124).unwrap();
+    assert!(add.reads_vx());
+    assert!(add.writes_vx());
+    assert!(add.writes_vf());
+    assert!(!add.is_branch());
+    assert!(!add.touches_memory());
+
+    let load = Opcode::decode(0x6A02).unwrap();
+    assert!(!load.reads_vx());
+    assert!(load.writes_vx());
+    assert!(!load.writes_vf());
+
+    let skip = Opcode::decode(0xE19E).unwrap();
+    assert!(skip.is_branch());
+    assert!(skip.reads_vx());
+
+    let store = Opcode::decode(0xF355).unwrap();
+    assert!(store.touches_memory());
+    assert!(store.writes_memory());
+    assert_eq!(store.registers_read(), 0b1111);
+
+    let fill = Opcode::decode(0xF265).unwrap();
+    assert!(fill.touches_memory());
+    assert!(!fill.writes_memory());
+    assert_eq!(fill.registers_written(), 0b111);
+
+    let draw = Opcode::decode(0xD125).unwrap();
+    assert!(draw.touches_memory());
+    assert!(draw.writes_vf());
+    assert!(!draw.writes_vx());
+
+    // Built by hand with operands past VF, only the low nibble counts
+    let add = Opcode::_8xy4(0x1F, 0x21);
+    assert_eq!(add.registers_read(), 1 << 0xF | 1 << 1);
+    assert_eq!(add.registers_written(), 1 << 0xF);
+    assert!(add.writes_vx());
+    assert_eq!(Opcode::_Bnnn(0xFFFF).registers_read(), 1 | 1 << 0xF);
+    assert_eq!(Opcode::_Fx55(0xF3).registers_read(), 0b1111);
+}
+
+#[test]
+fn disassembly_follows_the_program() {
+    // V0 = 5, CALL 0x208, JP 0x204, data, I = 0x300, RET
+    let rom = [
+        0x60, 0x05, 0x22, 0x08, 0x12, 0x04, 0xFF, 0xFF, 0xA3, 0x00, 0x00, 0xEE,
+    ];
+    let mut chip8 = Chip8::new();
+    chip8.load(&rom).unwrap();
+    let lines: Vec<String> = disassemble(chip8.bus(), 0x200, 6)
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "0x200  6005  LD V0, 0x05",
+            "0x202  2208  CALL 0x208",
+            "0x204  1204  JP 0x204",
+            "0x206  FFFF  DW 0xFFFF",
+            "0x208  A300  LD I, 0x300",
+            "0x20A  00EE  RET",
+        ]
+    );
+    assert_eq!(disassemble(chip8.bus(), 0xFFC, 4).len(), 2);
+
+    for _ in 0..3 {
+        chip8.emulate_cycle();
+    }
+    assert_eq!(
+        (chip8.pc(), chip8.i(), chip8.stack()),
+        (0x20A, 0x300, &[0x202][..])
+    );
+    chip8.set_pc(0x200).set_i(0x123);
+    chip8.emulate_cycle();
+    assert_eq!((chip8.register(0), chip8.i()), (5, 0x123));
+}
@@ -0,0 +1,56 @@
+use core::chip8::{Chip8, Engine};
+use core::profiler::Subroutine;
+use core::timing::Timing;
+
+// V0 = 3, DT = V0, CALL 0x20C, then spin at 0x206.
+// 0x20C: poll DT until it reaches 0, and return.
+const WAIT_FOR_TIMER: [u8; 20] = [
+    0x60, 0x03, 0xF0, 0x15, 0x22, 0x0C, 0x12, 0x06, 0x00, 0x00, 0x00, 0x00, 0xF1, 0x07, 0x31, 0x00,
+    0x12, 0x0C, 0x00, 0xEE,
+];
+
+#[test]
+fn profiles_count_instructions_subroutines_and_waits() {
+    // The profiler sees every instruction whatever the engine
+    let mut chip8 = Chip8::with_engine(Engine::Threaded);
+    chip8.load(&WAIT_FOR_TIMER).unwrap();
+    chip8.start_profiling();
+    for _ in 0..5 {
+        chip8.run_frame();
+    }
+    let profile = chip8.stop_profiling().unwrap();
+    assert!(!chip8.is_profiling());
+
+    assert_eq!((profile.instructions(), profile.cycles()), (50, 50));
+    assert_eq!(profile.executions(0x200), 1);
+    assert_eq!(profile.executions(0x20C), 10);
+    assert_eq!(profile.hotspots(2), [(0x206, 17), (0x20C, 10)]);
+    assert_eq!(profile.opcodes()["2NNN"], 1);
+    assert_eq!(profile.opcodes()["FX07"], 10);
+    // Nine rounds of three instructions while the timer runs, then the last read and RET
+    assert_eq!(
+        profile.subroutines()[&0x20C],
+        Subroutine {
+            calls: 1,
+            cycles: 30
+        }
+    );
+    assert_eq!(profile.busy_wait()[&0x20C], 27);
+
+    let report = profile.report(chip8.bus(), 3);
+    assert!(report.contains("27 cycles (54.0%) busy-waiting on the delay timer"));
+    assert!(report.contains("        17  0x206  1206  JP 0x206\n       ...\n"));
+    assert!(report.contains("0x20C         1 calls          30 cycles  60.0%"));
+}
+
+#[test]
+fn vip_timing_counts_machine_cycles() {
+    let mut chip8 = Chip8::new();
+    chip8.set_timing(Timing::CosmacVip);
+    chip8.load(&WAIT_FOR_TIMER).unwrap();
+    chip8.start_profiling();
+    chip8.run_frame();
+    let profile = chip8.stop_profiling().unwrap();
+    assert!(profile.cycles() > profile.instructions() * 6);
+    assert!(profile.busy_wait()[&0x20C] > 0);
+}
@@ -0,0 +1,122 @@
+use core::chip8::{Chip8, Engine};
+use core::Quirks;
+
+/// Machines with `quirks` after running `rom` for `frames` frames, one per engine.
+fn run(rom: &[u8], quirks: Quirks, setup: impl Fn(&mut Chip8), frames: usize) -> Vec<Chip8> {
+    [Engine::Interpreter, Engine::Verify]
+        .into_iter()
+        .map(|engine| {
+            let mut chip8 = Chip8::with_engine(engine);
+            chip8.set_quirks(quirks);
+            chip8.load(rom).unwrap();
+            setup(&mut chip8);
+            for _ in 0..frames {
+                chip8.run_frame();
+            }
+            chip8
+        })
+        .collect()
+}
+
+fn with(quirk: impl Fn(&mut Quirks)) -> Quirks {
+    let mut quirks = Quirks::default();
+    quirk(&mut quirks);
+    quirks
+}
+
+#[test]
+fn shift_picks_the_register_shifted() {
+    // SHR V1, V2, then spin
+    let rom = [0x81, 0x26, 0x12, 0x02];
+    let setup = |chip8: &mut Chip8| {
+        chip8.set_register(1, 0x03).set_register(2, 0x10);
+    };
+    for chip8 in run(&rom, with(|q| q.shift = true), setup, 1) {
+        assert_eq!((chip8.register(1), chip8.register(0xF)), (0x01, 1));
+    }
+    for chip8 in run(&rom, with(|q| q.shift = false), setup, 1) {
+        assert_eq!((chip8.register(1), chip8.register(0xF)), (0x08, 0));
+    }
+}
+
+#[test]
+fn logic_resets_vf() {
+    // OR, AND and XOR V0 with V1, each followed by a copy of VF
+    let rom = [
+        0x80, 0x11, 0x82, 0xF0, 0x80, 0x12, 0x83, 0xF0, 0x80, 0x13, 0x84, 0xF0, 0x12, 0x0C,
+    ];
+    let setup = |chip8: &mut Chip8| {
+        chip8.set_register(0xF, 7);
+    };
+    for chip8 in run(&rom, with(|q| q.logic = true), setup, 1) {
+        assert_eq!([2, 3, 4].map(|r| chip8.register(r)), [0, 0, 0]);
+    }
+    for chip8 in run(&rom, with(|q| q.logic = false), setup, 1) {
+        assert_eq!([2, 3, 4].map(|r| chip8.register(r)), [7, 7, 7]);
+    }
+}
+
+#[test]
+fn jump_adds_vx_instead_of_v0() {
+    // JP V0, 0x210
+    let rom = [0xB2, 0x10];
+    let mut chip8 = Chip8::new();
+    chip8.load(&rom).unwrap();
+    chip8.set_register(0, 4).set_register(2, 6);
+    chip8.emulate_cycle();
+    assert_eq!(chip8.pc(), 0x214);
+
+    chip8.load(&rom).unwrap();
+    chip8.set_quirks(with(|q| q.jump = true));
+    chip8.emulate_cycle();
+    assert_eq!(chip8.pc(), 0x216);
+}
+
+#[test]
+fn wrap_decides_whether_sprites_wrap_or_clip() {
+    // Draw the 8 pixels wide row at 0x206 at (V0, V1) = (60, 31), twice as tall
+    let rom = [0xA2, 0x06, 0xD0, 0x12, 0x12, 0x04, 0xFF, 0xFF];
+    let setup = |chip8: &mut Chip8| {
+        chip8.set_register(0, 60).set_register(1, 31);
+    };
+    for chip8 in run(&rom, with(|q| q.wrap = true), setup, 1) {
+        let display = chip8.get_display();
+        assert!(display.pixel(63, 31) && display.pixel(0, 31) && display.pixel(3, 0));
+    }
+    for chip8 in run(&rom, with(|q| q.wrap = false), setup, 1) {
+        let display = chip8.get_display();
+        assert!(display.pixel(63, 31));
+        assert!(!display.pixel(0, 31) && !display.pixel(63, 0) && !display.pixel(0, 0));
+    }
+}
+
+#[test]
+fn vblank_ends_the_frame_after_a_draw() {
+    // Draw, then count up in V1 forever
+    let rom = [0xD0, 0x01, 0x71, 0x01, 0x12, 0x02];
+    for chip8 in run(&rom, with(|q| q.vblank = true), |_| {}, 1) {
+        assert_eq!((chip8.pc(), chip8.register(1)), (0x202, 0));
+    }
+    for chip8 in run(&rom, with(|q| q.vblank = true), |_| {}, 2) {
+        assert_eq!(chip8.register(1), 5);
+    }
+    for chip8 in run(&rom, with(|q| q.vblank = false), |_| {}, 1) {
+        assert_eq!(chip8.register(1), 5);
+    }
+}
+
+#[test]
+fn memory_quirks_move_i() {
+    // LD I, 0x300, LD [I], V1, LD V1, [I], then spin
+    let rom = [0xA3, 0x00, 0xF1, 0x55, 0xF1, 0x65, 0x12, 0x06];
+    let cases = [
+        (Quirks::default(), 0x304),
+        (with(|q| q.memory_increment_by_x = true), 0x302),
+        (with(|q| q.memory_leave_i_unchanged = true), 0x300),
+    ];
+    for (quirks, i) in cases {
+        for chip8 in run(&rom, quirks, |_| {}, 1) {
+            assert_eq!(chip8.i(), i, "{:?}", quirks);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use core::chip8::Chip8;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+// Two random bytes, then loop forever
+const ROM: [u8; 6] = [0xC0, 0xFF, 0xC1, 0xFF, 0x12, 0x04];
+
+fn random_registers(chip8: &mut Chip8) -> (u8, u8) {
+    chip8.load(&ROM).unwrap();
+    chip8.run_frame();
+    (chip8.register(0), chip8.register(1))
+}
+
+#[test]
+fn injected_source_makes_cxnn_repeatable() {
+    let mut first = Chip8::new();
+    first
+        .seed_from_rng(StdRng::seed_from_u64(7))
+        .expect("StdRng never fails");
+    let mut second = Chip8::new();
+    second
+        .seed_from_rng(StdRng::seed_from_u64(7))
+        .expect("StdRng never fails");
+
+    assert_eq!(random_registers(&mut first), random_registers(&mut second));
+}
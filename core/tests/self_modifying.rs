@@ -0,0 +1,71 @@
+use core::bus::Bus;
+use core::chip8::{Chip8, Engine};
+
+// Rewrites its first instruction from `LD V2, 1` to `LD V2, 2` and runs it again.
+// A stale decoded instruction would loop forever instead of halting at 0x206.
+const ROM: [u8; 18] = [
+    0x62, 0x01, // 0x200: LD V2, 0x01
+    0x32, 0x02, // 0x202: SE V2, 0x02
+    0x12, 0x08, // 0x204: JP 0x208
+    0x12, 0x06, // 0x206: JP 0x206
+    0x60, 0x62, // 0x208: LD V0, 0x62
+    0x61, 0x02, // 0x20A: LD V1, 0x02
+    0xA2, 0x00, // 0x20C: LD I, 0x200
+    0xF1, 0x55, // 0x20E: LD [I], V1
+    0x12, 0x00, // 0x210: JP 0x200
+];
+
+#[test]
+fn rewritten_instructions_are_decoded_again() {
+    for engine in [Engine::Interpreter, Engine::Threaded, Engine::Verify] {
+        let mut chip8 = Chip8::with_engine(engine);
+        chip8.load(&ROM).unwrap();
+        for _ in 0..10 {
+            chip8.run_frame();
+        }
+        assert_eq!(chip8.register(2), 2, "{:?}", engine);
+        assert_eq!(chip8.pc(), 0x206, "{:?}", engine);
+    }
+}
+
+// Fx33 can only write digits, so it rewrites the operand of `SE V2, 0` into `SE V2, 1`.
+// The other two digits land on the jump after it, which is skipped from then on.
+// A stale decoded instruction would keep jumping back instead of halting at 0x20A.
+const BCD_ROM: [u8; 16] = [
+    0x62, 0x01, // 0x200: LD V2, 0x01
+    0x60, 0x7B, // 0x202: LD V0, 123
+    0xA2, 0x07, // 0x204: LD I, 0x207
+    0x32, 0x00, // 0x206: SE V2, 0x00
+    0x12, 0x0C, // 0x208: JP 0x20C
+    0x12, 0x0A, // 0x20A: JP 0x20A
+    0xF0, 0x33, // 0x20C: LD B, V0
+    0x12, 0x06, // 0x20E: JP 0x206
+];
+
+#[test]
+fn instructions_rewritten_by_bcd_are_decoded_again() {
+    for engine in [Engine::Interpreter, Engine::Threaded, Engine::Verify] {
+        let mut chip8 = Chip8::with_engine(engine);
+        chip8.load(&BCD_ROM).unwrap();
+        for _ in 0..10 {
+            chip8.run_frame();
+        }
+        assert_eq!(chip8.pc(), 0x20A, "{:?}", engine);
+        assert_eq!(chip8.bus().peek(0x207), 1, "{:?}", engine);
+    }
+}
+
+#[test]
+fn reloading_drops_decoded_instructions() {
+    let mut chip8 = Chip8::new();
+    chip8.load(&ROM).unwrap();
+    chip8.run_frame();
+
+    // Same program, but the first instruction loads 3
+    let mut rom = ROM;
+    rom[1] = 0x03;
+    chip8.reset();
+    chip8.load(&rom).unwrap();
+    chip8.emulate_cycle();
+    assert_eq!(chip8.register(2), 3);
+}
@@ -0,0 +1,101 @@
+use core::chip8::Chip8;
+use core::state::{StateError, STATE_VERSION};
+use core::MEMORY_SIZE;
+use std::path::PathBuf;
+
+fn pong() -> Chip8 {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..");
+    let rom = std::fs::read(root.join("roms/pong.ch8")).expect("bundled ROM exists");
+    let mut chip8 = Chip8::new();
+    chip8.seed(3);
+    chip8.load(&rom).unwrap();
+    chip8
+}
+
+fn run(chip8: &mut Chip8, frames: usize) {
+    for frame in 0..frames {
+        chip8.keypress(1, frame % 20 < 10);
+        chip8.run_frame();
+    }
+}
+
+#[test]
+fn loading_a_state_replays_the_same_frames() {
+    let mut chip8 = pong();
+    run(&mut chip8, 100);
+    let state = chip8.save_state();
+
+    run(&mut chip8, 200);
+    let expected = chip8.save_state();
+
+    // Into a fresh machine, with another seed that the state overrides
+    let mut restored = Chip8::new();
+    restored.seed(99);
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.save_state(), state);
+    run(&mut restored, 200);
+    assert_eq!(restored.save_state(), expected);
+    assert_eq!(
+        restored.get_display().to_string(),
+        chip8.get_display().to_string()
+    );
+}
+
+#[test]
+fn broken_states_are_rejected_without_changes() {
+    let mut chip8 = pong();
+    run(&mut chip8, 10);
+    let state = chip8.save_state();
+
+    let mut other = Chip8::new();
+    let before = other.save_state();
+
+    assert_eq!(other.load_state(b"nope"), Err(StateError::BadMagic));
+    assert_eq!(
+        other.load_state(&state[..state.len() - 1]),
+        Err(StateError::Truncated)
+    );
+    let mut newer = state.clone();
+    newer[4] = STATE_VERSION + 1;
+    assert_eq!(
+        other.load_state(&newer),
+        Err(StateError::UnsupportedVersion(STATE_VERSION + 1))
+    );
+    assert_eq!(other.save_state(), before);
+}
+
+#[test]
+fn states_with_registers_outside_memory_are_rejected() {
+    // Header, memory, then the delay and sound timers
+    const PC: usize = 5 + MEMORY_SIZE + 2;
+    const I: usize = PC + 3;
+    // A subroutine call, so there is a return address on the stack
+    let mut chip8 = Chip8::new();
+    chip8.load(&[0x22, 0x02, 0x12, 0x02]).unwrap();
+    chip8.emulate_cycle();
+    let state = chip8.save_state();
+
+    let corrupt = |offset: usize, value: u16| {
+        let mut state = state.clone();
+        state[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+        state
+    };
+    let cases = [
+        (corrupt(PC, MEMORY_SIZE as u16 - 1), "pc"),
+        (corrupt(PC, u16::MAX), "pc"),
+        (corrupt(I, MEMORY_SIZE as u16), "i"),
+        (corrupt(I + 2, 0xFFFF), "stack"),
+    ];
+    for (state, field) in cases {
+        assert_eq!(chip8.load_state(&state), Err(StateError::Invalid(field)));
+    }
+    assert_eq!(chip8.save_state(), state);
+
+    // The last instruction and the last byte are still fine
+    chip8
+        .load_state(&corrupt(PC, MEMORY_SIZE as u16 - 2))
+        .unwrap();
+    chip8
+        .load_state(&corrupt(I, MEMORY_SIZE as u16 - 1))
+        .unwrap();
+}
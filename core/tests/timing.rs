@@ -0,0 +1,91 @@
+use core::chip8::Chip8;
+use core::timing::{Timing, VIP_CYCLES_PER_FRAME, VIP_FRAME_BUDGET};
+
+/// COSMAC VIP machine cycles the first instruction of `rom` costs, as the profiler counts them.
+fn cycles(rom: &[u8], registers: &[(u8, u8)]) -> u64 {
+    let mut chip8 = Chip8::new();
+    chip8.set_timing(Timing::CosmacVip);
+    chip8.load(rom).unwrap();
+    for (register, value) in registers {
+        chip8.set_register(*register, *value);
+    }
+    chip8.start_profiling();
+    chip8.emulate_cycle();
+    chip8.stop_profiling().unwrap().cycles()
+}
+
+#[test]
+fn the_frame_is_what_the_vip_clock_allows() {
+    // 3.52128 MHz crystal halved, 8 clocks per machine cycle, 60 frames per second
+    assert_eq!(VIP_CYCLES_PER_FRAME, 3_521_280 / 2 / 8 / 60);
+    // Minus 128 scanlines of 8 bytes of display DMA and the timer interrupt
+    assert_eq!(VIP_FRAME_BUDGET, 3668 - 1024 - 104);
+}
+
+#[test]
+fn instructions_cost_the_fetch_plus_their_execution() {
+    // The 16 cycles of the fetch are included
+    let table: [([u8; 2], u64); 13] = [
+        ([0x00, 0xE0], 16 + 678),
+        ([0x12, 0x00], 16 + 12),
+        ([0x22, 0x00], 16 + 26),
+        ([0x30, 0x00], 16 + 10),
+        ([0x50, 0x10], 16 + 14),
+        ([0x60, 0x01], 16 + 6),
+        ([0x70, 0x01], 16 + 10),
+        ([0x80, 0x14], 16 + 44),
+        ([0xA2, 0x00], 16 + 12),
+        ([0xC0, 0xFF], 16 + 36),
+        ([0xF0, 0x1E], 16 + 18),
+        ([0xF0, 0x29], 16 + 20),
+        // Three registers stored
+        ([0xF2, 0x55], 16 + 14 + 3 * 14),
+    ];
+    for (rom, expected) in table {
+        assert_eq!(cycles(&rom, &[]), expected, "{:02X?}", rom);
+    }
+}
+
+#[test]
+fn draws_cost_more_when_x_is_off_a_byte_boundary() {
+    // DRW V0, V1, 5
+    let rom = [0xD0, 0x15];
+    // Aligned, every row is copied as is
+    assert_eq!(cycles(&rom, &[(0, 8)]), 16 + 26 + 5 * 16);
+    // Three bits off, every row is shifted three times
+    assert_eq!(cycles(&rom, &[(0, 11)]), 16 + 26 + 5 * (26 + 3 * 4));
+    assert_eq!(cycles(&rom, &[(0, 15)]), 16 + 26 + 5 * (26 + 7 * 4));
+}
+
+#[test]
+fn bcd_costs_grow_with_the_digits() {
+    // LD B, V0
+    let rom = [0xF0, 0x33];
+    assert_eq!(cycles(&rom, &[(0, 0)]), 16 + 80);
+    // 1 + 2 + 3 subtractions
+    assert_eq!(cycles(&rom, &[(0, 123)]), 16 + 80 + 6 * 16);
+    assert_eq!(cycles(&rom, &[(0, 255)]), 16 + 80 + 12 * 16);
+}
+
+#[test]
+fn a_frame_runs_as_many_instructions_as_its_budget_pays_for() {
+    // ADD V0, 1, then JP 0x200: 26 + 28 cycles a round
+    let mut chip8 = Chip8::new();
+    chip8.set_timing(Timing::CosmacVip);
+    chip8.load(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+    chip8.start_profiling();
+    chip8.run_frame();
+    let profile = chip8.stop_profiling().unwrap();
+
+    // 47 rounds leave 2 cycles, enough to start one more ADD, which overspends by 24
+    assert_eq!(profile.instructions(), 95);
+    assert_eq!(profile.cycles(), 47 * 54 + 26);
+    assert_eq!(chip8.register(0), 48);
+
+    // The tickrate doesn't matter, and the overspent cycles come out of the next frame
+    chip8.set_tickrate(1000);
+    chip8.start_profiling();
+    chip8.run_frame();
+    let profile = chip8.stop_profiling().unwrap();
+    assert_eq!(profile.cycles(), 28 + 46 * 54 + 26);
+}
@@ -0,0 +1,259 @@
+//! Everything the libretro entry points need, without any FFI.
+
+use crate::ffi::*;
+use crate::options::Options;
+use core::cheat::{Cheat, CheatError, Cheats};
+use core::chip8::{Chip8, LoadError, DEFAULT_TICKRATE};
+use core::database::{RomDatabase, RomInfo};
+use core::{Quirks, SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::collections::BTreeMap;
+use std::ffi::c_uint;
+
+pub const SAMPLE_RATE: u32 = 44100;
+pub const FPS: u32 = 60;
+/// Stereo frames of audio produced by every call to `run_frame`.
+pub const AUDIO_FRAMES: usize = (SAMPLE_RATE / FPS) as usize;
+
+const BEEP_FREQUENCY: u32 = 440;
+const BEEP_VOLUME: i16 = 6000;
+
+const WHITE: u32 = 0x00FF_FFFF;
+const BLACK: u32 = 0x0000_0000;
+
+/// Same layout as the web frontend: 1234 / QWER / ASDF / ZXCV.
+const KEYBOARD: [(c_uint, u8); 16] = [
+    (b'1' as c_uint, 0x1),
+    (b'2' as c_uint, 0x2),
+    (b'3' as c_uint, 0x3),
+    (b'4' as c_uint, 0xC),
+    (b'q' as c_uint, 0x4),
+    (b'w' as c_uint, 0x5),
+    (b'e' as c_uint, 0x6),
+    (b'r' as c_uint, 0xD),
+    (b'a' as c_uint, 0x7),
+    (b's' as c_uint, 0x8),
+    (b'd' as c_uint, 0x9),
+    (b'f' as c_uint, 0xE),
+    (b'z' as c_uint, 0xA),
+    (b'x' as c_uint, 0x0),
+    (b'c' as c_uint, 0xB),
+    (b'v' as c_uint, 0xF),
+];
+
+/// Hex key pressed by each RetroPad button, for the first two ports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    pub joypad: [[Option<u8>; 16]; 2],
+}
+
+impl Default for Keymap {
+    /// The directions on 2, 8, 4 and 6 and the action on 5, which many games use.
+    fn default() -> Self {
+        let mut joypad = [[None; 16]; 2];
+        joypad[0][RETRO_DEVICE_ID_JOYPAD_UP as usize] = Some(0x2);
+        joypad[0][RETRO_DEVICE_ID_JOYPAD_DOWN as usize] = Some(0x8);
+        joypad[0][RETRO_DEVICE_ID_JOYPAD_LEFT as usize] = Some(0x4);
+        joypad[0][RETRO_DEVICE_ID_JOYPAD_RIGHT as usize] = Some(0x6);
+        joypad[0][RETRO_DEVICE_ID_JOYPAD_A as usize] = Some(0x5);
+        joypad[0][RETRO_DEVICE_ID_JOYPAD_B as usize] = Some(0x0);
+        joypad[0][RETRO_DEVICE_ID_JOYPAD_X as usize] = Some(0xA);
+        joypad[0][RETRO_DEVICE_ID_JOYPAD_Y as usize] = Some(0xB);
+        joypad[0][RETRO_DEVICE_ID_JOYPAD_START as usize] = Some(0xF);
+        joypad[0][RETRO_DEVICE_ID_JOYPAD_SELECT as usize] = Some(0xE);
+        Keymap { joypad }
+    }
+}
+
+impl Keymap {
+    /// Uses the buttons named in the ROM database, like `up` or `player2Down`, when there are any.
+    pub fn for_rom(info: Option<&RomInfo>) -> Self {
+        let Some(info) = info.filter(|info| !info.keys.is_empty()) else {
+            return Keymap::default();
+        };
+        let mut joypad = [[None; 16]; 2];
+        for (name, key) in &info.keys {
+            let (port, button) = match name.strip_prefix("player2") {
+                Some(button) => (1, button),
+                None => (0, name.strip_prefix("player1").unwrap_or(name)),
+            };
+            let id = match button.to_ascii_lowercase().as_str() {
+                "up" => RETRO_DEVICE_ID_JOYPAD_UP,
+                "down" => RETRO_DEVICE_ID_JOYPAD_DOWN,
+                "left" => RETRO_DEVICE_ID_JOYPAD_LEFT,
+                "right" => RETRO_DEVICE_ID_JOYPAD_RIGHT,
+                "a" => RETRO_DEVICE_ID_JOYPAD_A,
+                "b" => RETRO_DEVICE_ID_JOYPAD_B,
+                _ => continue,
+            };
+            joypad[port][id as usize] = Some(*key);
+        }
+        Keymap { joypad }
+    }
+}
+
+/// Square wave played while the sound timer is running.
+#[derive(Debug, Default)]
+struct Beeper {
+    // Position in the wave, in units of 1 / SAMPLE_RATE of a period
+    phase: u32,
+}
+
+impl Beeper {
+    fn fill(&mut self, on: bool, samples: &mut Vec<i16>) {
+        samples.clear();
+        for _ in 0..AUDIO_FRAMES {
+            let sample = if !on {
+                0
+            } else if self.phase < SAMPLE_RATE / 2 {
+                BEEP_VOLUME
+            } else {
+                -BEEP_VOLUME
+            };
+            self.phase = (self.phase + BEEP_FREQUENCY) % SAMPLE_RATE;
+            samples.extend([sample, sample]);
+        }
+    }
+}
+
+pub struct Emulator {
+    pub chip8: Chip8,
+    rom: Vec<u8>,
+    rom_info: Option<RomInfo>,
+    keymap: Keymap,
+    options: Options,
+    beeper: Beeper,
+    palette: [u32; 2],
+    // Codes from the frontend by index, flattened into `cheats`
+    cheat_codes: BTreeMap<c_uint, Vec<Cheat>>,
+    cheats: Cheats,
+    /// XRGB8888, `SCREEN_WIDTH` pixels per row.
+    pub frame: Vec<u32>,
+    /// Interleaved stereo samples for the last frame.
+    pub audio: Vec<i16>,
+}
+
+impl Emulator {
+    pub fn new(rom: &[u8], options: Options) -> Result<Self, LoadError> {
+        let mut chip8 = Chip8::new();
+        let rom_info = chip8.load_known(rom, &RomDatabase::embedded())?;
+        let palette = rom_info
+            .as_ref()
+            .and_then(|info| info.colors.as_ref())
+            .and_then(|colors| {
+                let background = parse_color(colors.pixels.first()?)?;
+                let pixels = parse_color(colors.pixels.get(1)?)?;
+                Some([background, pixels])
+            })
+            .unwrap_or([BLACK, WHITE]);
+
+        let mut emulator = Emulator {
+            chip8,
+            rom: rom.to_vec(),
+            keymap: Keymap::for_rom(rom_info.as_ref()),
+            rom_info,
+            options,
+            beeper: Beeper::default(),
+            palette,
+            cheat_codes: BTreeMap::new(),
+            cheats: Cheats::default(),
+            frame: vec![BLACK; SCREEN_WIDTH * SCREEN_HEIGHT],
+            audio: Vec::with_capacity(AUDIO_FRAMES * 2),
+        };
+        emulator.set_options(options);
+        emulator.render();
+        Ok(emulator)
+    }
+
+    pub fn set_options(&mut self, options: Options) {
+        self.options = options;
+        let base = self.rom_info.as_ref();
+        let quirks = options.quirks(base.map_or_else(Quirks::default, |info| info.quirks));
+        let tickrate = options
+            .tickrate
+            .or(base.and_then(|info| info.tickrate))
+            .unwrap_or(DEFAULT_TICKRATE);
+        self.chip8
+            .set_quirks(quirks)
+            .set_tickrate(tickrate)
+            .set_timing(options.timing);
+    }
+
+    /// Starts the ROM over, keeping the options and cheats.
+    pub fn reset(&mut self) {
+        let rom = std::mem::take(&mut self.rom);
+        let cheat_codes = std::mem::take(&mut self.cheat_codes);
+        *self = Emulator::new(&rom, self.options).expect("the ROM loaded before");
+        self.cheat_codes = cheat_codes;
+        self.update_cheats();
+    }
+
+    /// Sets cheat `index` to `code`, several codes joined by `+` like `0x2F4 0+V3 1`.
+    pub fn set_cheat(
+        &mut self,
+        index: c_uint,
+        enabled: bool,
+        code: &str,
+    ) -> Result<(), CheatError> {
+        let cheats = code
+            .split('+')
+            .map(|code| {
+                let mut cheat = Cheat::from_code(code)?;
+                cheat.enabled = enabled;
+                Ok(cheat)
+            })
+            .collect::<Result<_, _>>()?;
+        self.cheat_codes.insert(index, cheats);
+        self.update_cheats();
+        Ok(())
+    }
+
+    pub fn reset_cheats(&mut self) {
+        self.cheat_codes.clear();
+        self.update_cheats();
+    }
+
+    fn update_cheats(&mut self) {
+        self.cheats.cheats = self.cheat_codes.values().flatten().cloned().collect();
+    }
+
+    /// Runs one frame. `pressed(port, device, id)` tells whether a button or key is down.
+    pub fn run_frame(&mut self, pressed: impl Fn(c_uint, c_uint, c_uint) -> bool) {
+        let mut keys = [false; 16];
+        for (port, buttons) in self.keymap.joypad.iter().enumerate() {
+            for (id, key) in buttons.iter().enumerate() {
+                if let Some(key) = key {
+                    keys[*key as usize] |=
+                        pressed(port as c_uint, RETRO_DEVICE_JOYPAD, id as c_uint);
+                }
+            }
+        }
+        for (keycode, key) in KEYBOARD {
+            keys[key as usize] |= pressed(0, RETRO_DEVICE_KEYBOARD, keycode);
+        }
+        for (key, down) in keys.iter().enumerate() {
+            self.chip8.keypress(key as u8, *down);
+        }
+
+        self.cheats.apply(&mut self.chip8);
+        self.chip8.run_frame();
+        self.beeper.fill(self.chip8.beeped(), &mut self.audio);
+        self.render();
+    }
+
+    fn render(&mut self) {
+        let [off, on] = self.palette;
+        self.frame.fill(off);
+        for (x, y) in self.chip8.get_display().lit_pixels() {
+            self.frame[y * SCREEN_WIDTH + x] = on;
+        }
+    }
+}
+
+/// `#rrggbb` as XRGB8888.
+fn parse_color(color: &str) -> Option<u32> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok()
+}
@@ -0,0 +1,357 @@
+//! The emulator as a libretro core, for RetroArch and other libretro frontends.
+//!
+//! Build it with `cargo build -p libretro --release` and load
+//! `target/release/liblibretro.so` (or `.dll` / `.dylib`) in the frontend.
+
+mod emulator;
+mod ffi;
+mod options;
+
+use emulator::Emulator;
+pub use emulator::{AUDIO_FRAMES, FPS, SAMPLE_RATE};
+pub use ffi::*;
+use options::{Options, VARIABLES};
+
+use core::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::ffi::{c_char, c_uint, c_void, CStr};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(Clone, Copy)]
+struct Callbacks {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+}
+
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+});
+
+/// The loaded game, if any.
+static EMULATOR: Mutex<Option<Emulator>> = Mutex::new(None);
+
+// Copied out, so no lock is held while the frontend runs
+fn callbacks() -> Callbacks {
+    *CALLBACKS.lock().unwrap()
+}
+
+fn callbacks_mut() -> MutexGuard<'static, Callbacks> {
+    CALLBACKS.lock().unwrap()
+}
+
+fn emulator() -> MutexGuard<'static, Option<Emulator>> {
+    EMULATOR.lock().unwrap()
+}
+
+/// Runs `f` on the loaded game, if any. An emulator panic, like an unknown opcode, must not
+/// unwind into the frontend: the game is unloaded and the frontend asked to shut the core down.
+fn with_emulator(f: impl FnOnce(&mut Emulator)) {
+    let mut emulator = emulator();
+    let Some(loaded) = emulator.as_mut() else {
+        return;
+    };
+    if panic::catch_unwind(AssertUnwindSafe(|| f(loaded))).is_err() {
+        *emulator = None;
+        drop(emulator);
+        environment(RETRO_ENVIRONMENT_SHUTDOWN, ptr::null_mut());
+    }
+}
+
+fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match callbacks().environment {
+        Some(environment) => unsafe { environment(cmd, data) },
+        None => false,
+    }
+}
+
+fn read_options() -> Options {
+    Options::read(|key| {
+        let mut variable = RetroVariable {
+            key: key.as_ptr(),
+            value: ptr::null(),
+        };
+        let found = environment(
+            RETRO_ENVIRONMENT_GET_VARIABLE,
+            &mut variable as *mut _ as *mut c_void,
+        );
+        if !found || variable.value.is_null() {
+            return None;
+        }
+        let value = unsafe { CStr::from_ptr(variable.value) };
+        Some(value.to_string_lossy().into_owned())
+    })
+}
+
+fn options_changed() -> bool {
+    let mut updated = false;
+    environment(
+        RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE,
+        &mut updated as *mut bool as *mut c_void,
+    ) && updated
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(callback: EnvironmentFn) {
+    callbacks_mut().environment = Some(callback);
+
+    let mut variables: Vec<RetroVariable> = VARIABLES
+        .iter()
+        .map(|(key, value)| RetroVariable {
+            key: key.as_ptr(),
+            value: value.as_ptr(),
+        })
+        .collect();
+    variables.push(RetroVariable {
+        key: ptr::null(),
+        value: ptr::null(),
+    });
+    environment(
+        RETRO_ENVIRONMENT_SET_VARIABLES,
+        variables.as_mut_ptr() as *mut c_void,
+    );
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: VideoRefreshFn) {
+    callbacks_mut().video_refresh = Some(callback);
+}
+
+/// Unused, audio goes through the batch callback.
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: AudioSampleFn) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: AudioSampleBatchFn) {
+    callbacks_mut().audio_sample_batch = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: InputPollFn) {
+    callbacks_mut().input_poll = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: InputStateFn) {
+    callbacks_mut().input_state = Some(callback);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *emulator() = None;
+}
+
+/// # Safety
+///
+/// `info` must point to writable memory for a `RetroSystemInfo`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut RetroSystemInfo) {
+    *info = RetroSystemInfo {
+        library_name: c"CHIP-8".as_ptr(),
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast(),
+        valid_extensions: c"ch8|c8".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+///
+/// `info` must point to writable memory for a `RetroSystemAvInfo`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut RetroSystemAvInfo) {
+    *info = RetroSystemAvInfo {
+        geometry: RetroGameGeometry {
+            base_width: SCREEN_WIDTH as c_uint,
+            base_height: SCREEN_HEIGHT as c_uint,
+            max_width: SCREEN_WIDTH as c_uint,
+            max_height: SCREEN_HEIGHT as c_uint,
+            aspect_ratio: SCREEN_WIDTH as f32 / SCREEN_HEIGHT as f32,
+        },
+        timing: RetroSystemTiming {
+            fps: FPS as f64,
+            sample_rate: SAMPLE_RATE as f64,
+        },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    with_emulator(Emulator::reset);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let callbacks = callbacks();
+    if let Some(input_poll) = callbacks.input_poll {
+        unsafe { input_poll() };
+    }
+    let options = options_changed().then(read_options);
+
+    with_emulator(|emulator| {
+        if let Some(options) = options {
+            emulator.set_options(options);
+        }
+
+        emulator.run_frame(|port, device, id| match callbacks.input_state {
+            Some(input_state) => unsafe { input_state(port, device, 0, id) != 0 },
+            None => false,
+        });
+
+        if let Some(video_refresh) = callbacks.video_refresh {
+            let pitch = SCREEN_WIDTH * std::mem::size_of::<u32>();
+            unsafe {
+                video_refresh(
+                    emulator.frame.as_ptr().cast(),
+                    SCREEN_WIDTH as c_uint,
+                    SCREEN_HEIGHT as c_uint,
+                    pitch,
+                )
+            };
+        }
+        if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+            unsafe { audio_sample_batch(emulator.audio.as_ptr(), emulator.audio.len() / 2) };
+        }
+    });
+}
+
+/// # Safety
+///
+/// `game` must be null or point to a `RetroGameInfo` whose `data` holds `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const RetroGameInfo) -> bool {
+    let Some(game) = game.as_ref() else {
+        return false;
+    };
+    if game.data.is_null() {
+        return false;
+    }
+    let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+    if !environment(
+        RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+        &mut format as *mut c_uint as *mut c_void,
+    ) {
+        return false;
+    }
+
+    let rom = std::slice::from_raw_parts(game.data.cast::<u8>(), game.size);
+    match Emulator::new(rom, read_options()) {
+        Ok(loaded) => {
+            *emulator() = Some(loaded);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const RetroGameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    *emulator() = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    emulator()
+        .as_ref()
+        .map_or(0, |emulator| emulator.chip8.save_state().len())
+}
+
+/// # Safety
+///
+/// `data` must point to `size` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let emulator = emulator();
+    let Some(emulator) = emulator.as_ref() else {
+        return false;
+    };
+    let state = emulator.chip8.save_state();
+    if data.is_null() || size < state.len() {
+        return false;
+    }
+    ptr::copy_nonoverlapping(state.as_ptr(), data.cast(), state.len());
+    true
+}
+
+/// # Safety
+///
+/// `data` must point to `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    let mut emulator = emulator();
+    let Some(emulator) = emulator.as_mut() else {
+        return false;
+    };
+    if data.is_null() {
+        return false;
+    }
+    let state = std::slice::from_raw_parts(data.cast::<u8>(), size);
+    emulator.chip8.load_state(state).is_ok()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {
+    if let Some(emulator) = emulator().as_mut() {
+        emulator.reset_cheats();
+    }
+}
+
+/// Codes look like `0x2F4 3` for memory or `VA 0xFF` for a register, joined by `+`.
+/// Invalid codes are ignored.
+///
+/// # Safety
+///
+/// `code` must be null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn retro_cheat_set(index: c_uint, enabled: bool, code: *const c_char) {
+    if code.is_null() {
+        return;
+    }
+    let code = CStr::from_ptr(code).to_string_lossy();
+    if let Some(emulator) = emulator().as_mut() {
+        let _ = emulator.set_cheat(index, enabled, &code);
+    }
+}
+
+/// Memory lives behind a lock, so it isn't exposed to the frontend directly.
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_id: c_uint) -> *mut c_void {
+    ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_id: c_uint) -> usize {
+    0
+}
@@ -0,0 +1,93 @@
+//! Core options, shown by the frontend in its menu.
+
+use core::timing::Timing;
+use core::Quirks;
+use std::ffi::CStr;
+
+/// Keys and `Description; default|other values` as the frontend expects them.
+pub const VARIABLES: [(&CStr, &CStr); 9] = [
+    (
+        c"chip8_tickrate",
+        c"Instructions per frame; auto|5|10|15|20|30|50|100|200|500|1000",
+    ),
+    (c"chip8_timing", c"Timing; fixed|cosmac-vip"),
+    (c"chip8_quirk_shift", c"Shift quirk; auto|enabled|disabled"),
+    (
+        c"chip8_quirk_memory_increment_by_x",
+        c"Load/store increments I by X; auto|enabled|disabled",
+    ),
+    (
+        c"chip8_quirk_memory_leave_i_unchanged",
+        c"Load/store leaves I unchanged; auto|enabled|disabled",
+    ),
+    (c"chip8_quirk_wrap", c"Wrap sprites; auto|enabled|disabled"),
+    (c"chip8_quirk_jump", c"Jump quirk; auto|enabled|disabled"),
+    (
+        c"chip8_quirk_vblank",
+        c"Wait for vblank; auto|enabled|disabled",
+    ),
+    (
+        c"chip8_quirk_logic",
+        c"Logic ops reset VF; auto|enabled|disabled",
+    ),
+];
+
+/// The frontend's choices. `None` means `auto`: whatever the ROM database suggests,
+/// or the emulator's default for unknown ROMs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Options {
+    pub tickrate: Option<u32>,
+    pub timing: Timing,
+    /// In the order of `VARIABLES`.
+    pub quirks: [Option<bool>; 7],
+}
+
+impl Options {
+    /// Builds the options from `get`, which returns the value of a variable by key.
+    /// Unknown values are treated as the default.
+    pub fn read(mut get: impl FnMut(&CStr) -> Option<String>) -> Self {
+        let mut options = Options::default();
+        for (key, _) in VARIABLES {
+            let Some(value) = get(key) else {
+                continue;
+            };
+            match key.to_bytes() {
+                b"chip8_tickrate" => options.tickrate = value.parse().ok(),
+                b"chip8_timing" => {
+                    options.timing = match value.as_str() {
+                        "cosmac-vip" => Timing::CosmacVip,
+                        _ => Timing::Fixed,
+                    }
+                }
+                quirk => {
+                    let index = VARIABLES[2..]
+                        .iter()
+                        .position(|(key, _)| key.to_bytes() == quirk)
+                        .expect("every other variable is a quirk");
+                    options.quirks[index] = match value.as_str() {
+                        "enabled" => Some(true),
+                        "disabled" => Some(false),
+                        _ => None,
+                    };
+                }
+            }
+        }
+        options
+    }
+
+    /// `base` with the quirks that aren't on `auto` overridden.
+    pub fn quirks(&self, base: Quirks) -> Quirks {
+        let [shift, memory_increment_by_x, memory_leave_i_unchanged, wrap, jump, vblank, logic] =
+            self.quirks;
+        Quirks {
+            shift: shift.unwrap_or(base.shift),
+            memory_increment_by_x: memory_increment_by_x.unwrap_or(base.memory_increment_by_x),
+            memory_leave_i_unchanged: memory_leave_i_unchanged
+                .unwrap_or(base.memory_leave_i_unchanged),
+            wrap: wrap.unwrap_or(base.wrap),
+            jump: jump.unwrap_or(base.jump),
+            vblank: vblank.unwrap_or(base.vblank),
+            logic: logic.unwrap_or(base.logic),
+        }
+    }
+}
@@ -0,0 +1,324 @@
+//! A tiny libretro frontend driving the core through its C entry points.
+
+use core::chip8::Chip8;
+use libretro::*;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::{c_uint, c_void, CStr, CString};
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+#[derive(Default)]
+struct Host {
+    pixel_format: Option<c_uint>,
+    declared_options: Vec<String>,
+    options: BTreeMap<CString, CString>,
+    options_updated: bool,
+    shutdown: bool,
+    pressed: HashSet<(c_uint, c_uint, c_uint)>,
+    frames: usize,
+    frame: Vec<u32>,
+    audio: Vec<i16>,
+}
+
+static HOST: Mutex<Option<Host>> = Mutex::new(None);
+// The core is a global, so tests take turns
+static TURN: Mutex<()> = Mutex::new(());
+
+fn host() -> MutexGuard<'static, Option<Host>> {
+    HOST.lock().unwrap()
+}
+
+fn with_host<T>(f: impl FnOnce(&mut Host) -> T) -> T {
+    f(host().as_mut().expect("host is set up"))
+}
+
+unsafe extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    with_host(|host| match cmd {
+        RETRO_ENVIRONMENT_SET_PIXEL_FORMAT => {
+            host.pixel_format = Some(*(data as *const c_uint));
+            true
+        }
+        RETRO_ENVIRONMENT_SET_VARIABLES => {
+            let mut variable = data as *const RetroVariable;
+            while !(*variable).key.is_null() {
+                let key = CStr::from_ptr((*variable).key);
+                host.declared_options
+                    .push(key.to_string_lossy().into_owned());
+                variable = variable.add(1);
+            }
+            true
+        }
+        RETRO_ENVIRONMENT_GET_VARIABLE => {
+            let variable = &mut *(data as *mut RetroVariable);
+            let key = CStr::from_ptr(variable.key);
+            match host.options.get(key) {
+                Some(value) => {
+                    variable.value = value.as_ptr();
+                    true
+                }
+                None => false,
+            }
+        }
+        RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE => {
+            *(data as *mut bool) = std::mem::take(&mut host.options_updated);
+            true
+        }
+        RETRO_ENVIRONMENT_SHUTDOWN => {
+            host.shutdown = true;
+            true
+        }
+        _ => false,
+    })
+}
+
+unsafe extern "C" fn video_refresh(
+    data: *const c_void,
+    width: c_uint,
+    height: c_uint,
+    pitch: usize,
+) {
+    assert_eq!((width, height, pitch), (64, 32, 64 * 4));
+    let pixels = std::slice::from_raw_parts(data as *const u32, (width * height) as usize);
+    with_host(|host| {
+        host.frames += 1;
+        host.frame = pixels.to_vec();
+    });
+}
+
+unsafe extern "C" fn audio_sample_batch(data: *const i16, frames: usize) -> usize {
+    let samples = std::slice::from_raw_parts(data, frames * 2);
+    with_host(|host| host.audio.extend_from_slice(samples));
+    frames
+}
+
+unsafe extern "C" fn input_poll() {}
+
+unsafe extern "C" fn input_state(port: c_uint, device: c_uint, _index: c_uint, id: c_uint) -> i16 {
+    with_host(|host| host.pressed.contains(&(port, device, id)) as i16)
+}
+
+/// Sets up a fresh host and core, and loads `rom`.
+fn start(rom: &[u8], options: &[(&str, &str)]) -> MutexGuard<'static, ()> {
+    let turn = TURN.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let mut host_state = Host::default();
+    for (key, value) in options {
+        host_state
+            .options
+            .insert(CString::new(*key).unwrap(), CString::new(*value).unwrap());
+    }
+    *host() = Some(host_state);
+
+    retro_set_environment(environment);
+    retro_set_video_refresh(video_refresh);
+    retro_set_audio_sample_batch(audio_sample_batch);
+    retro_set_input_poll(input_poll);
+    retro_set_input_state(input_state);
+    retro_init();
+
+    let game = RetroGameInfo {
+        path: std::ptr::null(),
+        data: rom.as_ptr().cast(),
+        size: rom.len(),
+        meta: std::ptr::null(),
+    };
+    assert!(unsafe { retro_load_game(&game) });
+    turn
+}
+
+fn stop() {
+    retro_unload_game();
+    retro_deinit();
+}
+
+fn run(frames: usize) {
+    for _ in 0..frames {
+        retro_run();
+    }
+}
+
+fn save_state() -> Vec<u8> {
+    let mut state = vec![0; retro_serialize_size()];
+    assert!(unsafe { retro_serialize(state.as_mut_ptr().cast(), state.len()) });
+    state
+}
+
+fn pong() -> Vec<u8> {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..");
+    std::fs::read(root.join("roms/pong.ch8")).expect("bundled ROM exists")
+}
+
+#[test]
+fn describes_itself() {
+    assert_eq!(retro_api_version(), 1);
+
+    let mut info = std::mem::MaybeUninit::<RetroSystemInfo>::uninit();
+    let info = unsafe {
+        retro_get_system_info(info.as_mut_ptr());
+        info.assume_init()
+    };
+    let name = unsafe { CStr::from_ptr(info.library_name) };
+    let extensions = unsafe { CStr::from_ptr(info.valid_extensions) };
+    assert_eq!(name.to_str(), Ok("CHIP-8"));
+    assert_eq!(extensions.to_str(), Ok("ch8|c8"));
+    assert!(!info.need_fullpath);
+
+    let mut av = std::mem::MaybeUninit::<RetroSystemAvInfo>::uninit();
+    let av = unsafe {
+        retro_get_system_av_info(av.as_mut_ptr());
+        av.assume_init()
+    };
+    assert_eq!((av.geometry.base_width, av.geometry.base_height), (64, 32));
+    assert_eq!(av.timing.fps, FPS as f64);
+    assert_eq!(av.timing.sample_rate, SAMPLE_RATE as f64);
+}
+
+#[test]
+fn runs_a_game() {
+    let _turn = start(&pong(), &[]);
+    run(60);
+    with_host(|host| {
+        assert_eq!(host.pixel_format, Some(RETRO_PIXEL_FORMAT_XRGB8888));
+        assert!(host
+            .declared_options
+            .contains(&"chip8_tickrate".to_string()));
+        assert_eq!(host.frames, 60);
+        assert!(host.frame.contains(&0x00FF_FFFF));
+        assert_eq!(host.audio.len(), 60 * AUDIO_FRAMES * 2);
+    });
+    stop();
+}
+
+#[test]
+fn joypad_presses_hex_keys() {
+    // Wait for a key, then draw its digit at X = key
+    let rom = [0xF0, 0x0A, 0xF0, 0x29, 0xD0, 0x15, 0x12, 0x06];
+    let _turn = start(&rom, &[]);
+    with_host(|host| {
+        host.pressed
+            .insert((0, RETRO_DEVICE_JOYPAD, RETRO_DEVICE_ID_JOYPAD_RIGHT));
+    });
+    run(2);
+
+    let mut expected = Chip8::new();
+    expected.load(&rom).unwrap();
+    expected.keypress(6, true);
+    expected.run_frame();
+    expected.run_frame();
+    let expected: Vec<_> = expected.get_display().lit_pixels().collect();
+    assert!(!expected.is_empty());
+
+    with_host(|host| {
+        let lit: Vec<_> = (0..32)
+            .flat_map(|y| (0..64).map(move |x| (x, y)))
+            .filter(|(x, y)| host.frame[y * 64 + x] != 0)
+            .collect();
+        assert_eq!(lit, expected);
+    });
+    stop();
+}
+
+#[test]
+fn sound_timer_beeps() {
+    // VF = 30, sound timer = VF, then loop
+    let rom = [0x6F, 0x1E, 0xFF, 0x18, 0x12, 0x04];
+    let _turn = start(&rom, &[]);
+    run(1);
+    assert!(with_host(|host| host
+        .audio
+        .iter()
+        .any(|sample| *sample != 0)));
+
+    run(40);
+    with_host(|host| {
+        let last = &host.audio[host.audio.len() - AUDIO_FRAMES * 2..];
+        assert!(last.iter().all(|sample| *sample == 0));
+    });
+    stop();
+}
+
+#[test]
+fn save_states_round_trip() {
+    let _turn = start(&pong(), &[]);
+    run(30);
+    let state = save_state();
+    run(30);
+    let expected = with_host(|host| host.frame.clone());
+
+    assert!(unsafe { retro_unserialize(state.as_ptr().cast(), state.len()) });
+    run(30);
+    assert_eq!(with_host(|host| host.frame.clone()), expected);
+
+    assert!(!unsafe { retro_unserialize(b"junk".as_ptr().cast(), 4) });
+    stop();
+}
+
+#[test]
+fn options_set_speed_and_quirks() {
+    // V0 counts instructions, two per loop
+    let rom = [0x70, 0x01, 0x12, 0x00];
+    let _turn = start(&rom, &[("chip8_tickrate", "100")]);
+    run(1);
+
+    let mut chip8 = Chip8::new();
+    chip8.load_state(&save_state()).unwrap();
+    assert_eq!(chip8.register(0), 50);
+    assert!(chip8.quirks().wrap);
+
+    with_host(|host| {
+        for (key, value) in [("chip8_tickrate", "20"), ("chip8_quirk_wrap", "disabled")] {
+            host.options
+                .insert(CString::new(key).unwrap(), CString::new(value).unwrap());
+        }
+        host.options_updated = true;
+    });
+    run(1);
+
+    chip8.load_state(&save_state()).unwrap();
+    assert_eq!(chip8.register(0), 60);
+    assert!(!chip8.quirks().wrap);
+    stop();
+}
+
+#[test]
+fn cheats_freeze_values() {
+    // V0 counts loops and is stored at 0x300
+    let rom = [0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00];
+    let _turn = start(&rom, &[]);
+    unsafe {
+        retro_cheat_set(0, true, c"0x301 7+V0 0x10".as_ptr());
+        retro_cheat_set(1, true, c"not a cheat".as_ptr());
+        retro_cheat_set(2, false, c"0x302 1".as_ptr());
+    }
+    run(1);
+
+    let mut chip8 = Chip8::new();
+    chip8.load_state(&save_state()).unwrap();
+    assert_eq!(chip8.bus().read(0x301), 7);
+    assert_eq!(chip8.bus().read(0x302), 0);
+    // Ten instructions from V0 = 0x10, so three increments
+    assert_eq!(chip8.register(0), 0x13);
+
+    retro_cheat_reset();
+    run(1);
+    chip8.load_state(&save_state()).unwrap();
+    assert_eq!(chip8.register(0), 0x13 + 2);
+    stop();
+}
+
+#[test]
+fn emulator_panics_stop_the_core_instead_of_unwinding() {
+    // LD V0, 1, then 5XY1, which isn't an instruction
+    let rom = [0x60, 0x01, 0x50, 0x11];
+    let _turn = start(&rom, &[]);
+    run(1);
+    with_host(|host| {
+        assert!(host.shutdown);
+        assert_eq!(host.frames, 0);
+    });
+    // The game is gone, later calls do nothing
+    assert_eq!(retro_serialize_size(), 0);
+    run(1);
+    retro_reset();
+    assert_eq!(with_host(|host| host.frames), 0);
+    stop();
+}
@@ -0,0 +1,407 @@
+//! Reinforcement learning environments, without any Python.
+
+use emulator::chip8::{Chip8, Engine, LoadError, RunError};
+use emulator::database::{RomDatabase, RomInfo};
+use emulator::{Ram, SCREEN_HEIGHT, SCREEN_WIDTH};
+use std::fmt;
+
+/// Bytes in an observation, one per pixel, row by row.
+pub const OBSERVATION_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
+
+/// Returned by custom extractors, e.g. the exception raised by a Python callable.
+pub type ExtractorError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A custom reward extractor: the reward for the frame that just ran, given the RAM.
+pub type RewardFn = Box<dyn FnMut(&Ram) -> Result<f64, ExtractorError> + Send + Sync>;
+/// A custom termination extractor: whether the episode is over, given the RAM.
+pub type DoneFn = Box<dyn FnMut(&Ram) -> Result<bool, ExtractorError> + Send + Sync>;
+
+/// How a game keeps a number in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    #[default]
+    U8,
+    /// Big-endian, like CHIP-8 addresses.
+    U16,
+    /// Hundreds, tens and ones in three bytes, as written by Fx33.
+    Bcd,
+}
+
+/// A number at a fixed place in RAM, like a score or a lives counter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RamValue {
+    pub address: u16,
+    pub encoding: Encoding,
+}
+
+impl RamValue {
+    pub fn new(address: u16, encoding: Encoding) -> Self {
+        RamValue { address, encoding }
+    }
+
+    pub fn read(&self, ram: &Ram) -> u32 {
+        // Wraps around the end of memory like the interpreter's own reads
+        let byte = |offset: u16| ram.read(self.address.wrapping_add(offset) & 0xFFF) as u32;
+        match self.encoding {
+            Encoding::U8 => byte(0),
+            Encoding::U16 => byte(0) << 8 | byte(1),
+            Encoding::Bcd => byte(0) * 100 + byte(1) * 10 + byte(2),
+        }
+    }
+}
+
+#[derive(Default)]
+pub enum Reward {
+    /// Always 0.
+    #[default]
+    None,
+    /// How much the value went up, like the points scored.
+    Delta(RamValue),
+    /// The value itself.
+    Value(RamValue),
+    Custom(RewardFn),
+}
+
+#[derive(Default)]
+pub enum Done {
+    /// Episodes never end by themselves.
+    #[default]
+    Never,
+    /// The episode ends once the value equals the given number, like 0 lives left.
+    Equals(RamValue, u32),
+    Custom(DoneFn),
+}
+
+pub struct EnvConfig {
+    /// Frames run by each step, with the action held down.
+    pub frameskip: u32,
+    /// Keys held down by each action.
+    pub actions: Vec<Vec<u8>>,
+    pub reward: Reward,
+    pub done: Done,
+    /// Overrides the speed from the ROM database.
+    pub tickrate: Option<u32>,
+}
+
+impl Default for EnvConfig {
+    /// One frame per step, and 17 actions: nothing, then each key from 0 to F.
+    fn default() -> Self {
+        EnvConfig {
+            frameskip: 1,
+            actions: default_actions(),
+            reward: Reward::None,
+            done: Done::Never,
+            tickrate: None,
+        }
+    }
+}
+
+pub fn default_actions() -> Vec<Vec<u8>> {
+    std::iter::once(Vec::new())
+        .chain((0..16).map(|key| vec![key]))
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum EnvError {
+    InvalidAction {
+        action: usize,
+        actions: usize,
+    },
+    InvalidKey(u8),
+    /// A `VecEnv` got a different number of actions than it has envs.
+    WrongActionCount {
+        given: usize,
+        expected: usize,
+    },
+    /// `step` was called after the episode ended, without a `reset`.
+    Finished,
+    Extractor(ExtractorError),
+    /// The game reached an instruction that can't run.
+    Run(RunError),
+    /// A `VecEnv` thread panicked, e.g. in a custom extractor.
+    Panicked,
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvError::InvalidAction { action, actions } => {
+                write!(f, "action {action} is out of range, there are {actions}")
+            }
+            EnvError::InvalidKey(key) => write!(f, "key {key:#x} is not a CHIP-8 key"),
+            EnvError::WrongActionCount { given, expected } => {
+                write!(f, "got {given} actions for {expected} environments")
+            }
+            EnvError::Finished => write!(f, "the episode is over, reset the environment"),
+            EnvError::Extractor(error) => write!(f, "extractor failed: {error}"),
+            EnvError::Run(error) => write!(f, "the game stopped: {error}"),
+            EnvError::Panicked => write!(f, "an environment thread panicked"),
+        }
+    }
+}
+
+impl std::error::Error for EnvError {}
+
+#[derive(Debug)]
+pub enum NewEnvError {
+    Load(LoadError),
+    Env(EnvError),
+}
+
+impl fmt::Display for NewEnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NewEnvError::Load(error) => write!(f, "{error}"),
+            NewEnvError::Env(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for NewEnvError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Step {
+    pub reward: f64,
+    pub done: bool,
+}
+
+/// One game, stepped a few frames at a time. Given the same seed and actions,
+/// every episode plays out the same.
+pub struct Env {
+    chip8: Chip8,
+    rom: Vec<u8>,
+    // Looked up once, the database is parsed on every `RomDatabase::embedded`
+    rom_info: Option<RomInfo>,
+    config: EnvConfig,
+    // Last value read by `Reward::Delta`
+    previous: u32,
+    done: bool,
+}
+
+impl Env {
+    /// Loads `rom` with the settings from the ROM database, and resets with seed 0.
+    pub fn new(rom: &[u8], config: EnvConfig) -> Result<Self, NewEnvError> {
+        for keys in &config.actions {
+            if let Some(key) = keys.iter().find(|key| **key > 0xF) {
+                return Err(NewEnvError::Env(EnvError::InvalidKey(*key)));
+            }
+        }
+        let mut chip8 = Chip8::with_engine(Engine::Threaded);
+        let rom_info = chip8
+            .load_known(rom, &RomDatabase::embedded())
+            .map_err(NewEnvError::Load)?;
+        let mut env = Env {
+            chip8,
+            rom: rom.to_vec(),
+            rom_info,
+            config,
+            previous: 0,
+            done: false,
+        };
+        env.reset(0);
+        Ok(env)
+    }
+
+    /// Starts a new episode, with Cxnn seeded by `seed`.
+    pub fn reset(&mut self, seed: u64) {
+        self.chip8
+            .load_info(&self.rom, self.rom_info.as_ref())
+            .expect("the ROM loaded before");
+        if let Some(tickrate) = self.config.tickrate {
+            self.chip8.set_tickrate(tickrate);
+        }
+        self.chip8.seed(seed);
+        if let Reward::Delta(value) = &self.config.reward {
+            self.previous = value.read(self.chip8.bus());
+        }
+        self.done = false;
+    }
+
+    /// Holds the keys of `action` for `frameskip` frames, and adds up the rewards.
+    /// Stops early if the episode ends.
+    pub fn step(&mut self, action: usize) -> Result<Step, EnvError> {
+        if self.done {
+            return Err(EnvError::Finished);
+        }
+        let keys = self
+            .config
+            .actions
+            .get(action)
+            .ok_or(EnvError::InvalidAction {
+                action,
+                actions: self.config.actions.len(),
+            })?;
+        for key in 0..16 {
+            self.chip8.keypress(key, keys.contains(&key));
+        }
+
+        let mut reward = 0.0;
+        for _ in 0..self.config.frameskip.max(1) {
+            self.chip8.try_run_frame().map_err(EnvError::Run)?;
+            reward += self.reward()?;
+            self.done = self.is_done()?;
+            if self.done {
+                break;
+            }
+        }
+        Ok(Step {
+            reward,
+            done: self.done,
+        })
+    }
+
+    fn reward(&mut self) -> Result<f64, EnvError> {
+        let ram = self.chip8.bus();
+        Ok(match &mut self.config.reward {
+            Reward::None => 0.0,
+            Reward::Delta(value) => {
+                let current = value.read(ram);
+                let delta = current as f64 - self.previous as f64;
+                self.previous = current;
+                delta
+            }
+            Reward::Value(value) => value.read(ram) as f64,
+            Reward::Custom(reward) => reward(ram).map_err(EnvError::Extractor)?,
+        })
+    }
+
+    fn is_done(&mut self) -> Result<bool, EnvError> {
+        let ram = self.chip8.bus();
+        Ok(match &mut self.config.done {
+            Done::Never => false,
+            Done::Equals(value, expected) => value.read(ram) == *expected,
+            Done::Custom(done) => done(ram).map_err(EnvError::Extractor)?,
+        })
+    }
+
+    /// Writes the screen to `out`, which holds `OBSERVATION_SIZE` bytes: 1 for lit pixels, 0 otherwise.
+    pub fn observe(&self, out: &mut [u8]) {
+        out.fill(0);
+        for (x, y) in self.chip8.get_display().lit_pixels() {
+            out[y * SCREEN_WIDTH + x] = 1;
+        }
+    }
+
+    pub fn observation(&self) -> Vec<u8> {
+        let mut out = vec![0; OBSERVATION_SIZE];
+        self.observe(&mut out);
+        out
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.done
+    }
+
+    pub fn num_actions(&self) -> usize {
+        self.config.actions.len()
+    }
+
+    pub fn chip8(&self) -> &Chip8 {
+        &self.chip8
+    }
+
+    /// What the ROM database knows about the game, if anything.
+    pub fn rom_info(&self) -> Option<&RomInfo> {
+        self.rom_info.as_ref()
+    }
+}
+
+/// Many copies of a game, stepped together on all cores.
+///
+/// Finished episodes start over by themselves: the step that ends one reports `done`
+/// along with the first screen of the next.
+pub struct VecEnv {
+    envs: Vec<Env>,
+    // Seed of the episode each env is playing
+    seeds: Vec<u64>,
+}
+
+impl VecEnv {
+    /// `config` is called once per env, since custom extractors can't be shared.
+    pub fn new(
+        rom: &[u8],
+        num_envs: usize,
+        mut config: impl FnMut() -> EnvConfig,
+    ) -> Result<Self, NewEnvError> {
+        let envs = (0..num_envs)
+            .map(|_| Env::new(rom, config()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut vec_env = VecEnv {
+            envs,
+            seeds: vec![0; num_envs],
+        };
+        vec_env.reset(0);
+        Ok(vec_env)
+    }
+
+    pub fn len(&self) -> usize {
+        self.envs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.envs.is_empty()
+    }
+
+    /// Env `i` is seeded with `seed + i`. Its later episodes take turns with the other envs,
+    /// with `seed + i + len`, `seed + i + 2 * len`, and so on.
+    pub fn reset(&mut self, seed: u64) {
+        for (i, (env, env_seed)) in self.envs.iter_mut().zip(&mut self.seeds).enumerate() {
+            *env_seed = seed.wrapping_add(i as u64);
+            env.reset(*env_seed);
+        }
+    }
+
+    /// Writes every screen to `out`, `OBSERVATION_SIZE` bytes per env.
+    pub fn observe(&self, out: &mut [u8]) {
+        for (env, out) in self.envs.iter().zip(out.chunks_mut(OBSERVATION_SIZE)) {
+            env.observe(out);
+        }
+    }
+
+    /// Steps env `i` with `actions[i]`.
+    pub fn step(&mut self, actions: &[usize]) -> Result<Vec<Step>, EnvError> {
+        if actions.len() != self.envs.len() {
+            return Err(EnvError::WrongActionCount {
+                given: actions.len(),
+                expected: self.envs.len(),
+            });
+        }
+        let stride = self.envs.len() as u64;
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk = self.envs.len().div_ceil(threads).max(1);
+
+        let results = std::thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .envs
+                .chunks_mut(chunk)
+                .zip(self.seeds.chunks_mut(chunk))
+                .zip(actions.chunks(chunk))
+                .map(|((envs, seeds), actions)| {
+                    scope.spawn(move || {
+                        let mut steps = Vec::with_capacity(envs.len());
+                        for ((env, seed), action) in envs.iter_mut().zip(seeds).zip(actions) {
+                            let step = env.step(*action)?;
+                            if step.done {
+                                *seed = seed.wrapping_add(stride);
+                                env.reset(*seed);
+                            }
+                            steps.push(step);
+                        }
+                        Ok(steps)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap_or(Err(EnvError::Panicked)))
+                .collect::<Vec<Result<Vec<Step>, EnvError>>>()
+        });
+
+        let mut steps = Vec::with_capacity(self.envs.len());
+        for result in results {
+            steps.extend(result?);
+        }
+        Ok(steps)
+    }
+}
@@ -0,0 +1,317 @@
+//! Python bindings: Gym-style environments for reinforcement learning.
+//!
+//! Build the module with `maturin develop --release` from this directory, then
+//! `import chip8`.
+
+pub mod env;
+
+use env::{Done, Encoding, EnvConfig, EnvError, NewEnvError, RamValue, Reward, OBSERVATION_SIZE};
+use numpy::ndarray::{Array1, Array2, Array3};
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyArray3};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use emulator::{SCREEN_HEIGHT, SCREEN_WIDTH};
+
+fn env_error(error: EnvError) -> PyErr {
+    match error {
+        // Hands back the exception raised by the Python callable
+        EnvError::Extractor(error) => match error.downcast::<PyErr>() {
+            Ok(error) => *error,
+            Err(error) => PyRuntimeError::new_err(error.to_string()),
+        },
+        error @ (EnvError::Finished | EnvError::Run(_) | EnvError::Panicked) => {
+            PyRuntimeError::new_err(error.to_string())
+        }
+        error => PyValueError::new_err(error.to_string()),
+    }
+}
+
+fn new_env_error(error: NewEnvError) -> PyErr {
+    match error {
+        NewEnvError::Load(error) => PyValueError::new_err(error.to_string()),
+        NewEnvError::Env(error) => env_error(error),
+    }
+}
+
+/// A number in RAM, read as `"u8"`, `"u16"` (big-endian) or `"bcd"` (three digits, as Fx33 writes them).
+#[pyclass(name = "RamValue", frozen, from_py_object)]
+#[derive(Clone, Copy)]
+struct PyRamValue(RamValue);
+
+#[pymethods]
+impl PyRamValue {
+    #[new]
+    #[pyo3(signature = (address, encoding = "u8"))]
+    fn new(address: u16, encoding: &str) -> PyResult<Self> {
+        if address as usize >= emulator::MEMORY_SIZE {
+            return Err(PyValueError::new_err(format!(
+                "address {address:#x} is outside of memory"
+            )));
+        }
+        let encoding = match encoding {
+            "u8" => Encoding::U8,
+            "u16" => Encoding::U16,
+            "bcd" => Encoding::Bcd,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "unknown encoding {encoding:?}, expected \"u8\", \"u16\" or \"bcd\""
+                )))
+            }
+        };
+        Ok(PyRamValue(RamValue::new(address, encoding)))
+    }
+
+    #[getter]
+    fn address(&self) -> u16 {
+        self.0.address
+    }
+
+    fn __repr__(&self) -> String {
+        let encoding = match self.0.encoding {
+            Encoding::U8 => "u8",
+            Encoding::U16 => "u16",
+            Encoding::Bcd => "bcd",
+        };
+        format!("RamValue({:#05x}, {encoding:?})", self.0.address)
+    }
+}
+
+/// The keyword arguments shared by `Env` and `VecEnv`.
+struct Settings {
+    frameskip: u32,
+    actions: Option<Vec<Vec<u8>>>,
+    reward: Option<Py<PyAny>>,
+    reward_mode: String,
+    done: Option<Py<PyAny>>,
+    done_value: u32,
+    tickrate: Option<u32>,
+}
+
+impl Settings {
+    /// `reward` and `done` are a `RamValue` or a callable that gets the RAM as `bytes`.
+    fn config(&self, py: Python<'_>) -> PyResult<EnvConfig> {
+        let reward = match &self.reward {
+            None => Reward::None,
+            Some(reward) => match reward.extract::<PyRamValue>(py) {
+                Ok(PyRamValue(value)) => match self.reward_mode.as_str() {
+                    "delta" => Reward::Delta(value),
+                    "value" => Reward::Value(value),
+                    mode => {
+                        return Err(PyValueError::new_err(format!(
+                            "unknown reward mode {mode:?}, expected \"delta\" or \"value\""
+                        )))
+                    }
+                },
+                Err(_) => {
+                    let reward = callable(py, reward)?;
+                    Reward::Custom(Box::new(move |ram| {
+                        Python::attach(|py| {
+                            let ram = PyBytes::new(py, ram.as_slice());
+                            Ok(reward.call1(py, (ram,))?.extract::<f64>(py)?)
+                        })
+                    }))
+                }
+            },
+        };
+        let done = match &self.done {
+            None => Done::Never,
+            Some(done) => match done.extract::<PyRamValue>(py) {
+                Ok(PyRamValue(value)) => Done::Equals(value, self.done_value),
+                Err(_) => {
+                    let done = callable(py, done)?;
+                    Done::Custom(Box::new(move |ram| {
+                        Python::attach(|py| {
+                            let ram = PyBytes::new(py, ram.as_slice());
+                            Ok(done.call1(py, (ram,))?.bind(py).is_truthy()?)
+                        })
+                    }))
+                }
+            },
+        };
+        Ok(EnvConfig {
+            frameskip: self.frameskip,
+            actions: self.actions.clone().unwrap_or_else(env::default_actions),
+            reward,
+            done,
+            tickrate: self.tickrate,
+        })
+    }
+}
+
+fn callable(py: Python<'_>, object: &Py<PyAny>) -> PyResult<Py<PyAny>> {
+    if object.bind(py).is_callable() {
+        Ok(object.clone_ref(py))
+    } else {
+        Err(PyValueError::new_err("expected a RamValue or a callable"))
+    }
+}
+
+fn screen(py: Python<'_>, pixels: Vec<u8>) -> Bound<'_, PyArray2<u8>> {
+    Array2::from_shape_vec((SCREEN_HEIGHT, SCREEN_WIDTH), pixels)
+        .expect("a whole screen")
+        .into_pyarray(py)
+}
+
+/// A CHIP-8 game as a reinforcement learning environment.
+///
+/// `step(action)` holds the keys of `actions[action]` for `frameskip` frames and returns
+/// `(screen, reward, done)`, where the screen is a `(32, 64)` uint8 array of 0s and 1s.
+/// By default there are 17 actions: nothing, then each key from 0 to F.
+///
+/// `reward` is a `RamValue`, rewarding how much it went up (`reward_mode="delta"`) or
+/// the value itself (`"value"`), or a callable taking the RAM as `bytes`. `done` is a
+/// `RamValue`, ending the episode when it equals `done_value`, or such a callable.
+#[pyclass(name = "Env")]
+struct PyEnv(env::Env);
+
+#[pymethods]
+impl PyEnv {
+    #[new]
+    #[pyo3(signature = (rom, *, frameskip = 1, actions = None, reward = None, reward_mode = "delta".to_string(), done = None, done_value = 0, tickrate = None))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python<'_>,
+        rom: &[u8],
+        frameskip: u32,
+        actions: Option<Vec<Vec<u8>>>,
+        reward: Option<Py<PyAny>>,
+        reward_mode: String,
+        done: Option<Py<PyAny>>,
+        done_value: u32,
+        tickrate: Option<u32>,
+    ) -> PyResult<Self> {
+        let settings = Settings {
+            frameskip,
+            actions,
+            reward,
+            reward_mode,
+            done,
+            done_value,
+            tickrate,
+        };
+        let env = env::Env::new(rom, settings.config(py)?).map_err(new_env_error)?;
+        Ok(PyEnv(env))
+    }
+
+    /// Starts a new episode and returns the first screen.
+    #[pyo3(signature = (seed = 0))]
+    fn reset<'py>(&mut self, py: Python<'py>, seed: u64) -> Bound<'py, PyArray2<u8>> {
+        self.0.reset(seed);
+        screen(py, self.0.observation())
+    }
+
+    fn step<'py>(
+        &mut self,
+        py: Python<'py>,
+        action: usize,
+    ) -> PyResult<(Bound<'py, PyArray2<u8>>, f64, bool)> {
+        let step = self.0.step(action).map_err(env_error)?;
+        Ok((screen(py, self.0.observation()), step.reward, step.done))
+    }
+
+    /// A copy of the 4 KiB of memory.
+    fn ram<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.0.chip8().bus().as_slice())
+    }
+
+    #[getter]
+    fn num_actions(&self) -> usize {
+        self.0.num_actions()
+    }
+}
+
+/// `num_envs` copies of a game, stepped together on all cores without holding the GIL.
+///
+/// Takes the same keyword arguments as `Env`. `reset(seed)` seeds env `i` with
+/// `seed + i`. `step(actions)` returns `(screens, rewards, dones)` with shapes
+/// `(num_envs, 32, 64)`, `(num_envs,)` and `(num_envs,)`. Finished episodes start over
+/// by themselves, so a screen next to `done` is the first one of the next episode.
+#[pyclass(name = "VecEnv")]
+struct PyVecEnv(env::VecEnv);
+
+#[pymethods]
+impl PyVecEnv {
+    #[new]
+    #[pyo3(signature = (rom, num_envs, *, frameskip = 1, actions = None, reward = None, reward_mode = "delta".to_string(), done = None, done_value = 0, tickrate = None))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python<'_>,
+        rom: &[u8],
+        num_envs: usize,
+        frameskip: u32,
+        actions: Option<Vec<Vec<u8>>>,
+        reward: Option<Py<PyAny>>,
+        reward_mode: String,
+        done: Option<Py<PyAny>>,
+        done_value: u32,
+        tickrate: Option<u32>,
+    ) -> PyResult<Self> {
+        let settings = Settings {
+            frameskip,
+            actions,
+            reward,
+            reward_mode,
+            done,
+            done_value,
+            tickrate,
+        };
+        // Checked once up front, so building the envs can't fail halfway
+        settings.config(py)?;
+        let vec_env = env::VecEnv::new(rom, num_envs, || {
+            settings.config(py).expect("checked above")
+        })
+        .map_err(new_env_error)?;
+        Ok(PyVecEnv(vec_env))
+    }
+
+    #[pyo3(signature = (seed = 0))]
+    fn reset<'py>(&mut self, py: Python<'py>, seed: u64) -> Bound<'py, PyArray3<u8>> {
+        self.0.reset(seed);
+        self.screens(py)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn step<'py>(
+        &mut self,
+        py: Python<'py>,
+        actions: Vec<usize>,
+    ) -> PyResult<(
+        Bound<'py, PyArray3<u8>>,
+        Bound<'py, PyArray1<f64>>,
+        Bound<'py, PyArray1<bool>>,
+    )> {
+        let vec_env = &mut self.0;
+        let steps = py.detach(|| vec_env.step(&actions)).map_err(env_error)?;
+        let rewards: Array1<f64> = steps.iter().map(|step| step.reward).collect();
+        let dones: Array1<bool> = steps.iter().map(|step| step.done).collect();
+        Ok((
+            self.screens(py),
+            rewards.into_pyarray(py),
+            dones.into_pyarray(py),
+        ))
+    }
+
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl PyVecEnv {
+    fn screens<'py>(&self, py: Python<'py>) -> Bound<'py, PyArray3<u8>> {
+        let mut pixels = vec![0; self.0.len() * OBSERVATION_SIZE];
+        self.0.observe(&mut pixels);
+        Array3::from_shape_vec((self.0.len(), SCREEN_HEIGHT, SCREEN_WIDTH), pixels)
+            .expect("whole screens")
+            .into_pyarray(py)
+    }
+}
+
+#[pymodule]
+fn chip8(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyRamValue>()?;
+    module.add_class::<PyEnv>()?;
+    module.add_class::<PyVecEnv>()?;
+    Ok(())
+}
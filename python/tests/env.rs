@@ -0,0 +1,196 @@
+use chip8::env::{
+    Done, Encoding, Env, EnvConfig, EnvError, RamValue, Reward, VecEnv, OBSERVATION_SIZE,
+};
+use emulator::chip8::{RunError, DEFAULT_TICKRATE};
+use emulator::opcode::UnknownOpcodeError;
+use std::path::PathBuf;
+
+// Counts loops at 0x300: V0 += 1, I = 0x300, store V0, jump back. Four instructions a loop.
+const COUNTER: [u8; 8] = [0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00];
+// Draws the 0 glyph at random places forever
+const RANDOM_DOTS: [u8; 10] = [0xC0, 0x3F, 0xC1, 0x1F, 0xA0, 0x00, 0xD0, 0x15, 0x12, 0x00];
+// Stores the key pressed at 0x300, then loops
+const KEY_TO_RAM: [u8; 10] = [0xF0, 0x0A, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x00, 0x00, 0x00];
+
+const COUNT: RamValue = RamValue {
+    address: 0x300,
+    encoding: Encoding::U8,
+};
+
+fn counter(frameskip: u32, done_at: u32) -> EnvConfig {
+    EnvConfig {
+        frameskip,
+        reward: Reward::Delta(COUNT),
+        done: Done::Equals(COUNT, done_at),
+        // Two loops a frame
+        tickrate: Some(8),
+        ..EnvConfig::default()
+    }
+}
+
+fn play(env: &mut Env, seed: u64, steps: usize) -> Vec<Vec<u8>> {
+    env.reset(seed);
+    (0..steps)
+        .map(|step| {
+            env.step(step % env.num_actions()).unwrap();
+            env.observation()
+        })
+        .collect()
+}
+
+#[test]
+fn rewards_add_up_over_skipped_frames() {
+    let mut env = Env::new(&COUNTER, counter(3, 20)).unwrap();
+    let step = env.step(0).unwrap();
+    assert_eq!(step.reward, 6.0);
+    assert!(!step.done);
+    assert_eq!(COUNT.read(env.chip8().bus()), 6);
+
+    // 12, 18, then stops at 20 without finishing the frameskip
+    env.step(0).unwrap();
+    env.step(0).unwrap();
+    let step = env.step(0).unwrap();
+    assert_eq!(step.reward, 2.0);
+    assert!(step.done);
+    assert!(matches!(env.step(0), Err(EnvError::Finished)));
+
+    env.reset(0);
+    assert_eq!(env.step(0).unwrap().reward, 6.0);
+}
+
+#[test]
+fn custom_extractors_see_ram() {
+    let config = EnvConfig {
+        reward: Reward::Custom(Box::new(|ram| Ok(ram.read(0x300) as f64 / 2.0))),
+        done: Done::Custom(Box::new(|ram| Ok(ram.read(0x300) >= 4))),
+        tickrate: Some(8),
+        ..EnvConfig::default()
+    };
+    let mut env = Env::new(&COUNTER, config).unwrap();
+    assert_eq!(env.step(0).unwrap().reward, 1.0);
+    let step = env.step(0).unwrap();
+    assert_eq!(step.reward, 2.0);
+    assert!(step.done);
+
+    let config = EnvConfig {
+        done: Done::Custom(Box::new(|_| Err("no".into()))),
+        ..EnvConfig::default()
+    };
+    let mut env = Env::new(&COUNTER, config).unwrap();
+    assert!(matches!(env.step(0), Err(EnvError::Extractor(_))));
+}
+
+#[test]
+fn actions_hold_keys() {
+    let config = EnvConfig {
+        actions: vec![vec![], vec![0x7], vec![0x3, 0xC]],
+        ..EnvConfig::default()
+    };
+    let mut env = Env::new(&KEY_TO_RAM, config).unwrap();
+    // Fx0A waits for the key to be released
+    env.step(1).unwrap();
+    env.step(0).unwrap();
+    assert_eq!(env.chip8().bus().read(0x300), 0x7);
+
+    assert!(matches!(
+        env.step(3),
+        Err(EnvError::InvalidAction {
+            action: 3,
+            actions: 3
+        })
+    ));
+    let config = EnvConfig {
+        actions: vec![vec![0x10]],
+        ..EnvConfig::default()
+    };
+    assert!(Env::new(&KEY_TO_RAM, config).is_err());
+}
+
+#[test]
+fn steps_are_deterministic_given_the_seed() {
+    let mut env = Env::new(&RANDOM_DOTS, EnvConfig::default()).unwrap();
+    let first = play(&mut env, 7, 20);
+    assert_eq!(play(&mut env, 7, 20), first);
+
+    let mut other = Env::new(&RANDOM_DOTS, EnvConfig::default()).unwrap();
+    assert_eq!(play(&mut other, 7, 20), first);
+    assert_ne!(play(&mut other, 8, 20), first);
+}
+
+#[test]
+fn vec_env_matches_single_envs_and_resets_finished_ones() {
+    let mut vec_env = VecEnv::new(&RANDOM_DOTS, 3, EnvConfig::default).unwrap();
+    vec_env.reset(10);
+    let mut screens = vec![0; 3 * OBSERVATION_SIZE];
+    for _ in 0..5 {
+        vec_env.step(&[0, 1, 2]).unwrap();
+    }
+    vec_env.observe(&mut screens);
+
+    for (i, screen) in screens.chunks(OBSERVATION_SIZE).enumerate() {
+        let mut env = Env::new(&RANDOM_DOTS, EnvConfig::default()).unwrap();
+        env.reset(10 + i as u64);
+        for _ in 0..5 {
+            env.step(i).unwrap();
+        }
+        assert_eq!(env.observation(), screen);
+    }
+    assert!(matches!(
+        vec_env.step(&[0]),
+        Err(EnvError::WrongActionCount {
+            given: 1,
+            expected: 3
+        })
+    ));
+
+    let mut vec_env = VecEnv::new(&COUNTER, 2, || counter(1, 4)).unwrap();
+    let steps = vec_env.step(&[0, 0]).unwrap();
+    assert!(steps.iter().all(|step| step.reward == 2.0 && !step.done));
+    let steps = vec_env.step(&[0, 0]).unwrap();
+    assert!(steps.iter().all(|step| step.done));
+    // Started over instead of failing with `Finished`
+    let steps = vec_env.step(&[0, 0]).unwrap();
+    assert!(steps.iter().all(|step| step.reward == 2.0 && !step.done));
+}
+
+#[test]
+fn games_that_stop_and_panicking_extractors_return_errors() {
+    let mut env = Env::new(&[0x60, 0x01, 0x50, 0x11], EnvConfig::default()).unwrap();
+    assert!(matches!(
+        env.step(0),
+        Err(EnvError::Run(RunError::UnknownOpcode(UnknownOpcodeError(
+            0x5011
+        ))))
+    ));
+    assert_eq!(env.chip8().pc(), 0x202);
+
+    let mut vec_env = VecEnv::new(&COUNTER, 2, || EnvConfig {
+        reward: Reward::Custom(Box::new(|_| panic!("extractor bug"))),
+        ..EnvConfig::default()
+    })
+    .unwrap();
+    assert!(matches!(vec_env.step(&[0, 0]), Err(EnvError::Panicked)));
+}
+
+#[test]
+fn resets_keep_the_settings_of_the_rom_database() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..");
+    let pong = std::fs::read(root.join("roms/pong.ch8")).unwrap();
+    let mut env = Env::new(&pong, EnvConfig::default()).unwrap();
+    let info = env.rom_info().unwrap().clone();
+    assert_eq!(info.title, "Pong");
+    assert!(info.quirks.vblank, "unlike the defaults");
+
+    play(&mut env, 3, 10);
+    env.reset(4);
+    assert_eq!(env.chip8().quirks(), info.quirks);
+    assert_eq!(
+        env.chip8().tickrate(),
+        info.tickrate.unwrap_or(DEFAULT_TICKRATE)
+    );
+
+    assert!(Env::new(&COUNTER, EnvConfig::default())
+        .unwrap()
+        .rom_info()
+        .is_none());
+}
@@ -1,46 +1,230 @@
-use core::chip8::Chip8;
+mod worker;
 
+use core::bus::Bus;
+use core::chip8::{Chip8, RunError};
+use core::clip::{Clip, ClipOptions, Palette};
+use core::database::{sha1_hex, RomDatabase, RomInfo};
+use core::opcode::disassemble;
+use core::timing::Timing;
+use core::{MEMORY_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH};
 use js_sys::Uint8Array;
 use wasm_bindgen::prelude::*;
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, KeyboardEvent};
+use web_sys::{
+    CanvasRenderingContext2d, HtmlCanvasElement, KeyboardEvent, OffscreenCanvas,
+    OffscreenCanvasRenderingContext2d,
+};
 
 #[wasm_bindgen]
 pub struct Emulator {
     chip8: Chip8,
-    ctx: CanvasRenderingContext2d,
+    renderer: Renderer,
+    database: RomDatabase,
+    rom: Option<Vec<u8>>,
+    rom_info: Option<RomInfo>,
+    rom_hash: Option<String>,
+    paused: bool,
+    fast_forward: bool,
+    clip: Option<Clip>,
 }
 
+/// ROMs shipped in `roms/`, by file name.
+const BUILTIN_ROMS: [(&str, &[u8]); 4] = [
+    ("pong.ch8", include_bytes!("../../roms/pong.ch8")),
+    (
+        "1-chip8-logo.ch8",
+        include_bytes!("../../roms/1-chip8-logo.ch8"),
+    ),
+    ("3-corax+.ch8", include_bytes!("../../roms/3-corax+.ch8")),
+    ("4-flags.ch8", include_bytes!("../../roms/4-flags.ch8")),
+];
+
+/// Frames run per `run_frame` while fast-forwarding.
+const FAST_FORWARD_FRAMES: u32 = 4;
+/// Held down to fast-forward.
+const FAST_FORWARD_KEY: &str = " ";
+/// Frames per second of the timers, and of `run_frame`.
+const FRAME_RATE: u32 = 60;
+
 #[wasm_bindgen]
 impl Emulator {
+    /// Draws on `canvas`, an `HTMLCanvasElement` or an `OffscreenCanvas`. Without one,
+    /// the screen is only available from `pixels`.
     #[wasm_bindgen(constructor)]
-    pub fn new() -> Result<Emulator, JsValue> {
-        let chip8 = Chip8::new();
+    pub fn new(canvas: Option<JsValue>) -> Result<Emulator, JsValue> {
+        let renderer = match canvas {
+            None => Renderer::Headless,
+            Some(canvas) if canvas.is_null() || canvas.is_undefined() => Renderer::Headless,
+            Some(canvas) => Renderer::new(canvas)?,
+        };
 
-        let document = web_sys::window().unwrap().document().unwrap();
-        let canvas = document.get_element_by_id("canvas").unwrap();
-        let canvas: HtmlCanvasElement = canvas
-            .dyn_into::<HtmlCanvasElement>()
-            .map_err(|_| ())
-            .unwrap();
+        Ok(Emulator {
+            chip8: Chip8::new(),
+            renderer,
+            database: RomDatabase::embedded(),
+            rom: None,
+            rom_info: None,
+            rom_hash: None,
+            paused: false,
+            fast_forward: false,
+            clip: None,
+        })
+    }
 
-        let ctx = canvas
-            .get_context("2d")
-            .unwrap()
-            .unwrap()
-            .dyn_into::<CanvasRenderingContext2d>()
-            .unwrap();
+    /// Starts the loaded ROM over, with the settings of the ROM database, like loading it
+    /// again. Also resumes and stops fast-forwarding.
+    #[wasm_bindgen]
+    pub fn reset(&mut self) {
+        match &self.rom {
+            Some(rom) => self
+                .chip8
+                .load_info(rom, self.rom_info.as_ref())
+                .expect("the ROM loaded before"),
+            None => {
+                self.chip8.reset();
+            }
+        }
+        self.paused = false;
+        self.fast_forward = false;
+    }
 
-        Ok(Emulator { chip8, ctx })
+    /// Throws on an instruction that can't run, like an unknown opcode, and pauses with
+    /// PC on it.
+    #[wasm_bindgen]
+    pub fn emulate_cycle(&mut self) -> Result<(), JsValue> {
+        self.chip8.try_emulate_cycle().map_err(|err| self.stop(err))
     }
 
+    /// Runs a frame, or several while fast-forwarding. Does nothing while paused, so the
+    /// timers are frozen too. Throws on an instruction that can't run, and pauses with PC on it.
     #[wasm_bindgen]
-    pub fn reset(&mut self) {
-        self.chip8.reset();
+    pub fn run_frame(&mut self) -> Result<(), JsValue> {
+        if self.paused {
+            return Ok(());
+        }
+        let frames = if self.fast_forward {
+            FAST_FORWARD_FRAMES
+        } else {
+            1
+        };
+        for _ in 0..frames {
+            self.frame()?;
+        }
+        Ok(())
+    }
+
+    fn frame(&mut self) -> Result<(), JsValue> {
+        self.chip8.try_run_frame().map_err(|err| self.stop(err))?;
+        if let Some(clip) = &mut self.clip {
+            clip.capture(self.chip8.get_display());
+        }
+        Ok(())
+    }
+
+    /// Pauses on an instruction the machine can't run, so it can be looked at.
+    fn stop(&mut self, err: RunError) -> JsValue {
+        self.paused = true;
+        self.fast_forward = false;
+        JsValue::from_str(&format!("{} at {:#05x}", err, self.chip8.pc()))
+    }
+
+    #[wasm_bindgen]
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    #[wasm_bindgen]
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    #[wasm_bindgen]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses and runs a single instruction. The timers don't move.
+    #[wasm_bindgen]
+    pub fn step_instruction(&mut self) -> Result<(), JsValue> {
+        self.paused = true;
+        self.emulate_cycle()
+    }
+
+    /// Pauses and runs a single frame, ticking the timers once.
+    #[wasm_bindgen]
+    pub fn step_frame(&mut self) -> Result<(), JsValue> {
+        self.paused = true;
+        self.frame()
+    }
+
+    /// Starts recording a clip of every frame run, in the colors of the ROM database or
+    /// the page's. `scale` is the size of a CHIP-8 pixel, and `frame_skip` the frames
+    /// dropped after each one kept.
+    #[wasm_bindgen]
+    pub fn start_clip(&mut self, scale: usize, frame_skip: u32) -> Result<(), JsValue> {
+        if scale == 0 || scale > 64 {
+            return Err(JsValue::from_str("scale must be between 1 and 64"));
+        }
+        let palette = self
+            .rom_colors()
+            .and_then(|colors| {
+                Some(Palette {
+                    background: parse_color(&colors[0])?,
+                    foreground: parse_color(&colors[1])?,
+                })
+            })
+            .unwrap_or_default();
+        self.clip = Some(Clip::new(ClipOptions {
+            palette,
+            scale,
+            frame_skip,
+        }));
+        Ok(())
     }
 
     #[wasm_bindgen]
-    pub fn emulate_cycle(&mut self) {
-        self.chip8.emulate_cycle();
+    pub fn is_clipping(&self) -> bool {
+        self.clip.is_some()
+    }
+
+    /// Stops recording and returns the clip as `"gif"` or `"y4m"`.
+    #[wasm_bindgen]
+    pub fn stop_clip(&mut self, format: &str) -> Result<Vec<u8>, JsValue> {
+        let clip = self
+            .clip
+            .take()
+            .ok_or_else(|| JsValue::from_str("no clip is being recorded"))?;
+        match format {
+            "gif" => Ok(clip.to_gif()),
+            "y4m" => Ok(clip.to_y4m()),
+            _ => Err(JsValue::from_str("format must be gif or y4m")),
+        }
+    }
+
+    /// Instructions per second, rounded to whole instructions per frame. Switches to
+    /// fixed timing, since COSMAC VIP timing has its own speed.
+    #[wasm_bindgen]
+    pub fn set_speed(&mut self, instructions_per_second: u32) {
+        let tickrate = instructions_per_second.saturating_add(FRAME_RATE / 2) / FRAME_RATE;
+        self.chip8
+            .set_timing(Timing::Fixed)
+            .set_tickrate(tickrate.max(1));
+    }
+
+    /// Instructions per second, or `None` with COSMAC VIP timing.
+    #[wasm_bindgen]
+    pub fn speed(&self) -> Option<u32> {
+        (self.chip8.timing() == Timing::Fixed).then(|| self.chip8.tickrate() * FRAME_RATE)
+    }
+
+    /// Fast-forwarding also happens while the space bar is held.
+    #[wasm_bindgen]
+    pub fn set_fast_forward(&mut self, fast_forward: bool) {
+        self.fast_forward = fast_forward;
+    }
+
+    #[wasm_bindgen]
+    pub fn is_fast_forward(&self) -> bool {
+        self.fast_forward
     }
 
     #[wasm_bindgen]
@@ -50,33 +234,267 @@ impl Emulator {
 
     #[wasm_bindgen]
     pub fn keypress(&mut self, e: KeyboardEvent, pressed: bool) {
-        if let Some(code) = key2btn(&e.key()) {
+        if self.key(&e.key(), pressed) && e.key() == FAST_FORWARD_KEY {
+            // Don't scroll the page
+            e.prevent_default();
+        }
+    }
+
+    /// Like `keypress`, with the `key` of a `KeyboardEvent`, for when the event itself
+    /// isn't at hand. Returns whether the key does anything.
+    #[wasm_bindgen]
+    pub fn key(&mut self, key: &str, pressed: bool) -> bool {
+        if key == FAST_FORWARD_KEY {
+            self.fast_forward = pressed;
+        } else if let Some(code) = key2btn(key) {
             self.chip8.keypress(code, pressed);
+        } else {
+            return false;
         }
+        true
+    }
+
+    #[wasm_bindgen]
+    pub fn load_rom(&mut self, data: Uint8Array) -> Result<(), JsValue> {
+        self.load(&data.to_vec())
+    }
+
+    /// File names of the ROMs built into the module.
+    #[wasm_bindgen]
+    pub fn list_builtin_roms() -> Vec<String> {
+        BUILTIN_ROMS
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    /// Loads one of `list_builtin_roms`, like `load_rom`.
+    #[wasm_bindgen]
+    pub fn load_builtin(&mut self, name: &str) -> Result<(), JsValue> {
+        let (_, rom) = BUILTIN_ROMS
+            .iter()
+            .find(|(builtin, _)| *builtin == name)
+            .ok_or_else(|| JsValue::from_str(&format!("no built-in ROM named {}", name)))?;
+        self.load(rom)
     }
 
+    /// Starts `rom` on a reset machine: nothing carries over from the previous ROM,
+    /// including the speed. A ROM that can't be loaded leaves the machine as it was.
+    fn load(&mut self, rom: &[u8]) -> Result<(), JsValue> {
+        self.rom_info = self
+            .chip8
+            .load_known(rom, &self.database)
+            .map_err(|err| JsValue::from_str(&err.to_string()))?;
+        self.rom = Some(rom.to_vec());
+        self.rom_hash = Some(sha1_hex(rom));
+        self.paused = false;
+        self.fast_forward = false;
+        Ok(())
+    }
+
+    /// SHA-1 of the loaded ROM, in hex.
     #[wasm_bindgen]
-    pub fn load_rom(&mut self, data: Uint8Array) {
-        self.chip8.load(&data.to_vec());
+    pub fn rom_hash(&self) -> Option<String> {
+        self.rom_hash.clone()
     }
 
+    /// The whole machine, including memory and settings.
     #[wasm_bindgen]
-    pub fn draw(&mut self, scale: usize) {
+    pub fn save_state(&self) -> Vec<u8> {
+        self.chip8.save_state()
+    }
+
+    /// Restores a state from `save_state`. The machine is left untouched if it's invalid.
+    #[wasm_bindgen]
+    pub fn load_state(&mut self, state: Uint8Array) -> Result<(), JsValue> {
+        self.chip8
+            .load_state(&state.to_vec())
+            .map_err(|err| JsValue::from_str(&err.to_string()))
+    }
+
+    /// Title of the loaded ROM, if the database knows it.
+    #[wasm_bindgen]
+    pub fn rom_title(&self) -> Option<String> {
+        self.rom_info.as_ref().map(|info| info.title.clone())
+    }
+
+    /// What each CHIP-8 key does in the loaded ROM, e.g. "player1Up: 1, player1Down: 4".
+    #[wasm_bindgen]
+    pub fn rom_keys(&self) -> Option<String> {
+        let info = self.rom_info.as_ref()?;
+        if info.keys.is_empty() {
+            return None;
+        }
+        let keys: Vec<String> = info
+            .keys
+            .iter()
+            .map(|(action, key)| format!("{}: {:X}", action, key))
+            .collect();
+        Some(keys.join(", "))
+    }
+
+    /// Background and pixel colors suggested for the loaded ROM.
+    #[wasm_bindgen]
+    pub fn rom_colors(&self) -> Option<Vec<String>> {
+        let colors = self.rom_info.as_ref()?.colors.as_ref()?;
+        (colors.pixels.len() >= 2).then(|| colors.pixels[..2].to_vec())
+    }
+
+    /// V0 to VF.
+    #[wasm_bindgen]
+    pub fn registers(&self) -> Vec<u8> {
+        (0..16)
+            .map(|register| self.chip8.register(register))
+            .collect()
+    }
+
+    #[wasm_bindgen]
+    pub fn set_register(&mut self, register: u8, value: u8) -> Result<(), JsValue> {
+        if register > 0xF {
+            return Err(JsValue::from_str("register must be between 0 and F"));
+        }
+        self.chip8.set_register(register, value);
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn i(&self) -> u16 {
+        self.chip8.i()
+    }
+
+    #[wasm_bindgen]
+    pub fn set_i(&mut self, i: u16) {
+        self.chip8.set_i(i);
+    }
+
+    #[wasm_bindgen]
+    pub fn pc(&self) -> u16 {
+        self.chip8.pc()
+    }
+
+    #[wasm_bindgen]
+    pub fn set_pc(&mut self, pc: u16) {
+        self.chip8.set_pc(pc);
+    }
+
+    #[wasm_bindgen]
+    pub fn sp(&self) -> usize {
+        self.chip8.stack().len()
+    }
+
+    #[wasm_bindgen]
+    pub fn stack(&self) -> Vec<u16> {
+        self.chip8.stack().to_vec()
+    }
+
+    #[wasm_bindgen]
+    pub fn delay_timer(&self) -> u8 {
+        self.chip8.delay_timer()
+    }
+
+    #[wasm_bindgen]
+    pub fn sound_timer(&self) -> u8 {
+        self.chip8.sound_timer()
+    }
+
+    /// `len` bytes of memory from `start`, cut at the end of memory.
+    #[wasm_bindgen]
+    pub fn memory(&self, start: u16, len: usize) -> Vec<u8> {
+        let bus = self.chip8.bus();
+        (start as usize..MEMORY_SIZE.min((start as usize).saturating_add(len)))
+            .map(|addr| bus.peek(addr as u16))
+            .collect()
+    }
+
+    #[wasm_bindgen]
+    pub fn poke(&mut self, addr: u16, value: u8) -> Result<(), JsValue> {
+        if addr as usize >= MEMORY_SIZE {
+            return Err(JsValue::from_str("address is out of memory"));
+        }
+        self.chip8.poke(addr, value);
+        Ok(())
+    }
+
+    /// Disassembly of `before` instructions before PC, the one at PC and `after` more,
+    /// one line per instruction.
+    #[wasm_bindgen]
+    pub fn disassemble(&self, before: u16, after: usize) -> Vec<String> {
+        let pc = self.chip8.pc();
+        let start = pc.saturating_sub(before.saturating_mul(2));
+        let count = (((pc - start) / 2) as usize + 1).saturating_add(after);
+        disassemble(self.chip8.bus(), start, count)
+            .iter()
+            .map(|line| line.to_string())
+            .collect()
+    }
+
+    /// Fills a `scale`-sized square for every lit pixel, with the current fill style.
+    /// The canvas isn't cleared first.
+    #[wasm_bindgen]
+    pub fn draw(&mut self, scale: usize) -> Result<(), JsValue> {
+        let fill_rect: &dyn Fn(f64, f64, f64, f64) = match &self.renderer {
+            Renderer::Canvas(ctx) => &|x, y, w, h| ctx.fill_rect(x, y, w, h),
+            Renderer::Offscreen(ctx) => &|x, y, w, h| ctx.fill_rect(x, y, w, h),
+            Renderer::Headless => return Err(JsValue::from_str("emulator has no canvas")),
+        };
+        let scale = scale as f64;
+        for (x, y) in self.chip8.get_display().lit_pixels() {
+            fill_rect(x as f64 * scale, y as f64 * scale, scale, scale);
+        }
+        Ok(())
+    }
+
+    /// The screen, one byte per pixel row by row, 1 for lit and 0 for dark.
+    #[wasm_bindgen]
+    pub fn pixels(&self) -> Vec<u8> {
         let display = self.chip8.get_display();
-        for (idx, on) in display.screen.iter().enumerate() {
-            if *on {
-                let (x, y) = display.to_xy(idx);
-                self.ctx.fill_rect(
-                    (x * scale) as f64,
-                    (y * scale) as f64,
-                    scale as f64,
-                    scale as f64,
-                );
-            }
+        (0..SCREEN_HEIGHT)
+            .flat_map(|y| (0..SCREEN_WIDTH).map(move |x| display.pixel(x, y) as u8))
+            .collect()
+    }
+
+    #[wasm_bindgen]
+    pub fn width() -> usize {
+        SCREEN_WIDTH
+    }
+
+    #[wasm_bindgen]
+    pub fn height() -> usize {
+        SCREEN_HEIGHT
+    }
+}
+
+/// Where `draw` paints the screen.
+enum Renderer {
+    Canvas(CanvasRenderingContext2d),
+    Offscreen(OffscreenCanvasRenderingContext2d),
+    Headless,
+}
+
+impl Renderer {
+    fn new(canvas: JsValue) -> Result<Renderer, JsValue> {
+        let no_context = || JsValue::from_str("canvas has no 2d context");
+        if let Some(canvas) = canvas.dyn_ref::<HtmlCanvasElement>() {
+            let ctx = canvas.get_context("2d")?.ok_or_else(no_context)?;
+            return Ok(Renderer::Canvas(ctx.dyn_into()?));
+        }
+        if let Some(canvas) = canvas.dyn_ref::<OffscreenCanvas>() {
+            let ctx = canvas.get_context("2d")?.ok_or_else(no_context)?;
+            return Ok(Renderer::Offscreen(ctx.dyn_into()?));
         }
+        Err(JsValue::from_str(
+            "expected an HTMLCanvasElement, an OffscreenCanvas or nothing",
+        ))
     }
 }
 
+/// A `#rrggbb` color.
+fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#').filter(|hex| hex.len() == 6)?;
+    let channel = |at: usize| u8::from_str_radix(hex.get(at..at + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
 /*
     Keyboard                    Chip-8
     +---+---+---+---+           +---+---+---+---+
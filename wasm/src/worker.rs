@@ -0,0 +1,110 @@
+//! Messages for an `Emulator` running in a Web Worker. The page can't call its methods,
+//! so it posts objects with a `type` and the fields below, and the worker passes them to
+//! `handle_message`:
+//!
+//! - `{ type: "key", key, pressed }`: `key` is the `key` of a `KeyboardEvent`.
+//! - `{ type: "list_builtin_roms" }`: replies with the names for `load_builtin`.
+//! - `{ type: "load_rom", rom }` and `{ type: "load_builtin", name }`: reply with
+//!   `{ title, keys, colors, speed }`. The first three are missing if the ROM database
+//!   doesn't know the ROM, `speed` is missing with COSMAC VIP timing.
+//! - `{ type: "reset" }`: starts the loaded ROM over.
+//! - `{ type: "pause" }`, `{ type: "resume" }`, `{ type: "step_instruction" }` and
+//!   `{ type: "step_frame" }`.
+//! - `{ type: "set_speed", speed }` in instructions per second, and
+//!   `{ type: "set_fast_forward", fast_forward }`.
+//! - `{ type: "save_state" }`: replies with the state as a `Uint8Array`.
+//! - `{ type: "load_state", state }`.
+//!
+//! Other messages reply with `undefined`.
+//!
+//! The worker runs the frames itself, and posts `{ error }` without an `id` when one
+//! stops at an instruction that can't run, which also pauses the emulator.
+
+use crate::Emulator;
+use js_sys::{Object, Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+impl Emulator {
+    /// Runs a message posted by the page. Errors come back as exceptions, for the
+    /// worker to post back.
+    #[wasm_bindgen]
+    pub fn handle_message(&mut self, message: JsValue) -> Result<JsValue, JsValue> {
+        let kind = field(&message, "type")?
+            .as_string()
+            .ok_or_else(|| invalid("type"))?;
+        match kind.as_str() {
+            "key" => {
+                let key = field(&message, "key")?
+                    .as_string()
+                    .ok_or_else(|| invalid("key"))?;
+                let pressed = field(&message, "pressed")?.is_truthy();
+                self.key(&key, pressed);
+            }
+            "list_builtin_roms" => return Ok(Emulator::list_builtin_roms().into()),
+            "load_rom" => {
+                let rom = field(&message, "rom")?
+                    .dyn_into::<Uint8Array>()
+                    .map_err(|_| invalid("rom"))?;
+                self.load_rom(rom)?;
+                return self.rom_summary();
+            }
+            "load_builtin" => {
+                let name = field(&message, "name")?
+                    .as_string()
+                    .ok_or_else(|| invalid("name"))?;
+                self.load_builtin(&name)?;
+                return self.rom_summary();
+            }
+            "reset" => self.reset(),
+            "pause" => self.pause(),
+            "resume" => self.resume(),
+            "step_instruction" => self.step_instruction()?,
+            "step_frame" => self.step_frame()?,
+            "set_speed" => {
+                let speed = field(&message, "speed")?
+                    .as_f64()
+                    .filter(|speed| *speed >= 1.0)
+                    .ok_or_else(|| invalid("speed"))?;
+                self.set_speed(speed as u32);
+            }
+            "set_fast_forward" => {
+                self.set_fast_forward(field(&message, "fast_forward")?.is_truthy());
+            }
+            "save_state" => return Ok(Uint8Array::from(&self.save_state()[..]).into()),
+            "load_state" => {
+                let state = field(&message, "state")?
+                    .dyn_into::<Uint8Array>()
+                    .map_err(|_| invalid("state"))?;
+                self.load_state(state)?;
+            }
+            _ => return Err(JsValue::from_str(&format!("unknown message type {}", kind))),
+        }
+        Ok(JsValue::UNDEFINED)
+    }
+
+    fn rom_summary(&self) -> Result<JsValue, JsValue> {
+        let summary = Object::new();
+        if let Some(title) = self.rom_title() {
+            Reflect::set(&summary, &"title".into(), &title.into())?;
+        }
+        if let Some(keys) = self.rom_keys() {
+            Reflect::set(&summary, &"keys".into(), &keys.into())?;
+        }
+        if let Some(colors) = self.rom_colors() {
+            Reflect::set(&summary, &"colors".into(), &colors.into())?;
+        }
+        if let Some(speed) = self.speed() {
+            Reflect::set(&summary, &"speed".into(), &speed.into())?;
+        }
+        Ok(summary.into())
+    }
+}
+
+fn field(message: &JsValue, name: &str) -> Result<JsValue, JsValue> {
+    Reflect::get(message, &JsValue::from_str(name))
+}
+
+fn invalid(name: &str) -> JsValue {
+    JsValue::from_str(&format!("message has no valid {}", name))
+}
@@ -0,0 +1,42 @@
+// Records the screen into an animated GIF or a Y4M video, offered as a download.
+
+export function createClipControls(chip8, root) {
+  const recordButton = root.querySelector("#clip-record");
+  const formatSelect = root.querySelector("#clip-format");
+  const scaleInput = root.querySelector("#clip-scale");
+  const skipInput = root.querySelector("#clip-skip");
+
+  for (const input of [scaleInput, skipInput]) {
+    input.addEventListener("keydown", (e) => e.stopPropagation());
+  }
+
+  const showRecording = () => {
+    const recording = chip8.is_clipping();
+    recordButton.textContent = recording ? "Stop and download" : "Record";
+    formatSelect.disabled = scaleInput.disabled = skipInput.disabled = recording;
+  };
+
+  recordButton.addEventListener("click", () => {
+    try {
+      if (chip8.is_clipping()) {
+        const format = formatSelect.value;
+        const clip = chip8.stop_clip(format);
+        const type = format === "gif" ? "image/gif" : "video/x-yuv4mpeg";
+        const link = document.createElement("a");
+        link.href = URL.createObjectURL(new Blob([clip], { type }));
+        link.download = `${chip8.rom_title() ?? "chip8"}.${format}`;
+        link.click();
+        URL.revokeObjectURL(link.href);
+      } else {
+        const scale = parseInt(scaleInput.value, 10) || 1;
+        const frameSkip = parseInt(skipInput.value, 10) || 0;
+        chip8.start_clip(scale, frameSkip);
+      }
+    } catch (err) {
+      alert(err);
+    }
+    showRecording();
+  });
+
+  showRecording();
+}
@@ -0,0 +1,149 @@
+// Live view of the machine: registers, stack, disassembly around PC and a page of memory.
+// Registers and memory bytes can be edited, in hex.
+
+const PAGE_SIZE = 256;
+const PAGES = 4096 / PAGE_SIZE;
+
+const hex = (value, digits) => value.toString(16).toUpperCase().padStart(digits, "0");
+
+// An input showing a hex value, which calls `write` with the new value on edit
+function hexInput(digits, write) {
+  const input = document.createElement("input");
+  input.className =
+    "w-full bg-zinc-800 text-center font-mono tabular-nums focus:bg-zinc-700 focus:outline-none";
+  input.maxLength = digits;
+  input.addEventListener("change", () => {
+    const value = parseInt(input.value, 16);
+    if (!Number.isNaN(value) && value < 16 ** digits) {
+      try {
+        write(value);
+      } catch (err) {
+        alert(err);
+      }
+    }
+    input.blur();
+  });
+  input.addEventListener("keydown", (e) => {
+    // Keep the emulator from seeing the digits typed
+    e.stopPropagation();
+    if (e.key === "Enter") {
+      input.blur();
+    }
+  });
+  return input;
+}
+
+// Sets the shown value, unless it's being edited
+function show(input, value, digits) {
+  const text = hex(value, digits);
+  if (document.activeElement !== input && input.value !== text) {
+    input.value = text;
+  }
+}
+
+function labeled(label, element) {
+  const cell = document.createElement("label");
+  cell.className = "flex flex-col text-xs text-zinc-400";
+  cell.append(label, element);
+  return cell;
+}
+
+export function createDebugPanel(chip8, root) {
+  const registers = root.querySelector("#registers");
+  const stack = root.querySelector("#stack");
+  const disassembly = root.querySelector("#disassembly");
+  const memory = root.querySelector("#memory");
+  const pageInput = root.querySelector("#memory-page");
+  let page = 0x2;
+
+  const vInputs = [];
+  for (let register = 0; register < 16; register++) {
+    const input = hexInput(2, (value) => chip8.set_register(register, value));
+    vInputs.push(input);
+    registers.append(labeled(`V${hex(register, 1)}`, input));
+  }
+  const iInput = hexInput(3, (value) => chip8.set_i(value));
+  const pcInput = hexInput(3, (value) => chip8.set_pc(value));
+  registers.append(labeled("I", iInput), labeled("PC", pcInput));
+  const readOnly = {};
+  for (const name of ["SP", "DT", "ST"]) {
+    readOnly[name] = document.createElement("span");
+    readOnly[name].className = "font-mono tabular-nums text-zinc-200";
+    registers.append(labeled(name, readOnly[name]));
+  }
+
+  const byteInputs = [];
+  for (let row = 0; row < PAGE_SIZE / 16; row++) {
+    const tr = document.createElement("tr");
+    const address = document.createElement("td");
+    address.className = "pr-2 font-mono text-zinc-400";
+    tr.append(address);
+    for (let column = 0; column < 16; column++) {
+      const offset = row * 16 + column;
+      const input = hexInput(2, (value) => chip8.poke(page * PAGE_SIZE + offset, value));
+      input.className += " w-7";
+      const td = document.createElement("td");
+      td.append(input);
+      tr.append(td);
+      byteInputs.push(input);
+    }
+    memory.append(tr);
+  }
+
+  const showPage = () => {
+    pageInput.value = hex(page, 1);
+    memory.querySelectorAll("tr").forEach((tr, row) => {
+      tr.firstChild.textContent = hex(page * PAGE_SIZE + row * 16, 3);
+    });
+  };
+  pageInput.addEventListener("keydown", (e) => e.stopPropagation());
+  pageInput.addEventListener("change", () => {
+    const value = parseInt(pageInput.value, 16);
+    if (!Number.isNaN(value) && value < PAGES) {
+      page = value;
+    }
+    showPage();
+    update();
+  });
+  root.querySelector("#memory-prev").addEventListener("click", () => {
+    page = (page + PAGES - 1) % PAGES;
+    showPage();
+    update();
+  });
+  root.querySelector("#memory-next").addEventListener("click", () => {
+    page = (page + 1) % PAGES;
+    showPage();
+    update();
+  });
+  showPage();
+
+  const update = () => {
+    chip8.registers().forEach((value, register) => show(vInputs[register], value, 2));
+    show(iInput, chip8.i(), 3);
+    show(pcInput, chip8.pc(), 3);
+    readOnly.SP.textContent = chip8.sp();
+    readOnly.DT.textContent = hex(chip8.delay_timer(), 2);
+    readOnly.ST.textContent = hex(chip8.sound_timer(), 2);
+
+    stack.textContent = Array.from(chip8.stack(), (addr) => hex(addr, 3)).join("\n") || "empty";
+
+    const pc = `0x${hex(chip8.pc(), 3)}`;
+    disassembly.replaceChildren(
+      ...chip8.disassemble(8, 8).map((line) => {
+        const div = document.createElement("div");
+        div.textContent = line;
+        if (line.startsWith(pc)) {
+          div.className = "bg-zinc-700 text-zinc-50";
+        }
+        return div;
+      })
+    );
+
+    chip8
+      .memory(page * PAGE_SIZE, PAGE_SIZE)
+      .forEach((value, offset) => show(byteInputs[offset], value, 2));
+  };
+
+  update();
+  return { update };
+}
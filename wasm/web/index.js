@@ -1,4 +1,7 @@
 import init, { Emulator } from "./wasm.js";
+import { createDebugPanel } from "./debug.js";
+import { createStateControls } from "./states.js";
+import { createClipControls } from "./clip.js";
 
 const WIDTH = 64;
 const HEIGHT = 32;
@@ -7,10 +10,17 @@ const SCALE = 15;
 const BG_COLOR = "#09090b";
 const PRIMARY_COLOR = "#e4e4e7";
 
-const TICKS_PER_FRAME = 10;
 let last_tick = 0;
+let bgColor = BG_COLOR;
+let primaryColor = PRIMARY_COLOR;
 
 const romInput = document.getElementById("rom");
+const builtinSelect = document.getElementById("builtin-rom");
+const romInfo = document.getElementById("rom-info");
+const debugPanel = document.getElementById("debug");
+const pauseButton = document.getElementById("pause");
+const speedInput = document.getElementById("speed");
+const statesPanel = document.getElementById("states");
 
 const canvas = document.getElementById("canvas");
 canvas.width = WIDTH * SCALE;
@@ -24,7 +34,56 @@ const main = async () => {
   // Wasm module is initialized here
   await init();
 
-  let chip8 = new Emulator();
+  let chip8 = new Emulator(canvas);
+  const debug = createDebugPanel(chip8, debugPanel);
+  // A state holds the whole memory, so it runs even without loading its ROM first
+  const states = createStateControls(chip8, statesPanel, () => {
+    showRunState(chip8);
+    start(chip8, debug);
+  });
+  createClipControls(chip8, document.getElementById("clip"));
+  showRunState(chip8);
+
+  pauseButton.addEventListener("click", () => {
+    if (chip8.is_paused()) {
+      chip8.resume();
+    } else {
+      chip8.pause();
+    }
+    showRunState(chip8);
+  });
+
+  document.getElementById("step-instruction").addEventListener("click", () => {
+    try {
+      chip8.step_instruction();
+    } catch (err) {
+      alert(err);
+    }
+    showRunState(chip8);
+  });
+
+  document.getElementById("step-frame").addEventListener("click", () => {
+    try {
+      chip8.step_frame();
+    } catch (err) {
+      alert(err);
+    }
+    showRunState(chip8);
+  });
+
+  // Space fast-forwards, it mustn't also click the focused button
+  for (const button of document.querySelectorAll("button")) {
+    button.addEventListener("click", () => button.blur());
+  }
+
+  speedInput.addEventListener("keydown", (e) => e.stopPropagation());
+  speedInput.addEventListener("change", () => {
+    const speed = parseInt(speedInput.value, 10);
+    if (speed > 0) {
+      chip8.set_speed(speed);
+    }
+    showRunState(chip8);
+  });
 
   document.addEventListener("keydown", (e) => {
     chip8.keypress(e, true);
@@ -34,49 +93,127 @@ const main = async () => {
     chip8.keypress(e, false);
   });
 
+  // `load` puts the ROM on a reset machine, from a file or the built-in ones
+  const loadRom = (load) => {
+    try {
+      load();
+    } catch (err) {
+      alert(err);
+      return;
+    }
+    showRomInfo(chip8);
+    showRunState(chip8);
+    states.showSlots();
+    start(chip8, debug);
+  };
+  const loadFile = async (file) => {
+    const rom = new Uint8Array(await file.arrayBuffer());
+    loadRom(() => chip8.load_rom(rom));
+  };
+
+  for (const name of Emulator.list_builtin_roms()) {
+    const option = document.createElement("option");
+    option.value = option.textContent = name;
+    builtinSelect.append(option);
+  }
+  builtinSelect.addEventListener("change", () => {
+    const name = builtinSelect.value;
+    if (name) {
+      loadRom(() => chip8.load_builtin(name));
+    }
+    builtinSelect.blur();
+  });
+
   romInput.addEventListener(
     "change",
     (e) => {
-      // Stop previous game from rendering, if one exists
-      if (last_tick != 0) {
-        window.cancelAnimationFrame(last_tick);
-      }
-
       let file = e.target.files[0];
-      if (!file) {
-        return;
+      if (file) {
+        builtinSelect.value = "";
+        loadFile(file);
       }
-
-      let fileReader = new FileReader();
-      fileReader.onload = () => {
-        const buffer = fileReader.result;
-        const rom = new Uint8Array(buffer);
-
-        chip8.reset();
-        chip8.load_rom(rom);
-        gameloop(chip8);
-      };
-
-      fileReader.readAsArrayBuffer(file);
     },
     false
   );
+
+  canvas.addEventListener("dragover", (e) => e.preventDefault());
+  canvas.addEventListener("drop", (e) => {
+    e.preventDefault();
+    const file = e.dataTransfer.files[0];
+    if (file) {
+      builtinSelect.value = "";
+      loadFile(file);
+    }
+  });
+
+  // ?rom= names a bundled ROM, or a URL to fetch it from
+  const romParam = new URLSearchParams(window.location.search).get("rom");
+  if (romParam) {
+    if (Emulator.list_builtin_roms().includes(romParam)) {
+      builtinSelect.value = romParam;
+      loadRom(() => chip8.load_builtin(romParam));
+    } else {
+      try {
+        const response = await fetch(romParam);
+        if (!response.ok) {
+          throw new Error(`HTTP ${response.status}`);
+        }
+        const rom = new Uint8Array(await response.arrayBuffer());
+        loadRom(() => chip8.load_rom(rom));
+      } catch (err) {
+        alert(`Couldn't fetch ${romParam}: ${err.message}`);
+      }
+    }
+  }
 };
 
-function gameloop(chip8) {
-  for (let i = 0; i < TICKS_PER_FRAME; i++) {
-    chip8.emulate_cycle();
+// Show what the ROM database knows about the loaded ROM, and use its colors
+function showRomInfo(chip8) {
+  const title = chip8.rom_title();
+  const keys = chip8.rom_keys();
+  romInfo.textContent = [title, keys].filter(Boolean).join(" - ");
+
+  const colors = chip8.rom_colors();
+  [bgColor, primaryColor] = colors ?? [BG_COLOR, PRIMARY_COLOR];
+}
+
+// Reflect pausing and the speed, which the ROM database may have set, in the controls
+function showRunState(chip8) {
+  pauseButton.textContent = chip8.is_paused() ? "Resume" : "Pause";
+  const speed = chip8.speed();
+  speedInput.value = speed ?? "";
+  speedInput.placeholder = speed === undefined ? "VIP" : "";
+}
+
+function start(chip8, debug) {
+  // Stop previous game from rendering, if one exists
+  if (last_tick != 0) {
+    window.cancelAnimationFrame(last_tick);
   }
+  gameloop(chip8, debug);
+}
 
-  chip8.tick_timers();
+function gameloop(chip8, debug) {
+  // Does nothing while paused, and runs several frames while fast-forwarding
+  try {
+    chip8.run_frame();
+  } catch (err) {
+    // Paused on the instruction, for the debugger
+    showRunState(chip8);
+    alert(err);
+  }
 
-  ctx.fillStyle = BG_COLOR;
+  ctx.fillStyle = bgColor;
   ctx.fillRect(0, 0, WIDTH * SCALE, HEIGHT * SCALE);
 
-  ctx.fillStyle = PRIMARY_COLOR;
+  ctx.fillStyle = primaryColor;
   chip8.draw(SCALE);
 
-  last_tick = window.requestAnimationFrame(() => gameloop(chip8));
+  if (debugPanel.open) {
+    debug.update();
+  }
+
+  last_tick = window.requestAnimationFrame(() => gameloop(chip8, debug));
 }
 
 main().catch(console.error);
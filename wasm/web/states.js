@@ -0,0 +1,92 @@
+// Save states: quick-save slots in localStorage, keyed by the SHA-1 of the ROM, and files
+// to move states between machines.
+
+const SLOTS = 4;
+
+const slotKey = (hash, slot) => `chip8:state:${hash}:${slot}`;
+
+function toBase64(bytes) {
+  let binary = "";
+  for (const byte of bytes) {
+    binary += String.fromCharCode(byte);
+  }
+  return btoa(binary);
+}
+
+function fromBase64(text) {
+  return Uint8Array.from(atob(text), (char) => char.charCodeAt(0));
+}
+
+// `onLoad` is called after a state is loaded, to get the game running
+export function createStateControls(chip8, root, onLoad) {
+  const slotSelect = root.querySelector("#state-slot");
+  const saveButton = root.querySelector("#state-save");
+  const loadButton = root.querySelector("#state-load");
+  const downloadButton = root.querySelector("#state-download");
+  const uploadInput = root.querySelector("#state-upload");
+
+  const load = (state) => {
+    try {
+      chip8.load_state(state);
+    } catch (err) {
+      alert(err);
+      return;
+    }
+    onLoad();
+  };
+
+  // Slot names show which ones hold a state for this ROM
+  const showSlots = () => {
+    const hash = chip8.rom_hash();
+    const selected = slotSelect.value || "1";
+    slotSelect.replaceChildren();
+    for (let slot = 1; slot <= SLOTS; slot++) {
+      const option = document.createElement("option");
+      option.value = slot;
+      const saved = hash && localStorage.getItem(slotKey(hash, slot)) !== null;
+      option.textContent = `Slot ${slot}${saved ? " (saved)" : ""}`;
+      slotSelect.append(option);
+    }
+    slotSelect.value = selected;
+    slotSelect.disabled = saveButton.disabled = loadButton.disabled = !hash;
+  };
+
+  saveButton.addEventListener("click", () => {
+    const key = slotKey(chip8.rom_hash(), slotSelect.value);
+    try {
+      localStorage.setItem(key, toBase64(chip8.save_state()));
+    } catch (err) {
+      alert(`Couldn't save the state: ${err}`);
+    }
+    showSlots();
+  });
+
+  loadButton.addEventListener("click", () => {
+    const saved = localStorage.getItem(slotKey(chip8.rom_hash(), slotSelect.value));
+    if (saved === null) {
+      alert("This slot is empty");
+      return;
+    }
+    load(fromBase64(saved));
+  });
+
+  downloadButton.addEventListener("click", () => {
+    const blob = new Blob([chip8.save_state()], { type: "application/octet-stream" });
+    const link = document.createElement("a");
+    link.href = URL.createObjectURL(blob);
+    link.download = `${chip8.rom_title() ?? chip8.rom_hash() ?? "chip8"}.state`;
+    link.click();
+    URL.revokeObjectURL(link.href);
+  });
+
+  uploadInput.addEventListener("change", async () => {
+    const file = uploadInput.files[0];
+    if (file) {
+      load(new Uint8Array(await file.arrayBuffer()));
+    }
+    uploadInput.value = "";
+  });
+
+  showSlots();
+  return { showSlots };
+}
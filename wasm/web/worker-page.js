@@ -0,0 +1,119 @@
+// The page side of worker.js: forwards input and controls as messages, the worker
+// emulates and draws.
+const WIDTH = 64;
+const HEIGHT = 32;
+const SCALE = 15;
+
+const romInput = document.getElementById("rom");
+const builtinSelect = document.getElementById("builtin-rom");
+const romInfo = document.getElementById("rom-info");
+const pauseButton = document.getElementById("pause");
+const speedInput = document.getElementById("speed");
+
+const canvas = document.getElementById("canvas");
+canvas.width = WIDTH * SCALE;
+canvas.height = HEIGHT * SCALE;
+
+const worker = new Worker("worker.js", { type: "module" });
+const offscreen = canvas.transferControlToOffscreen();
+worker.postMessage({ type: "init", canvas: offscreen, scale: SCALE }, [offscreen]);
+
+// Replies are matched to requests by id
+let nextId = 0;
+const pending = new Map();
+worker.addEventListener("message", ({ data }) => {
+  if (data.id === undefined) {
+    // A frame stopped at an instruction that can't run, and the worker paused
+    paused = true;
+    pauseButton.textContent = "Resume";
+    alert(data.error);
+    return;
+  }
+  const { resolve, reject } = pending.get(data.id);
+  pending.delete(data.id);
+  if ("error" in data) {
+    reject(data.error);
+  } else {
+    resolve(data.result);
+  }
+});
+
+function request(message, transfer = []) {
+  const id = nextId++;
+  worker.postMessage({ id, ...message }, transfer);
+  return new Promise((resolve, reject) => pending.set(id, { resolve, reject }));
+}
+
+let paused = false;
+
+async function loadRom(message, transfer) {
+  let info;
+  try {
+    info = await request(message, transfer);
+  } catch (err) {
+    alert(err);
+    return;
+  }
+  romInfo.textContent = [info.title, info.keys].filter(Boolean).join(" - ");
+  paused = false;
+  pauseButton.textContent = "Pause";
+  speedInput.value = info.speed ?? "";
+}
+
+for (const name of await request({ type: "list_builtin_roms" })) {
+  const option = document.createElement("option");
+  option.value = option.textContent = name;
+  builtinSelect.append(option);
+}
+builtinSelect.addEventListener("change", () => {
+  if (builtinSelect.value) {
+    loadRom({ type: "load_builtin", name: builtinSelect.value });
+  }
+  builtinSelect.blur();
+});
+
+romInput.addEventListener("change", async () => {
+  const file = romInput.files[0];
+  if (file) {
+    builtinSelect.value = "";
+    const rom = new Uint8Array(await file.arrayBuffer());
+    loadRom({ type: "load_rom", rom }, [rom.buffer]);
+  }
+});
+
+pauseButton.addEventListener("click", () => {
+  paused = !paused;
+  worker.postMessage({ type: paused ? "pause" : "resume" });
+  pauseButton.textContent = paused ? "Resume" : "Pause";
+  pauseButton.blur();
+});
+
+document.getElementById("step-frame").addEventListener("click", (e) => {
+  request({ type: "step_frame" }).catch(alert);
+  paused = true;
+  pauseButton.textContent = "Resume";
+  e.currentTarget.blur();
+});
+
+speedInput.addEventListener("keydown", (e) => e.stopPropagation());
+speedInput.addEventListener("change", () => {
+  const speed = parseInt(speedInput.value, 10);
+  if (speed > 0) {
+    request({ type: "set_speed", speed }).catch(alert);
+  }
+});
+
+for (const [event, pressed] of [
+  ["keydown", true],
+  ["keyup", false],
+]) {
+  document.addEventListener(event, (e) => {
+    // Space fast-forwards, don't scroll the page
+    if (e.key === " ") {
+      e.preventDefault();
+    }
+    if (!e.repeat) {
+      worker.postMessage({ type: "key", key: e.key, pressed });
+    }
+  });
+}
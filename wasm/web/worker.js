@@ -0,0 +1,68 @@
+// Runs an Emulator in a dedicated worker, drawing on the OffscreenCanvas sent by the page.
+// The first message is { type: "init", canvas, scale }, the others are passed to
+// `Emulator.handle_message`. Messages with an `id` get a reply with the same `id` and
+// either a `result` or an `error`.
+import init, { Emulator } from "./wasm.js";
+
+const BG_COLOR = "#09090b";
+const PRIMARY_COLOR = "#e4e4e7";
+
+// Workers don't always have requestAnimationFrame
+const nextFrame = self.requestAnimationFrame
+  ? (callback) => self.requestAnimationFrame(callback)
+  : (callback) => setTimeout(callback, 1000 / 60);
+
+const ready = init();
+let chip8;
+let ctx;
+let scale;
+let bgColor = BG_COLOR;
+let primaryColor = PRIMARY_COLOR;
+
+function start({ canvas, scale: canvasScale }) {
+  chip8 = new Emulator(canvas);
+  // The same context the emulator draws with
+  ctx = canvas.getContext("2d");
+  scale = canvasScale;
+  gameloop();
+}
+
+function gameloop() {
+  // Fast-forwarding only slows this worker down, not the page
+  try {
+    chip8.run_frame();
+  } catch (err) {
+    // The emulator paused itself, the page has to know
+    self.postMessage({ error: String(err) });
+  }
+
+  ctx.fillStyle = bgColor;
+  ctx.fillRect(0, 0, ctx.canvas.width, ctx.canvas.height);
+  ctx.fillStyle = primaryColor;
+  chip8.draw(scale);
+
+  nextFrame(gameloop);
+}
+
+self.addEventListener("message", async (e) => {
+  await ready;
+  const { id, ...message } = e.data;
+  const reply = { id };
+  try {
+    if (message.type === "init") {
+      start(message);
+    } else {
+      reply.result = chip8.handle_message(message);
+      if (message.type === "load_rom" || message.type === "load_builtin") {
+        [bgColor, primaryColor] = reply.result.colors ?? [BG_COLOR, PRIMARY_COLOR];
+      }
+    }
+  } catch (err) {
+    reply.error = String(err);
+  }
+  if (id !== undefined) {
+    self.postMessage(reply);
+  } else if (reply.error) {
+    console.error(reply.error);
+  }
+});